@@ -1,7 +1,32 @@
-use std::fmt::Display;
+use std::{collections::BTreeMap, fmt::Display, path::Path};
+
+mod assembler;
+mod disassembler;
+mod error;
+mod expr;
+mod linker;
+mod listing;
+mod object;
+mod symbols;
+
+pub use disassembler::{
+    disassemble, disassemble_flow, disassemble_mem, Disassembled, Disassembly, FlowDisassembly,
+};
+pub use error::{AssembleError, AssembleErrors, ErrorKind, Span};
+pub use linker::{
+    link, LinkError, Linked, MemoryArea, MemoryConfig, Placement, SegmentKind, SegmentPlacement,
+};
+pub use listing::{Listing, ListingLine};
+pub use object::{
+    Export, Import, Object, ObjectSegment, Relocation, RelocationKind, RelocationTarget,
+};
+pub use symbols::{
+    read_symbols, write_symbols, Symbol, SymbolError, SymbolFormat, SymbolKind, SymbolScope,
+};
 
 /// Address code
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AddrCode {
     /// Required cycles
     pub cycles: u32,
@@ -11,6 +36,7 @@ pub struct AddrCode {
 
 /// Addressing modes
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AddrMode {
     /// Accumulator
     Accumulator(AddrCode),
@@ -60,8 +86,42 @@ impl Display for AddrMode {
     }
 }
 
+impl AddrMode {
+    /// Get the opcode and cycle information of the addressing mode
+    pub fn code(&self) -> &AddrCode {
+        match self {
+            AddrMode::Accumulator(code) => code,
+            AddrMode::Immediate(code) => code,
+            AddrMode::ZeroPage(code) => code,
+            AddrMode::ZeroPageX(code) => code,
+            AddrMode::ZeroPageY(code) => code,
+            AddrMode::Absolute(code) => code,
+            AddrMode::AbsoluteX(code) => code,
+            AddrMode::AbsoluteY(code) => code,
+            AddrMode::Indirect(code) => code,
+            AddrMode::IndirectX(code) => code,
+            AddrMode::IndirectY(code) => code,
+            AddrMode::Relative(code) => code,
+            AddrMode::Implied(code) => code,
+        }
+    }
+
+    /// Size of an instruction using this addressing mode, opcode included
+    pub fn size(&self) -> u16 {
+        match self {
+            AddrMode::Accumulator(_) | AddrMode::Implied(_) => 1,
+            AddrMode::Absolute(_)
+            | AddrMode::AbsoluteX(_)
+            | AddrMode::AbsoluteY(_)
+            | AddrMode::Indirect(_) => 3,
+            _ => 2,
+        }
+    }
+}
+
 /// Instructions
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instructions {
     /// ADC
     ADC(AddrMode),
@@ -179,68 +239,140 @@ pub enum Instructions {
 
 impl Display for Instructions {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Instructions::ADC(a_mode) => write!(f, "ADC : {}", a_mode),
-            Instructions::AND(a_mode) => write!(f, "AND : {}", a_mode),
-            Instructions::ASL(a_mode) => write!(f, "ASL : {}", a_mode),
-            Instructions::BCC(a_mode) => write!(f, "BCC : {}", a_mode),
-            Instructions::BCS(a_mode) => write!(f, "BCS : {}", a_mode),
-            Instructions::BEQ(a_mode) => write!(f, "BEQ : {}", a_mode),
-            Instructions::BIT(a_mode) => write!(f, "BIT : {}", a_mode),
-            Instructions::BMI(a_mode) => write!(f, "BMI : {}", a_mode),
-            Instructions::BNE(a_mode) => write!(f, "BNE : {}", a_mode),
-            Instructions::BPL(a_mode) => write!(f, "BPL : {}", a_mode),
-            Instructions::BRK(a_mode) => write!(f, "BRK : {}", a_mode),
-            Instructions::BVC(a_mode) => write!(f, "BVC : {}", a_mode),
-            Instructions::BVS(a_mode) => write!(f, "BVS : {}", a_mode),
-            Instructions::CLC(a_mode) => write!(f, "CLC : {}", a_mode),
-            Instructions::CLD(a_mode) => write!(f, "CLD : {}", a_mode),
-            Instructions::CLI(a_mode) => write!(f, "CLI : {}", a_mode),
-            Instructions::CLV(a_mode) => write!(f, "CLV : {}", a_mode),
-            Instructions::CMP(a_mode) => write!(f, "CMP : {}", a_mode),
-            Instructions::CPX(a_mode) => write!(f, "CPX : {}", a_mode),
-            Instructions::CPY(a_mode) => write!(f, "CPY : {}", a_mode),
-            Instructions::DEC(a_mode) => write!(f, "DEC : {}", a_mode),
-            Instructions::DEX(a_mode) => write!(f, "DEX : {}", a_mode),
-            Instructions::DEY(a_mode) => write!(f, "DEY : {}", a_mode),
-            Instructions::EOR(a_mode) => write!(f, "EOR : {}", a_mode),
-            Instructions::INC(a_mode) => write!(f, "INC : {}", a_mode),
-            Instructions::INX(a_mode) => write!(f, "INX : {}", a_mode),
-            Instructions::INY(a_mode) => write!(f, "INY : {}", a_mode),
-            Instructions::JMP(a_mode) => write!(f, "JMP : {}", a_mode),
-            Instructions::JSR(a_mode) => write!(f, "JSR : {}", a_mode),
-            Instructions::LDA(a_mode) => write!(f, "LDA : {}", a_mode),
-            Instructions::LDX(a_mode) => write!(f, "LDX : {}", a_mode),
-            Instructions::LDY(a_mode) => write!(f, "LDY : {}", a_mode),
-            Instructions::LSR(a_mode) => write!(f, "LSR : {}", a_mode),
-            Instructions::NOP(a_mode) => write!(f, "NOP : {}", a_mode),
-            Instructions::ORA(a_mode) => write!(f, "ORA : {}", a_mode),
-            Instructions::PHA(a_mode) => write!(f, "PHA : {}", a_mode),
-            Instructions::PHP(a_mode) => write!(f, "PHP : {}", a_mode),
-            Instructions::PLA(a_mode) => write!(f, "PLA : {}", a_mode),
-            Instructions::PLP(a_mode) => write!(f, "PLP : {}", a_mode),
-            Instructions::ROL(a_mode) => write!(f, "ROL : {}", a_mode),
-            Instructions::ROR(a_mode) => write!(f, "ROR : {}", a_mode),
-            Instructions::RTI(a_mode) => write!(f, "RTI : {}", a_mode),
-            Instructions::RTS(a_mode) => write!(f, "RTS : {}", a_mode),
-            Instructions::SBC(a_mode) => write!(f, "SBC : {}", a_mode),
-            Instructions::SEC(a_mode) => write!(f, "SEC : {}", a_mode),
-            Instructions::SED(a_mode) => write!(f, "SED : {}", a_mode),
-            Instructions::SEI(a_mode) => write!(f, "SEI : {}", a_mode),
-            Instructions::STA(a_mode) => write!(f, "STA : {}", a_mode),
-            Instructions::STX(a_mode) => write!(f, "STX : {}", a_mode),
-            Instructions::STY(a_mode) => write!(f, "STY : {}", a_mode),
-            Instructions::TAX(a_mode) => write!(f, "TAX : {}", a_mode),
-            Instructions::TAY(a_mode) => write!(f, "TAY : {}", a_mode),
-            Instructions::TSX(a_mode) => write!(f, "TSX : {}", a_mode),
-            Instructions::TXA(a_mode) => write!(f, "TXA : {}", a_mode),
-            Instructions::TXS(a_mode) => write!(f, "TXS : {}", a_mode),
-            Instructions::TYA(a_mode) => write!(f, "TYA : {}", a_mode),
-        }
+        write!(f, "{} : {}", self.mnemonic(), self.addr_mode())
     }
 }
 
 impl Instructions {
+    /// Get the mnemonic of the instruction
+    /// ## Example
+    /// ```
+    /// use rusty_6502::asm;
+    /// assert_eq!(asm::Instructions::resolve(0xA9).mnemonic(), "LDA");
+    /// ```
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instructions::ADC(_) => "ADC",
+            Instructions::AND(_) => "AND",
+            Instructions::ASL(_) => "ASL",
+            Instructions::BCC(_) => "BCC",
+            Instructions::BCS(_) => "BCS",
+            Instructions::BEQ(_) => "BEQ",
+            Instructions::BIT(_) => "BIT",
+            Instructions::BMI(_) => "BMI",
+            Instructions::BNE(_) => "BNE",
+            Instructions::BPL(_) => "BPL",
+            Instructions::BRK(_) => "BRK",
+            Instructions::BVC(_) => "BVC",
+            Instructions::BVS(_) => "BVS",
+            Instructions::CLC(_) => "CLC",
+            Instructions::CLD(_) => "CLD",
+            Instructions::CLI(_) => "CLI",
+            Instructions::CLV(_) => "CLV",
+            Instructions::CMP(_) => "CMP",
+            Instructions::CPX(_) => "CPX",
+            Instructions::CPY(_) => "CPY",
+            Instructions::DEC(_) => "DEC",
+            Instructions::DEX(_) => "DEX",
+            Instructions::DEY(_) => "DEY",
+            Instructions::EOR(_) => "EOR",
+            Instructions::INC(_) => "INC",
+            Instructions::INX(_) => "INX",
+            Instructions::INY(_) => "INY",
+            Instructions::JMP(_) => "JMP",
+            Instructions::JSR(_) => "JSR",
+            Instructions::LDA(_) => "LDA",
+            Instructions::LDX(_) => "LDX",
+            Instructions::LDY(_) => "LDY",
+            Instructions::LSR(_) => "LSR",
+            Instructions::NOP(_) => "NOP",
+            Instructions::ORA(_) => "ORA",
+            Instructions::PHA(_) => "PHA",
+            Instructions::PHP(_) => "PHP",
+            Instructions::PLA(_) => "PLA",
+            Instructions::PLP(_) => "PLP",
+            Instructions::ROL(_) => "ROL",
+            Instructions::ROR(_) => "ROR",
+            Instructions::RTI(_) => "RTI",
+            Instructions::RTS(_) => "RTS",
+            Instructions::SBC(_) => "SBC",
+            Instructions::SEC(_) => "SEC",
+            Instructions::SED(_) => "SED",
+            Instructions::SEI(_) => "SEI",
+            Instructions::STA(_) => "STA",
+            Instructions::STX(_) => "STX",
+            Instructions::STY(_) => "STY",
+            Instructions::TAX(_) => "TAX",
+            Instructions::TAY(_) => "TAY",
+            Instructions::TSX(_) => "TSX",
+            Instructions::TXA(_) => "TXA",
+            Instructions::TXS(_) => "TXS",
+            Instructions::TYA(_) => "TYA",
+        }
+    }
+
+    /// Get the addressing mode of the instruction
+    pub fn addr_mode(&self) -> &AddrMode {
+        match self {
+            Instructions::ADC(a_mode) => a_mode,
+            Instructions::AND(a_mode) => a_mode,
+            Instructions::ASL(a_mode) => a_mode,
+            Instructions::BCC(a_mode) => a_mode,
+            Instructions::BCS(a_mode) => a_mode,
+            Instructions::BEQ(a_mode) => a_mode,
+            Instructions::BIT(a_mode) => a_mode,
+            Instructions::BMI(a_mode) => a_mode,
+            Instructions::BNE(a_mode) => a_mode,
+            Instructions::BPL(a_mode) => a_mode,
+            Instructions::BRK(a_mode) => a_mode,
+            Instructions::BVC(a_mode) => a_mode,
+            Instructions::BVS(a_mode) => a_mode,
+            Instructions::CLC(a_mode) => a_mode,
+            Instructions::CLD(a_mode) => a_mode,
+            Instructions::CLI(a_mode) => a_mode,
+            Instructions::CLV(a_mode) => a_mode,
+            Instructions::CMP(a_mode) => a_mode,
+            Instructions::CPX(a_mode) => a_mode,
+            Instructions::CPY(a_mode) => a_mode,
+            Instructions::DEC(a_mode) => a_mode,
+            Instructions::DEX(a_mode) => a_mode,
+            Instructions::DEY(a_mode) => a_mode,
+            Instructions::EOR(a_mode) => a_mode,
+            Instructions::INC(a_mode) => a_mode,
+            Instructions::INX(a_mode) => a_mode,
+            Instructions::INY(a_mode) => a_mode,
+            Instructions::JMP(a_mode) => a_mode,
+            Instructions::JSR(a_mode) => a_mode,
+            Instructions::LDA(a_mode) => a_mode,
+            Instructions::LDX(a_mode) => a_mode,
+            Instructions::LDY(a_mode) => a_mode,
+            Instructions::LSR(a_mode) => a_mode,
+            Instructions::NOP(a_mode) => a_mode,
+            Instructions::ORA(a_mode) => a_mode,
+            Instructions::PHA(a_mode) => a_mode,
+            Instructions::PHP(a_mode) => a_mode,
+            Instructions::PLA(a_mode) => a_mode,
+            Instructions::PLP(a_mode) => a_mode,
+            Instructions::ROL(a_mode) => a_mode,
+            Instructions::ROR(a_mode) => a_mode,
+            Instructions::RTI(a_mode) => a_mode,
+            Instructions::RTS(a_mode) => a_mode,
+            Instructions::SBC(a_mode) => a_mode,
+            Instructions::SEC(a_mode) => a_mode,
+            Instructions::SED(a_mode) => a_mode,
+            Instructions::SEI(a_mode) => a_mode,
+            Instructions::STA(a_mode) => a_mode,
+            Instructions::STX(a_mode) => a_mode,
+            Instructions::STY(a_mode) => a_mode,
+            Instructions::TAX(a_mode) => a_mode,
+            Instructions::TAY(a_mode) => a_mode,
+            Instructions::TSX(a_mode) => a_mode,
+            Instructions::TXA(a_mode) => a_mode,
+            Instructions::TXS(a_mode) => a_mode,
+            Instructions::TYA(a_mode) => a_mode,
+        }
+    }
+
     /// Resolve instruction from opcode
     /// ## Arguments
     /// * `opcode` - opcode to resolve [`u8`]
@@ -257,7 +389,19 @@ impl Instructions {
     /// })));
     /// ```
     pub fn resolve(opcode: u8) -> Instructions {
-        match opcode {
+        match Instructions::try_resolve(opcode) {
+            Some(instruction) => instruction,
+            None => panic!("Wrong opcode: {}", opcode),
+        }
+    }
+
+    /// Resolve instruction from opcode without panicking on unknown opcodes
+    /// ## Arguments
+    /// * `opcode` - opcode to resolve [`u8`]
+    /// ## Returns
+    /// * [`Option<Instructions>`] `None` if the opcode is not a documented instruction
+    pub fn try_resolve(opcode: u8) -> Option<Instructions> {
+        Some(match opcode {
             0x69 => Instructions::ADC(AddrMode::Immediate(AddrCode {
                 cycles: 2,
                 opcode: 0x69,
@@ -862,18 +1006,37 @@ impl Instructions {
                 cycles: 2,
                 opcode: 0x98,
             })),
-            _ => panic!("Wrong opcode: {}", opcode),
-        }
+            _ => return None,
+        })
     }
 }
 
+/// A block of bytes placed at its own address
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    /// Address of the first byte
+    pub origin: u16,
+    /// Bytes of the segment
+    pub data: Vec<u8>,
+}
+
 /// A program structure
-#[derive(Debug)] 
+#[derive(Debug)]
 pub struct Program {
     /// Start address
     pub start_addr: usize,
     /// Lines
     pub lines: Vec<u8>,
+    /// Segments started with `.org`, each placed at its own origin
+    pub segments: Vec<Segment>,
+    /// Labels defined by the assembled source and their addresses
+    pub labels: BTreeMap<String, u16>,
+    /// Constants defined by the assembled source with `NAME = value`
+    pub constants: BTreeMap<String, i64>,
+    /// Listing of the assembled source
+    pub listing: Listing,
+    /// Labels and constants with their source location, see [`write_symbols`]
+    pub symbols: Vec<Symbol>,
 }
 
 impl Program {
@@ -882,8 +1045,13 @@ impl Program {
     /// * `start_addr` - Start address [`usize`]
     pub fn new(start_addr: usize) -> Self {
         Program {
-            start_addr: start_addr,
+            start_addr,
             lines: Vec::new(),
+            segments: Vec::new(),
+            labels: BTreeMap::new(),
+            constants: BTreeMap::new(),
+            listing: Listing::default(),
+            symbols: Vec::new(),
         }
     }
 
@@ -896,33 +1064,311 @@ impl Program {
     }
 
     /// Fill the ram with the program
+    ///
+    /// Lines are placed from `start_addr`, every segment is placed at its own origin
     /// ## Parameters
     /// * `ram` - Ram [`crate::mem::MEM`]
     pub fn fill_ram(&self, ram: &mut crate::mem::MEM) {
-        for (i, line) in self.lines.clone().iter_mut().enumerate() {
+        for (i, line) in self.lines.iter().enumerate() {
             ram[self.start_addr + i] = *line;
         }
+        for segment in &self.segments {
+            for (i, byte) in segment.data.iter().enumerate() {
+                ram[segment.origin as usize + i] = *byte;
+            }
+        }
     }
 
-    /// Parse the program from given string
+    /// Parse the program from given string of hex bytes
     /// ## Parameters
     /// * `program` - Program [`str`]
+    /// ## Returns
+    /// The program, or a [`ErrorKind::Syntax`] error for every word which is not a hex byte,
+    /// nothing is added when there are errors
     /// ## Example
     /// ```
     /// use rusty_6502::{asm, mem};
     /// let mut mem = mem::MEM::new();
     /// let program = rusty_6502::asm::Program::new(600)
     /// .get_from_str("A2 01")
+    /// .unwrap()
     /// .fill_ram(&mut mem);
     /// assert_eq!(mem[600], 0xA2);
     /// assert_eq!(mem[601], 0x01);
     /// ```
-    pub fn get_from_str(&mut self, code: &str) -> &mut Self {
-        let instructions = code.split(" ");
-        for instruction in instructions {
-            self.lines
-                .push(u8::from_str_radix(instruction, 16).unwrap())
+    pub fn get_from_str(&mut self, code: &str) -> Result<&mut Self, AssembleErrors> {
+        let mut bytes = Vec::new();
+        let mut errors = Vec::new();
+        for (index, line) in code.lines().enumerate() {
+            for word in line.split_whitespace() {
+                let valid = (1..=2).contains(&word.len())
+                    && word.bytes().all(|byte| byte.is_ascii_hexdigit());
+                if valid {
+                    bytes.push(u8::from_str_radix(word, 16).unwrap());
+                    continue;
+                }
+                //Words are slices of the line, so their offset is their position
+                let start = word.as_ptr() as usize - line.as_ptr() as usize;
+                errors.push(AssembleError {
+                    kind: ErrorKind::Syntax,
+                    message: format!("Invalid hex byte '{}'", word),
+                    span: Span {
+                        file: None,
+                        line: index + 1,
+                        column: line[..start].chars().count() + 1,
+                        length: word.chars().count(),
+                    },
+                    source_line: line.to_string(),
+                });
+            }
         }
-        self
+        if !errors.is_empty() {
+            return Err(AssembleErrors(errors));
+        }
+        self.lines.extend(bytes);
+        Ok(self)
+    }
+
+    /// Assemble the program from given 6502 assembly source
+    ///
+    /// Code is placed right after the existing lines, or after the last segment.
+    /// Labels are written as `name:`, constants as `NAME = value` and comments start with `;`.
+    /// Symbols can be used before they are defined.
+    ///
+    /// Values are expressions of `$hex`, `%binary`, decimal and `'c'` literals, symbols and `*`
+    /// for the address of the current line. Operators from the highest to the lowest precedence:
+    /// * `-` `~` `!` `<` (low byte) `>` (high byte) - Unary operators
+    /// * `*` `/` `%`
+    /// * `+` `-`
+    /// * `<<` `>>`
+    /// * `<` `<=` `>` `>=`
+    /// * `==` `!=`
+    /// * `&`
+    /// * `^`
+    /// * `|`
+    /// * `&&`
+    /// * `||`
+    ///
+    /// Comparisons and logical operators result in 1 for true and 0 for false.
+    ///
+    /// Parentheses group sub expressions, except around the whole operand of a instruction,
+    /// which is indirect addressing like `JMP (vector)` and a error for the instructions
+    /// without it.
+    ///
+    /// Supported directives:
+    /// * `.org address` - Continue at the given address in a new segment
+    /// * `.byte value, "string", ...` - Emit bytes
+    /// * `.word value, ...` - Emit little endian words
+    /// * `.text "string", ...` - Emit ASCII strings
+    /// * `.fill count[, value]` - Emit `count` copies of `value`
+    /// * `.align alignment[, value]` - Pad with `value` until the address is a multiple of `alignment`
+    /// * `.include "file"` - Assemble another source file in place
+    /// * `.incbin "file"[, offset[, length]]` - Emit the contents of a binary file
+    /// * `.macro name[ parameter, ...]` ... `.endmacro` - Define a macro, invoked like an
+    ///   instruction with `name argument, ...`
+    /// * `.if condition` ... `[.else ...]` `.endif` - Assemble lines only if the condition is not zero
+    /// * `.repeat count[, variable]` ... `.endrepeat` - Assemble lines `count` times, `variable`
+    ///   is replaced with the iteration starting from 0
+    /// * `.segment "NAME"`, `.code`, `.data`, `.zeropage`, `.bss`, `.export name, ...`,
+    ///   `.import name, ...` and `.importzp name, ...` - Only in relocatable [`Object`]s
+    ///
+    /// Files are resolved relative to the including file, or to the working directory
+    /// for sources which are not read from a file.
+    ///
+    /// Macro parameters are replaced with the arguments as written. Labels starting with `@`
+    /// inside a macro or repeat block are local, every expansion gets its own copy of them.
+    /// Conditions and repeat counts must only use symbols defined before them.
+    ///
+    /// Lines with errors are skipped and assembling goes on, so every error of the source
+    /// is returned with its position. They render like rustc diagnostics with `Display`.
+    ///
+    /// Every assembled line is added to [`Program::listing`] with its address, bytes and cycles.
+    /// ## Parameters
+    /// * `source` - Assembly source [`str`]
+    /// ## Example
+    /// ```
+    /// use rusty_6502::{asm, mem};
+    /// let mut mem = mem::MEM::new();
+    /// asm::Program::new(0x600)
+    ///     .assemble(
+    ///         "
+    ///         PTR = $10
+    ///         loop: LDA (PTR),Y ; Read from pointer
+    ///               BNE loop
+    ///               JMP loop
+    ///         .org $FFFC
+    ///         .word loop
+    ///         ",
+    ///     )
+    ///     .unwrap()
+    ///     .fill_ram(&mut mem);
+    /// assert_eq!(mem.hex_dump(0x600, 0x607), "B1 10 D0 FC 4C 00 06 ");
+    /// assert_eq!(mem.hex_dump(0xFFFC, 0xFFFE), "00 06 ");
+    /// ```
+    pub fn assemble(&mut self, source: &str) -> Result<&mut Self, AssembleErrors> {
+        self.assemble_from(source, None)
+    }
+
+    /// Assemble the program from given 6502 assembly source file
+    ///
+    /// See [`Program::assemble`] for the syntax.
+    /// ## Parameters
+    /// * `path` - Path of the source file [`Path`]
+    pub fn assemble_file(&mut self, path: impl AsRef<Path>) -> Result<&mut Self, AssembleErrors> {
+        let path = path.as_ref();
+        let source = read_source(path)?;
+        self.assemble_from(&source, Some(path))
+    }
+
+    fn assemble_from(
+        &mut self,
+        source: &str,
+        file: Option<&Path>,
+    ) -> Result<&mut Self, AssembleErrors> {
+        let origin = match self.segments.last() {
+            Some(segment) => segment.origin as usize + segment.data.len(),
+            None => self.start_addr + self.lines.len(),
+        };
+        let predefined = self
+            .labels
+            .iter()
+            .map(|(name, address)| (name.clone(), *address as i64))
+            .chain(self.constants.clone())
+            .collect();
+        let assembled = assembler::assemble(source, file, origin as u16, &predefined, false)
+            .map_err(AssembleErrors)?;
+        let mut segments = assembled.segments.into_iter();
+        let continued = segments.next().unwrap().data;
+        match self.segments.last_mut() {
+            Some(segment) => segment.data.extend(continued),
+            None => self.lines.extend(continued),
+        }
+        self.segments.extend(segments);
+        self.labels.extend(assembled.labels);
+        self.constants.extend(assembled.constants);
+        self.listing.0.extend(assembled.listing);
+        self.symbols.extend(assembled.symbols);
+        Ok(self)
+    }
+}
+
+/// Read a source file, failures are reported like assembler errors
+fn read_source(path: &Path) -> Result<String, AssembleErrors> {
+    std::fs::read_to_string(path).map_err(|error| {
+        AssembleErrors(vec![AssembleError {
+            kind: ErrorKind::File,
+            message: format!("Can not read file: {}", error),
+            span: Span {
+                file: Some(path.display().to_string()),
+                line: 0,
+                column: 0,
+                length: 0,
+            },
+            source_line: String::new(),
+        }])
+    })
+}
+
+/// Assemble 6502 assembly inside Rust code into bytes, see [`assemble_inline`]
+///
+/// Statements are separated with `;`, labels are written like `loop: DEX`. The origin of
+/// the bytes is `$0600` unless it is given with `origin = address;` first. Values which
+/// are not valid Rust tokens, like `$0E` or `$0b`, can be written in a string literal
+/// instead, which is assembled as a normal source with `;` comments.
+/// ## Panics
+/// At run time, with every error of the source
+/// ## Example
+/// ```
+/// use rusty_6502::{asm6502, cpu::CPU, mem::MEM};
+/// let bytes = asm6502! { LDX #$01; STX $00; LDA $00 };
+/// assert_eq!(bytes, [0xA2, 0x01, 0x86, 0x00, 0xA5, 0x00]);
+///
+/// let bytes = asm6502! { origin = 600; loop: DEX; BNE loop };
+/// assert_eq!(bytes, [0xCA, 0xD0, 0xFD]);
+/// assert_eq!(asm6502!("LDA #$0E ; Fourteen"), [0xA9, 0x0E]);
+/// ```
+#[macro_export]
+macro_rules! asm6502 {
+    (origin = $origin:expr; $source:literal) => {
+        $crate::asm::assemble_inline($origin, $source)
+    };
+    (origin = $origin:expr; $($tokens:tt)*) => {
+        $crate::asm::assemble_inline($origin, &$crate::asm::inline_source(stringify!($($tokens)*)))
+    };
+    ($source:literal) => {
+        $crate::asm6502!(origin = 0x600; $source)
+    };
+    ($($tokens:tt)*) => {
+        $crate::asm6502!(origin = 0x600; $($tokens)*)
+    };
+}
+
+/// Assemble a source into continuous bytes, used by [`asm6502!`](crate::asm6502)
+/// ## Arguments
+/// * `origin` - Address of the first byte [`usize`]
+/// * `source` - Assembly source [`str`]
+/// ## Panics
+/// If the source has errors, with every error, or if it uses `.org`
+pub fn assemble_inline(origin: usize, source: &str) -> Vec<u8> {
+    let mut program = Program::new(origin);
+    if let Err(errors) = program.assemble(source) {
+        panic!("asm6502! failed to assemble:\n{}", errors);
+    }
+    if !program.segments.is_empty() {
+        panic!("asm6502! can not use .org, the bytes must follow each other");
+    }
+    program.lines
+}
+
+/// Turn the stringified tokens of [`asm6502!`](crate::asm6502) into source lines
+///
+/// Whitespace is collapsed since Rust may break the tokens into lines anywhere, `;`
+/// separates the statements and a space is put back after mnemonics like in `LDA($10),Y`.
+#[doc(hidden)]
+pub fn inline_source(tokens: &str) -> String {
+    let mut statements = vec![String::new()];
+    let mut quote = None;
+    for c in tokens.chars() {
+        let statement = statements.last_mut().unwrap();
+        match (quote, c) {
+            (None, ';') => statements.push(String::new()),
+            (None, c) if c.is_whitespace() => {
+                if !statement.is_empty() && !statement.ends_with(' ') {
+                    statement.push(' ');
+                }
+            }
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                statement.push(c);
+            }
+            (Some(q), c) if q == c => {
+                quote = None;
+                statement.push(c);
+            }
+            (_, c) => statement.push(c),
+        }
+    }
+    let mut source = String::new();
+    for statement in &statements {
+        let statement = statement.trim();
+        let (label, mut rest) = match statement.split_once(':') {
+            Some((label, rest)) if !label.contains(['"', '\'']) => {
+                (&statement[..=label.len()], rest.trim())
+            }
+            _ => ("", statement),
+        };
+        let word = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let glued =
+            rest[word..].starts_with(|c: char| !(c.is_whitespace() || c == '=' || c == ':'));
+        let spaced;
+        if word > 0 && glued && !rest.starts_with(|c: char| c.is_ascii_digit()) {
+            spaced = format!("{} {}", &rest[..word], &rest[word..]);
+            rest = &spaced;
+        }
+        source += [label, rest].join(" ").trim();
+        source.push('\n');
     }
+    source
 }
@@ -0,0 +1,2000 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use super::{
+    error::{AssembleError, ErrorKind, Span},
+    expr::{self, Base, EvalError, Expr, Relocatable},
+    listing::ListingLine,
+    object::{Export, Import, Object, ObjectSegment, Relocation, RelocationKind, RelocationTarget},
+    symbols::{Symbol, SymbolKind, SymbolScope},
+    AddrCode, AddrMode, Instructions, Segment,
+};
+
+/// Output of a successful assembly
+pub(crate) struct Assembled {
+    /// Assembled segments, the first one starts at the requested origin
+    pub segments: Vec<Segment>,
+    pub labels: BTreeMap<String, u16>,
+    pub constants: BTreeMap<String, i64>,
+    pub listing: Vec<ListingLine>,
+    /// Labels and constants in the order they are defined
+    pub symbols: Vec<Symbol>,
+    /// Segments, exports and imports when the source is assembled as a relocatable
+    /// object, its name is left empty
+    pub object: Option<Object>,
+}
+
+/// Where a line comes from
+#[derive(Clone)]
+struct Location {
+    file: Option<Rc<PathBuf>>,
+    line: usize,
+    /// Line as written in the file, lines of expansions keep the text of their definition
+    text: Rc<str>,
+}
+
+impl Location {
+    /// Resolve a path relative to the file of this location
+    fn resolve(&self, path: &str) -> PathBuf {
+        match self.file.as_ref().and_then(|file| file.parent()) {
+            Some(directory) => directory.join(path),
+            None => PathBuf::from(path),
+        }
+    }
+
+    /// Error spanning the statement of the line
+    fn error(&self, kind: ErrorKind, message: impl Into<String>) -> AssembleError {
+        self.error_at("", kind, message)
+    }
+
+    /// Error spanning the first occurrence of `fragment` in the statement of the line,
+    /// or the whole statement if it can not be found
+    fn error_at(
+        &self,
+        fragment: &str,
+        kind: ErrorKind,
+        message: impl Into<String>,
+    ) -> AssembleError {
+        let text = &*self.text;
+        let offset = |part: &str| part.as_ptr() as usize - text.as_ptr() as usize;
+        let code = strip_comment(text).trim();
+        let (_, statement) = split_label(code);
+        let statement = if statement.is_empty() {
+            code
+        } else {
+            statement
+        };
+        let start = offset(statement);
+        let (start, end) = find_fragment(statement, fragment)
+            .map(|(from, to)| (start + from, start + to))
+            .or_else(|| {
+                find_fragment(code, fragment)
+                    .map(|(from, to)| (offset(code) + from, offset(code) + to))
+            })
+            .unwrap_or((start, start + statement.len()));
+        AssembleError {
+            kind,
+            message: message.into(),
+            span: Span {
+                file: self.file.as_ref().map(|file| file.display().to_string()),
+                line: self.line,
+                column: text[..start].chars().count() + 1,
+                length: text[start..end].chars().count(),
+            },
+            source_line: text.to_string(),
+        }
+    }
+}
+
+/// Find the first occurrence of `fragment`, ignoring case and whitespace,
+/// identifiers only match whole words
+/// ## Returns
+/// Byte range of the occurrence
+fn find_fragment(text: &str, fragment: &str) -> Option<(usize, usize)> {
+    let wanted: Vec<char> = fragment.chars().filter(|c| !c.is_whitespace()).collect();
+    let word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '@';
+    let first = *wanted.first()?;
+    let last = *wanted.last()?;
+    for (start, _) in text.char_indices() {
+        let mut matched = 0;
+        let mut end = start;
+        for (index, c) in text[start..].char_indices() {
+            if matched == wanted.len() {
+                break;
+            } else if c.eq_ignore_ascii_case(&wanted[matched]) {
+                matched += 1;
+                end = start + index + c.len_utf8();
+            } else if !(c.is_whitespace() && matched > 0) {
+                break;
+            }
+        }
+        let before = text[..start].chars().next_back();
+        let after = text[end..].chars().next();
+        if matched == wanted.len()
+            && !(word(first) && before.is_some_and(word))
+            && !(word(last) && after.is_some_and(word))
+        {
+            return Some((start, end));
+        }
+    }
+    None
+}
+
+/// Expression with the text it was parsed from
+struct Value {
+    expr: Expr,
+    text: String,
+}
+
+impl Value {
+    fn zero() -> Self {
+        Value {
+            expr: Expr::Number(0),
+            text: String::new(),
+        }
+    }
+}
+
+/// Operand as written in the source, before a addressing mode is selected
+enum Operand {
+    None,
+    Accumulator,
+    Immediate(Value),
+    Direct(Value),
+    DirectX(Value),
+    DirectY(Value),
+    Indirect(Value),
+    IndirectX(Value),
+    IndirectY(Value),
+}
+
+/// Encoding of the operand bytes after a addressing mode is selected
+enum Encoding {
+    None,
+    Byte(Value),
+    Word(Value),
+    Relative(Value),
+}
+
+/// Argument of `.byte` and `.text`
+enum Data {
+    Value(Value),
+    String(Vec<u8>),
+}
+
+enum Statement {
+    /// Instruction or macro invocation with its operand as written
+    Instruction(String, String),
+    Constant(String, Expr),
+    Org(Value),
+    Byte(Vec<Data>),
+    Word(Vec<Value>),
+    Fill(Value, Option<Value>),
+    Align(Value, Option<Value>),
+    Include(String),
+    Incbin(String, Option<Value>, Option<Value>),
+    Macro(String, Vec<String>),
+    EndMacro,
+    If(Value),
+    Repeat(Value, Option<String>),
+    EndRepeat,
+    Segment(String),
+    Export(Vec<String>),
+    /// Imported names, and whether they are in the zero page
+    Import(Vec<String>, bool),
+}
+
+struct Line {
+    location: Location,
+    label: Option<String>,
+    statement: Option<Statement>,
+}
+
+/// Source lines which are being assembled, a file or an expansion
+struct Frame {
+    lines: VecDeque<(Location, String)>,
+    /// Canonical path of the file, to detect recursive includes
+    include: Option<PathBuf>,
+}
+
+impl Frame {
+    fn read(source: &str, file: Option<Rc<PathBuf>>) -> Self {
+        Frame {
+            lines: source
+                .lines()
+                .enumerate()
+                .map(|(index, text)| {
+                    let location = Location {
+                        file: file.clone(),
+                        line: index + 1,
+                        text: text.into(),
+                    };
+                    (location, text.to_string())
+                })
+                .collect(),
+            include: file.map(|file| file.canonicalize().unwrap_or_else(|_| (*file).clone())),
+        }
+    }
+}
+
+/// Limit of nested includes and expansions, to stop recursive macros
+const MAX_DEPTH: usize = 64;
+
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<(Location, String)>,
+}
+
+/// Block whose lines are collected until its closing directive
+enum Block {
+    Macro(String, Vec<String>),
+    Repeat(usize, Option<String>),
+}
+
+impl Block {
+    /// Names of the opening and closing directives
+    fn directives(&self) -> (&'static str, &'static str) {
+        match self {
+            Block::Macro(..) => ("macro", "endmacro"),
+            Block::Repeat(..) => ("repeat", "endrepeat"),
+        }
+    }
+}
+
+struct Recording {
+    location: Location,
+    block: Block,
+    /// Count of nested blocks of the same kind
+    nesting: usize,
+    body: Vec<(Location, String)>,
+}
+
+/// State of a `.if` block
+struct Conditional {
+    location: Location,
+    /// Frame count when the block was opened, it must be closed in the same frame
+    depth: usize,
+    active: bool,
+    /// Whether a branch was taken already, or can not be taken
+    taken: bool,
+    has_else: bool,
+}
+
+/// Definition of a symbol
+enum Definition {
+    /// Label, import or predefined symbol
+    Value(Relocatable),
+    /// Constant, evaluated when it is used with `pc` as the value of `*`
+    Constant(Expr, Relocatable),
+}
+
+/// Symbol table, constants are resolved lazily so they can refer to later symbols
+struct Symbols(BTreeMap<String, Definition>);
+
+impl Symbols {
+    fn define(
+        &mut self,
+        location: &Location,
+        name: String,
+        definition: Definition,
+    ) -> Result<(), AssembleError> {
+        if self.0.contains_key(&name) {
+            return Err(location.error_at(
+                &name,
+                ErrorKind::DuplicateSymbol,
+                format!("Symbol '{}' is already defined", name),
+            ));
+        }
+        self.0.insert(name, definition);
+        Ok(())
+    }
+
+    /// Resolve a symbol
+    /// ## Arguments
+    /// * `name` - Name of the symbol
+    /// * `visiting` - Constants which are being resolved, to detect circular definitions
+    fn resolve(&self, name: &str, visiting: &mut Vec<String>) -> Result<Relocatable, EvalError> {
+        match self.0.get(name) {
+            None => Err(EvalError::Undefined(name.to_string())),
+            Some(Definition::Value(value)) => Ok(value.clone()),
+            Some(Definition::Constant(expr, pc)) => {
+                if visiting.iter().any(|visited| visited == name) {
+                    return Err(EvalError::Circular(name.to_string()));
+                }
+                visiting.push(name.to_string());
+                let value = expr.evaluate(pc, &mut |name| self.resolve(name, visiting));
+                visiting.pop();
+                value
+            }
+        }
+    }
+
+    fn evaluate(&self, expr: &Expr, pc: &Relocatable) -> Result<Relocatable, EvalError> {
+        expr.evaluate(pc, &mut |name| self.resolve(name, &mut Vec::new()))
+    }
+}
+
+/// Output of the first pass, with its address and size fixed
+enum Item {
+    Org,
+    Instruction(u8, Encoding),
+    Byte(Value),
+    Word(Value),
+    Fill(usize, Value),
+    Bytes(Vec<u8>),
+}
+
+struct Planned {
+    location: Location,
+    /// Address, or offset in the segment of a relocatable object
+    address: u16,
+    /// Index of the segment of a relocatable object
+    segment: usize,
+    size: u32,
+    item: Item,
+    /// Index of the listing line of the item
+    entry: usize,
+}
+
+/// State of the first pass, which expands includes, macros and conditionals,
+/// defines symbols and fixes the size of every line
+struct FirstPass {
+    symbols: Symbols,
+    labels: BTreeMap<String, u16>,
+    /// Defined labels and constants, constants are evaluated at the end to report
+    /// broken definitions
+    definitions: Vec<(Location, String, SymbolKind)>,
+    planned: Vec<Planned>,
+    pc: u32,
+    frames: Vec<Frame>,
+    macros: BTreeMap<String, Macro>,
+    conditionals: Vec<Conditional>,
+    recording: Option<Recording>,
+    /// Count of macro and repeat expansions, used to give local labels unique names
+    expansions: usize,
+    /// Every line given to the first pass, bytes are added in the second pass
+    listing: Vec<ListingLine>,
+    /// Segments, exports and imports, `None` when assembling at absolute addresses
+    object: Option<ObjectState>,
+    errors: Vec<AssembleError>,
+}
+
+/// Segments of a relocatable object and its exports and imports
+struct ObjectState {
+    /// Name, size and alignment of the segments in the order they are opened
+    segments: Vec<(String, u32, u32)>,
+    /// Index of the current segment
+    current: usize,
+    exports: Vec<(Location, String)>,
+    imports: Vec<Import>,
+}
+
+impl ObjectState {
+    /// Base of the addresses in the segment
+    fn base(&self, segment: usize) -> Base {
+        let name = &self.segments[segment].0;
+        Base {
+            name: name.clone(),
+            import: false,
+            zero_page: name == "ZEROPAGE",
+        }
+    }
+}
+
+/// Assemble the given source, starting from `origin`
+/// ## Arguments
+/// * `source` - Assembly source [`str`]
+/// * `file` - File of the source, used to resolve `.include` and `.incbin` paths [`Path`]
+/// * `origin` - Address of the first emitted byte [`u16`]
+/// * `predefined` - Symbols which are already known [`BTreeMap`]
+/// * `relocatable` - Assemble a relocatable object, `origin` is not used [`bool`]
+/// ## Returns
+/// The assembled program or every error which was found, sorted by their span
+pub(crate) fn assemble(
+    source: &str,
+    file: Option<&Path>,
+    origin: u16,
+    predefined: &BTreeMap<String, i64>,
+    relocatable: bool,
+) -> Result<Assembled, Vec<AssembleError>> {
+    //First pass: a line with an error is skipped so the following lines are still checked
+    let mut pass = FirstPass {
+        symbols: Symbols(
+            predefined
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.clone(),
+                        Definition::Value(Relocatable::Absolute(*value)),
+                    )
+                })
+                .collect(),
+        ),
+        labels: BTreeMap::new(),
+        definitions: Vec::new(),
+        planned: Vec::new(),
+        pc: if relocatable { 0 } else { origin as u32 },
+        frames: vec![Frame::read(
+            source,
+            file.map(|file| Rc::new(file.to_path_buf())),
+        )],
+        macros: BTreeMap::new(),
+        conditionals: Vec::new(),
+        recording: None,
+        expansions: 0,
+        listing: Vec::new(),
+        object: relocatable.then(|| ObjectState {
+            segments: vec![("CODE".to_string(), 0, 1)],
+            current: 0,
+            exports: Vec::new(),
+            imports: Vec::new(),
+        }),
+        errors: Vec::new(),
+    };
+    while let Some((location, text)) = pass.next_line() {
+        if let Err(error) = pass.line(location, text) {
+            pass.errors.push(error);
+        }
+    }
+    let FirstPass {
+        symbols,
+        labels,
+        definitions,
+        planned,
+        mut listing,
+        object,
+        mut errors,
+        ..
+    } = pass;
+
+    //Second pass: every label is known, emit the bytes
+    let mut segments = vec![(
+        None,
+        Segment {
+            origin,
+            data: Vec::new(),
+        },
+    )];
+    //Sections of a relocatable object, with the relocations of their bytes
+    let mut sections: Vec<(Vec<u8>, Vec<Relocation>)> = match &object {
+        Some(state) => vec![(Vec::new(), Vec::new()); state.segments.len()],
+        None => Vec::new(),
+    };
+    let mut absolute = Vec::new();
+    for planned in planned {
+        let location = &planned.location;
+        let (pc, data, relocations) = match &object {
+            Some(state) => {
+                let (data, relocations) = &mut sections[planned.segment];
+                let pc = Relocatable::Relative(state.base(planned.segment), planned.address as i64);
+                (pc, data, relocations)
+            }
+            None => {
+                if let Item::Org = planned.item {
+                    segments.push((
+                        Some(planned.location),
+                        Segment {
+                            origin: planned.address,
+                            data: Vec::new(),
+                        },
+                    ));
+                    continue;
+                }
+                let data = &mut segments.last_mut().unwrap().1.data;
+                let pc = Relocatable::Absolute(planned.address as i64);
+                (pc, data, &mut absolute)
+            }
+        };
+        let offset = data.len();
+        if let Err(error) = emit(&planned.item, location, &pc, &symbols, data, relocations) {
+            //Keep the size so the following addresses stay right
+            errors.push(error);
+            data.resize(offset + planned.size as usize, 0);
+        }
+        let bytes = &data[offset..];
+        let entry = &mut listing[planned.entry];
+        if let Item::Instruction(opcode, encoding) = &planned.item {
+            entry.cycles = Some(Instructions::resolve(*opcode).addr_mode().code().cycles);
+            if let Encoding::Relative(_) = encoding {
+                let offset = bytes[1] as i8 as i16;
+                entry.branch = Some(planned.address.wrapping_add(2).wrapping_add(offset as u16));
+            }
+        }
+        entry.bytes.extend_from_slice(bytes);
+    }
+
+    //Segments of a relocatable object do not overlap until they are linked
+    let object = object.map(|state| {
+        let mut exports = Vec::new();
+        for (location, name) in &state.exports {
+            match symbols.resolve(name, &mut Vec::new()) {
+                Ok(Relocatable::Absolute(value)) => exports.push(Export {
+                    name: name.clone(),
+                    segment: None,
+                    value,
+                }),
+                Ok(Relocatable::Relative(base, value)) if !base.import => exports.push(Export {
+                    name: name.clone(),
+                    segment: Some(base.name),
+                    value,
+                }),
+                Ok(_) => errors.push(location.error_at(
+                    name,
+                    ErrorKind::InvalidValue,
+                    format!("Symbol '{}' can not be exported", name),
+                )),
+                Err(error) => errors.push(evaluation_error(location, error, false, name)),
+            }
+        }
+        let segments = state
+            .segments
+            .into_iter()
+            .zip(sections)
+            .filter(|(_, (data, _))| !data.is_empty())
+            .map(|((name, _, align), (data, relocations))| ObjectSegment {
+                name,
+                align,
+                data,
+                relocations,
+            })
+            .collect();
+        Object {
+            name: String::new(),
+            segments,
+            exports,
+            imports: state.imports,
+        }
+    });
+
+    //The first segment is kept even when empty, it continues the existing program
+    let first = segments.remove(0);
+    segments.retain(|(_, segment)| !segment.data.is_empty());
+    //An empty first segment holds no bytes, so it can never overlap another one
+    let mut sorted: Vec<&(Option<Location>, Segment)> = segments
+        .iter()
+        .chain(Some(&first).filter(|(_, segment)| !segment.data.is_empty()))
+        .collect();
+    sorted.sort_by_key(|(_, segment)| segment.origin);
+    for pair in sorted.windows(2) {
+        let ((_, lower), (location, upper)) = (pair[0], pair[1]);
+        if lower.origin as usize + lower.data.len() > upper.origin as usize {
+            let message = format!(
+                "Segment at ${:04X} overlaps segment at ${:04X}",
+                upper.origin, lower.origin
+            );
+            match location.as_ref().or(pair[0].0.as_ref()) {
+                Some(location) => errors.push(location.error(ErrorKind::Layout, message)),
+                None => errors.push(AssembleError {
+                    kind: ErrorKind::Layout,
+                    message,
+                    span: Span {
+                        file: file.map(|file| file.display().to_string()),
+                        line: 0,
+                        column: 0,
+                        length: 0,
+                    },
+                    source_line: String::new(),
+                }),
+            }
+        }
+    }
+    segments.insert(0, first);
+
+    //Unused constants are evaluated too, so every broken definition is reported
+    let segments: Vec<Segment> = segments.into_iter().map(|(_, segment)| segment).collect();
+    let mut addresses: Vec<u16> = labels.values().copied().collect();
+    addresses.sort_unstable();
+    let mut constants = BTreeMap::new();
+    let mut defined = Vec::new();
+    for (location, name, kind) in definitions {
+        let (value, size) = match kind {
+            SymbolKind::Label => {
+                let address = labels[&name];
+                (address as i64, label_size(address, &addresses, &segments))
+            }
+            SymbolKind::Constant => match symbols.resolve(&name, &mut Vec::new()) {
+                Ok(value) => {
+                    constants.insert(name.clone(), value.number());
+                    (value.number(), 0)
+                }
+                Err(error) => {
+                    errors.push(evaluation_error(&location, error, false, ""));
+                    continue;
+                }
+            },
+        };
+        defined.push(Symbol {
+            scope: if name.starts_with('@') {
+                SymbolScope::Local
+            } else {
+                SymbolScope::Global
+            },
+            name,
+            value,
+            kind,
+            size,
+            file: location
+                .file
+                .as_ref()
+                .map(|file| file.display().to_string()),
+            line: location.line,
+        });
+    }
+    if !errors.is_empty() {
+        //Errors are found pass by pass, they are reported in source order
+        errors.sort_by(|a, b| {
+            (&a.span.file, a.span.line, a.span.column).cmp(&(
+                &b.span.file,
+                b.span.line,
+                b.span.column,
+            ))
+        });
+        return Err(errors);
+    }
+    Ok(Assembled {
+        segments,
+        labels,
+        constants,
+        listing,
+        symbols: defined,
+        object,
+    })
+}
+
+/// Bytes from a label to the next label or the end of its segment
+/// ## Arguments
+/// * `address` - Address of the label [`u16`]
+/// * `addresses` - Sorted addresses of every label [`u16`]
+/// * `segments` - Assembled segments [`Segment`]
+fn label_size(address: u16, addresses: &[u16], segments: &[Segment]) -> usize {
+    let end = segments
+        .iter()
+        .map(|segment| {
+            (
+                segment.origin as usize,
+                segment.origin as usize + segment.data.len(),
+            )
+        })
+        .find(|(origin, end)| (*origin..*end).contains(&(address as usize)))
+        .map_or(address as usize, |(_, end)| end);
+    let next = addresses
+        .iter()
+        .find(|next| **next > address)
+        .map_or(end, |next| *next as usize);
+    next.min(end) - address as usize
+}
+
+/// Emit the bytes of a planned item
+/// ## Arguments
+/// * `relocations` - Relocations of relocatable values are added here [`Relocation`]
+fn emit(
+    item: &Item,
+    location: &Location,
+    pc: &Relocatable,
+    symbols: &Symbols,
+    data: &mut Vec<u8>,
+    relocations: &mut Vec<Relocation>,
+) -> Result<(), AssembleError> {
+    let start = data.len();
+    let mut fixups = Vec::new();
+    match item {
+        Item::Org => (),
+        Item::Instruction(opcode, encoding) => {
+            let operand = match encoding {
+                Encoding::None => Vec::new(),
+                Encoding::Byte(value) => {
+                    let (byte, fixup) = byte(location, value, pc, symbols)?;
+                    fixups.extend(fixup.map(|fixup| (1, fixup)));
+                    vec![byte]
+                }
+                Encoding::Word(value) => {
+                    let (word, fixup) = word(location, value, pc, symbols)?;
+                    fixups.extend(fixup.map(|fixup| (1, fixup)));
+                    word.to_vec()
+                }
+                Encoding::Relative(value) => {
+                    let offset = match (evaluate(location, value, pc, symbols)?, pc) {
+                        (Relocatable::Absolute(target), Relocatable::Absolute(pc)) => {
+                            target - (pc + 2)
+                        }
+                        (Relocatable::Relative(to, target), Relocatable::Relative(from, pc))
+                            if to == *from =>
+                        {
+                            target - (pc + 2)
+                        }
+                        _ => {
+                            return Err(location.error_at(
+                                &value.text,
+                                ErrorKind::BranchOutOfRange,
+                                "Branch target must be in the same segment",
+                            ))
+                        }
+                    };
+                    if !(-128..=127).contains(&offset) {
+                        return Err(location.error_at(
+                            &value.text,
+                            ErrorKind::BranchOutOfRange,
+                            format!("Branch target is out of range ({} bytes)", offset),
+                        ));
+                    }
+                    vec![offset as u8]
+                }
+            };
+            data.push(*opcode);
+            data.extend(operand);
+        }
+        Item::Byte(value) => {
+            let (byte, fixup) = byte(location, value, pc, symbols)?;
+            fixups.extend(fixup.map(|fixup| (0, fixup)));
+            data.push(byte);
+        }
+        Item::Word(value) => {
+            let (word, fixup) = word(location, value, pc, symbols)?;
+            fixups.extend(fixup.map(|fixup| (0, fixup)));
+            data.extend_from_slice(&word);
+        }
+        Item::Fill(count, value) => {
+            let (byte, fixup) = byte(location, value, pc, symbols)?;
+            if fixup.is_some() {
+                return Err(location.error_at(
+                    &value.text,
+                    ErrorKind::InvalidValue,
+                    "Fill value must be known before the object is linked",
+                ));
+            }
+            data.resize(data.len() + count, byte);
+        }
+        Item::Bytes(bytes) => data.extend(bytes),
+    }
+    relocations.extend(
+        fixups
+            .into_iter()
+            .map(|(offset, fixup)| fixup.at((start + offset) as u16)),
+    );
+    Ok(())
+}
+
+/// Relocation of a value, its offset is known when the value is emitted
+struct Fixup {
+    kind: RelocationKind,
+    base: Base,
+    addend: i64,
+}
+
+impl Fixup {
+    fn at(self, offset: u16) -> Relocation {
+        Relocation {
+            offset,
+            kind: self.kind,
+            target: if self.base.import {
+                RelocationTarget::Import(self.base.name)
+            } else {
+                RelocationTarget::Segment(self.base.name)
+            },
+            addend: self.addend,
+        }
+    }
+}
+
+impl FirstPass {
+    /// Next line to assemble, blocks which are not closed at the end of their
+    /// file or expansion are reported here
+    fn next_line(&mut self) -> Option<(Location, String)> {
+        loop {
+            let frame = self.frames.last_mut()?;
+            if let Some(line) = frame.lines.pop_front() {
+                return Some(line);
+            }
+            if let Some(recording) = self.recording.take() {
+                let (opening, closing) = recording.block.directives();
+                self.errors.push(recording.location.error(
+                    ErrorKind::Syntax,
+                    format!("Missing .{} for .{}", closing, opening),
+                ));
+            }
+            while let Some(conditional) = self
+                .conditionals
+                .pop_if(|conditional| conditional.depth == self.frames.len())
+            {
+                self.errors.push(
+                    conditional
+                        .location
+                        .error(ErrorKind::Syntax, "Missing .endif for .if"),
+                );
+            }
+            self.frames.pop();
+        }
+    }
+
+    fn line(&mut self, location: Location, text: String) -> Result<(), AssembleError> {
+        self.listing.push(ListingLine {
+            file: location
+                .file
+                .as_ref()
+                .map(|file| file.display().to_string()),
+            line: location.line,
+            address: self.pc as u16,
+            bytes: Vec::new(),
+            cycles: None,
+            branch: None,
+            source: text.clone(),
+        });
+        let keyword = keyword(&text);
+
+        //Lines of a macro or repeat block are kept as written until the block is closed
+        if let Some(active) = &mut self.recording {
+            let (opening, closing) = active.block.directives();
+            match keyword.as_deref() {
+                Some(keyword) if keyword == opening => active.nesting += 1,
+                Some(keyword) if keyword == closing && active.nesting > 0 => active.nesting -= 1,
+                Some(keyword) if keyword == closing => return self.finish_recording(&location),
+                _ => (),
+            }
+            active.body.push((location, text));
+            return Ok(());
+        }
+
+        //Conditionals are tracked in skipped blocks too, to find the matching .else and .endif
+        let skipping = self
+            .conditionals
+            .iter()
+            .any(|conditional| !conditional.active);
+        let depth = self.frames.len();
+        match keyword.as_deref() {
+            Some("else") => {
+                return match self
+                    .conditionals
+                    .last_mut()
+                    .filter(|conditional| conditional.depth == depth)
+                {
+                    Some(conditional) if conditional.has_else => {
+                        Err(location.error(ErrorKind::Syntax, "Duplicate .else for .if"))
+                    }
+                    Some(conditional) => {
+                        conditional.active = !conditional.taken;
+                        conditional.taken = true;
+                        conditional.has_else = true;
+                        Ok(())
+                    }
+                    None => Err(location.error(ErrorKind::Syntax, ".else without .if")),
+                };
+            }
+            Some("endif") => {
+                return match self
+                    .conditionals
+                    .pop_if(|conditional| conditional.depth == depth)
+                {
+                    Some(_) => Ok(()),
+                    None => Err(location.error(ErrorKind::Syntax, ".endif without .if")),
+                };
+            }
+            Some("if") if skipping => {
+                self.conditionals.push(Conditional {
+                    location,
+                    depth,
+                    active: false,
+                    taken: true,
+                    has_else: false,
+                });
+                return Ok(());
+            }
+            _ if skipping => return Ok(()),
+            _ => (),
+        }
+
+        let line = match parse_line(location.clone(), &text) {
+            Ok(line) => line,
+            Err(error) => {
+                self.open_failed_block(location, keyword.as_deref());
+                return Err(error);
+            }
+        };
+        let location = line.location;
+        if let Some(label) = line.label {
+            self.symbols
+                .define(&location, label.clone(), Definition::Value(self.here()))?;
+            self.labels.insert(label.clone(), self.pc as u16);
+            self.definitions
+                .push((location.clone(), label, SymbolKind::Label));
+        }
+        match line.statement {
+            Some(statement) => self.statement(location, statement),
+            None => Ok(()),
+        }
+    }
+
+    /// Open a empty block for a block directive with an error, so its lines
+    /// and its closing directive do not cause more errors
+    fn open_failed_block(&mut self, location: Location, keyword: Option<&str>) {
+        let block = match keyword {
+            Some("macro") => Block::Macro(String::new(), Vec::new()),
+            Some("repeat") => Block::Repeat(0, None),
+            Some("if") => {
+                self.conditionals.push(Conditional {
+                    location,
+                    depth: self.frames.len(),
+                    active: false,
+                    taken: true,
+                    has_else: false,
+                });
+                return;
+            }
+            _ => return,
+        };
+        self.recording = Some(Recording {
+            location,
+            block,
+            nesting: 0,
+            body: Vec::new(),
+        });
+    }
+
+    /// Define the recorded macro or expand the recorded repeat block
+    fn finish_recording(&mut self, location: &Location) -> Result<(), AssembleError> {
+        let Recording { block, body, .. } = self.recording.take().unwrap();
+        match block {
+            Block::Macro(name, _) if name.is_empty() => Ok(()),
+            Block::Macro(name, parameters) => {
+                self.macros.insert(name, Macro { parameters, body });
+                Ok(())
+            }
+            Block::Repeat(count, variable) => {
+                let mut lines = VecDeque::new();
+                for iteration in 0..count {
+                    self.expansions += 1;
+                    let id = self.expansions;
+                    lines.extend(body.iter().map(|(location, text)| {
+                        let text = substitute(text, |name| {
+                            if Some(name) == variable.as_deref() {
+                                Some(iteration.to_string())
+                            } else {
+                                local(name, id)
+                            }
+                        });
+                        (location.clone(), text)
+                    }));
+                }
+                self.push_frame(location, lines)
+            }
+        }
+    }
+
+    /// Push the lines of a include or expansion
+    fn push_frame(
+        &mut self,
+        location: &Location,
+        lines: VecDeque<(Location, String)>,
+    ) -> Result<(), AssembleError> {
+        if self.frames.len() >= MAX_DEPTH {
+            return Err(location.error(
+                ErrorKind::File,
+                "Includes or macro expansions are nested too deeply",
+            ));
+        }
+        self.frames.push(Frame {
+            lines,
+            include: None,
+        });
+        Ok(())
+    }
+
+    /// Value of `*`, relative to the current segment in a relocatable object
+    fn here(&self) -> Relocatable {
+        match &self.object {
+            Some(state) => Relocatable::Relative(state.base(state.current), self.pc as i64),
+            None => Relocatable::Absolute(self.pc as i64),
+        }
+    }
+
+    fn statement(&mut self, location: Location, statement: Statement) -> Result<(), AssembleError> {
+        let pc = self.pc;
+        let here = self.here();
+        let mut items = Vec::new();
+        match statement {
+            Statement::Instruction(name, operand) if self.macros.contains_key(&name) => {
+                let definition = &self.macros[&name];
+                let arguments = if operand.is_empty() {
+                    Vec::new()
+                } else {
+                    split_arguments(&operand)
+                };
+                if arguments.len() != definition.parameters.len() {
+                    return Err(location.error(
+                        ErrorKind::Syntax,
+                        format!(
+                            "Macro '{}' expects {} arguments, found {}",
+                            name,
+                            definition.parameters.len(),
+                            arguments.len()
+                        ),
+                    ));
+                }
+                let id = self.expansions + 1;
+                let lines = definition
+                    .body
+                    .iter()
+                    .map(|(location, text)| {
+                        let text = substitute(text, |name| {
+                            match definition.parameters.iter().position(|p| p == name) {
+                                Some(index) => Some(arguments[index].to_string()),
+                                None => local(name, id),
+                            }
+                        });
+                        (location.clone(), text)
+                    })
+                    .collect();
+                self.expansions = id;
+                self.push_frame(&location, lines)?;
+            }
+            Statement::Instruction(mnemonic, operand) => {
+                let (instruction, encoding) =
+                    select(&location, &mnemonic, &operand, &here, &self.symbols)?;
+                let opcode = instruction.addr_mode().code().opcode;
+                items.push((
+                    instruction.addr_mode().size() as u32,
+                    Item::Instruction(opcode, encoding),
+                ));
+            }
+            Statement::Constant(name, expr) => {
+                self.symbols
+                    .define(&location, name.clone(), Definition::Constant(expr, here))?;
+                self.definitions
+                    .push((location.clone(), name, SymbolKind::Constant));
+            }
+            Statement::Org(_) if self.object.is_some() => {
+                return Err(location.error(
+                    ErrorKind::Syntax,
+                    ".org can not be used in a relocatable object, use .segment",
+                ))
+            }
+            Statement::Org(value) => {
+                let address = evaluate_now(&location, &value, &here, &self.symbols)?;
+                if !(0..=0xFFFF).contains(&address) {
+                    return Err(location.error_at(
+                        &value.text,
+                        ErrorKind::ValueTooLarge,
+                        format!("Origin {} is outside of the address space", address),
+                    ));
+                }
+                self.pc = address as u32;
+                items.push((0, Item::Org));
+            }
+            Statement::Byte(data) => {
+                for data in data {
+                    match data {
+                        Data::Value(value) => items.push((1, Item::Byte(value))),
+                        Data::String(bytes) => items.push((bytes.len() as u32, Item::Bytes(bytes))),
+                    }
+                }
+            }
+            Statement::Word(values) => {
+                items.extend(values.into_iter().map(|value| (2, Item::Word(value))));
+            }
+            Statement::Fill(count, value) => {
+                let number = evaluate_now(&location, &count, &here, &self.symbols)?;
+                if !(0..=0x10000).contains(&number) {
+                    return Err(location.error_at(
+                        &count.text,
+                        ErrorKind::InvalidValue,
+                        format!("Invalid fill count {}", number),
+                    ));
+                }
+                let value = value.unwrap_or_else(Value::zero);
+                items.push((number as u32, Item::Fill(number as usize, value)));
+            }
+            Statement::Align(alignment, value) => {
+                let number = evaluate_now(&location, &alignment, &here, &self.symbols)?;
+                if !(1..=0x10000).contains(&number) {
+                    return Err(location.error_at(
+                        &alignment.text,
+                        ErrorKind::InvalidValue,
+                        format!("Invalid alignment {}", number),
+                    ));
+                }
+                let number = number as u32;
+                //The linker aligns the segment, so offsets in it stay aligned
+                if let Some(state) = &mut self.object {
+                    let align = &mut state.segments[state.current].2;
+                    *align = (*align).max(number);
+                }
+                let count = (number - pc % number) % number;
+                let value = value.unwrap_or_else(Value::zero);
+                items.push((count, Item::Fill(count as usize, value)));
+            }
+            Statement::Incbin(path, offset, length) => {
+                let path = location.resolve(&path);
+                let data = fs::read(&path).map_err(|error| {
+                    location.error(
+                        ErrorKind::File,
+                        format!("Can not read '{}': {}", path.display(), error),
+                    )
+                })?;
+                let offset = match offset {
+                    Some(offset) => evaluate_now(&location, &offset, &here, &self.symbols)?,
+                    None => 0,
+                };
+                let length = match length {
+                    Some(length) => evaluate_now(&location, &length, &here, &self.symbols)?,
+                    None => data.len() as i64 - offset,
+                };
+                if offset < 0 || length < 0 || offset + length > data.len() as i64 {
+                    return Err(location.error(
+                        ErrorKind::InvalidValue,
+                        format!(
+                            "Range {}..{} is outside of '{}' ({} bytes)",
+                            offset,
+                            offset + length,
+                            path.display(),
+                            data.len()
+                        ),
+                    ));
+                }
+                let bytes = data[offset as usize..(offset + length) as usize].to_vec();
+                items.push((bytes.len() as u32, Item::Bytes(bytes)));
+            }
+            Statement::Include(path) => {
+                let path = location.resolve(&path);
+                let included = fs::read_to_string(&path).map_err(|error| {
+                    location.error(
+                        ErrorKind::File,
+                        format!("Can not read '{}': {}", path.display(), error),
+                    )
+                })?;
+                let frame = Frame::read(&included, Some(Rc::new(path.clone())));
+                if self.frames.iter().any(|open| open.include == frame.include) {
+                    return Err(location.error(
+                        ErrorKind::File,
+                        format!("Recursive include of '{}'", path.display()),
+                    ));
+                }
+                self.push_frame(&location, frame.lines)?;
+                self.frames.last_mut().unwrap().include = frame.include;
+            }
+            Statement::Macro(name, parameters) => {
+                if self.macros.contains_key(&name) {
+                    return Err(location.error_at(
+                        &name,
+                        ErrorKind::DuplicateSymbol,
+                        format!("Macro '{}' is already defined", name),
+                    ));
+                }
+                self.recording = Some(Recording {
+                    location: location.clone(),
+                    block: Block::Macro(name, parameters),
+                    nesting: 0,
+                    body: Vec::new(),
+                });
+            }
+            Statement::Repeat(count, variable) => {
+                let number =
+                    evaluate_now(&location, &count, &here, &self.symbols).and_then(|number| {
+                        match number {
+                            0..=0x10000 => Ok(number),
+                            _ => Err(location.error_at(
+                                &count.text,
+                                ErrorKind::InvalidValue,
+                                format!("Invalid repeat count {}", number),
+                            )),
+                        }
+                    });
+                let number = match number {
+                    Ok(number) => number,
+                    Err(error) => {
+                        self.open_failed_block(location, Some("repeat"));
+                        return Err(error);
+                    }
+                };
+                self.recording = Some(Recording {
+                    location: location.clone(),
+                    block: Block::Repeat(number as usize, variable),
+                    nesting: 0,
+                    body: Vec::new(),
+                });
+            }
+            Statement::If(condition) => {
+                let active = match evaluate_now(&location, &condition, &here, &self.symbols) {
+                    Ok(value) => value != 0,
+                    Err(error) => {
+                        self.open_failed_block(location, Some("if"));
+                        return Err(error);
+                    }
+                };
+                self.conditionals.push(Conditional {
+                    location: location.clone(),
+                    depth: self.frames.len(),
+                    active,
+                    taken: active,
+                    has_else: false,
+                });
+            }
+            Statement::EndMacro => {
+                return Err(location.error(ErrorKind::Syntax, ".endmacro without .macro"))
+            }
+            Statement::EndRepeat => {
+                return Err(location.error(ErrorKind::Syntax, ".endrepeat without .repeat"))
+            }
+            Statement::Segment(name) => {
+                let state = self.object_state(&location, "Segments")?;
+                state.segments[state.current].1 = pc;
+                state.current = match state.segments.iter().position(|(open, ..)| *open == name) {
+                    Some(index) => index,
+                    None => {
+                        state.segments.push((name, 0, 1));
+                        state.segments.len() - 1
+                    }
+                };
+                self.pc = state.segments[state.current].1;
+            }
+            Statement::Export(names) => {
+                let state = self.object_state(&location, "Exports")?;
+                for name in names {
+                    if state.exports.iter().any(|(_, exported)| *exported == name) {
+                        return Err(location.error_at(
+                            &name,
+                            ErrorKind::DuplicateSymbol,
+                            format!("Symbol '{}' is already exported", name),
+                        ));
+                    }
+                    state.exports.push((location.clone(), name));
+                }
+            }
+            Statement::Import(names, zero_page) => {
+                self.object_state(&location, "Imports")?;
+                for name in names {
+                    let base = Base {
+                        name: name.clone(),
+                        import: true,
+                        zero_page,
+                    };
+                    self.symbols.define(
+                        &location,
+                        name.clone(),
+                        Definition::Value(Relocatable::Relative(base, 0)),
+                    )?;
+                    let state = self.object.as_mut().unwrap();
+                    state.imports.push(Import { name, zero_page });
+                }
+            }
+        }
+
+        for (size, item) in items {
+            if self.pc + size > 0x10000 {
+                return Err(location.error(
+                    ErrorKind::Layout,
+                    "Program does not fit in the address space",
+                ));
+            }
+            //The address of a line is where its first item is, after a .org
+            let entry = self.listing.len() - 1;
+            if self
+                .planned
+                .last()
+                .is_none_or(|planned| planned.entry != entry)
+            {
+                self.listing[entry].address = self.pc as u16;
+            }
+            self.planned.push(Planned {
+                location: location.clone(),
+                address: self.pc as u16,
+                segment: self.object.as_ref().map_or(0, |state| state.current),
+                size,
+                item,
+                entry,
+            });
+            self.pc += size;
+        }
+        Ok(())
+    }
+
+    /// State of the relocatable object, directives which need one fail in absolute programs
+    fn object_state(
+        &mut self,
+        location: &Location,
+        feature: &str,
+    ) -> Result<&mut ObjectState, AssembleError> {
+        self.object.as_mut().ok_or_else(|| {
+            location.error(
+                ErrorKind::Syntax,
+                format!("{} can only be used in a relocatable object", feature),
+            )
+        })
+    }
+}
+
+/// Unique name of a local label like `@loop` in the given expansion
+fn local(name: &str, expansion: usize) -> Option<String> {
+    name.starts_with('@')
+        .then(|| format!("{}@{}", name, expansion))
+}
+
+/// Replace the identifiers outside of quotes and number literals, the comment is removed
+fn substitute(text: &str, replace: impl Fn(&str) -> Option<String>) -> String {
+    let text = strip_comment(text);
+    let mut result = String::new();
+    let mut chars = text.char_indices().peekable();
+    let mut quote = None;
+    while let Some((start, c)) = chars.next() {
+        let word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '@';
+        match quote {
+            Some(q) if q == c => quote = None,
+            Some(_) => (),
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if word(c) => {
+                let mut end = start + 1;
+                while let Some((index, _)) = chars.next_if(|(_, c)| word(*c)) {
+                    end = index + 1;
+                }
+                let name = &text[start..end];
+                //Hex numbers and directive names are not identifiers
+                let literal = c.is_ascii_digit() || result.ends_with('$') || result.ends_with('.');
+                match (!literal).then(|| replace(name)).flatten() {
+                    Some(replacement) => result.push_str(&replacement),
+                    None => result.push_str(name),
+                }
+                continue;
+            }
+            None => (),
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// Name of the directive of a line, lowercase and without the dot
+fn keyword(text: &str) -> Option<String> {
+    let (_, text) = split_label(strip_comment(text).trim());
+    let name = text.split_whitespace().next()?;
+    name.strip_prefix('.').map(str::to_ascii_lowercase)
+}
+
+/// Remove the comment from a line, ignoring semicolons inside quotes
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (None, ';') => return &text[..index],
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            _ => (),
+        }
+    }
+    text
+}
+
+/// Split directive arguments on commas which are not inside quotes
+fn split_arguments(text: &str) -> Vec<&str> {
+    let mut arguments = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (None, ',') => {
+                arguments.push(text[start..index].trim());
+                start = index + 1;
+            }
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            _ => (),
+        }
+    }
+    arguments.push(text[start..].trim());
+    arguments
+}
+
+/// Remove whitespace which is not inside quotes
+fn remove_whitespace(text: &str) -> String {
+    let mut result = String::new();
+    let mut quote = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => continue,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            _ => (),
+        }
+        result.push(c);
+    }
+    result
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '@' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '@')
+        }
+        _ => false,
+    }
+}
+
+/// Split the `name:` label from the rest of the line
+fn split_label(text: &str) -> (Option<&str>, &str) {
+    if let Some(position) = text.find(':') {
+        let name = text[..position].trim();
+        if is_identifier(name) {
+            return (Some(name), text[position + 1..].trim());
+        }
+    }
+    (None, text)
+}
+
+fn parse_line(location: Location, text: &str) -> Result<Line, AssembleError> {
+    let (label, text) = split_label(strip_comment(text).trim());
+    let label = label.map(str::to_string);
+    if text.is_empty() {
+        return Ok(Line {
+            location,
+            label,
+            statement: None,
+        });
+    }
+    if let Some(position) = text.find('=') {
+        let name = text[..position].trim();
+        if is_identifier(name) {
+            let value = parse_expr(&location, &text[position + 1..])?;
+            return Ok(Line {
+                location,
+                label,
+                statement: Some(Statement::Constant(name.to_string(), value.expr)),
+            });
+        }
+    }
+
+    let (name, operand) = match text.find(char::is_whitespace) {
+        Some(position) => (&text[..position], text[position..].trim()),
+        None => (text, ""),
+    };
+    let statement = match name.strip_prefix('.') {
+        Some(directive) => parse_directive(&location, directive, operand)?,
+        None => Statement::Instruction(name.to_ascii_uppercase(), operand.to_string()),
+    };
+    Ok(Line {
+        location,
+        label,
+        statement: Some(statement),
+    })
+}
+
+fn parse_directive(
+    location: &Location,
+    directive: &str,
+    text: &str,
+) -> Result<Statement, AssembleError> {
+    let directive = directive.to_ascii_lowercase();
+    let arguments = if text.is_empty() {
+        Vec::new()
+    } else {
+        split_arguments(text)
+    };
+    let count = |min: usize, max: usize| {
+        if arguments.len() < min || arguments.len() > max {
+            Err(location.error(
+                ErrorKind::Syntax,
+                format!("Wrong number of arguments for .{}", directive),
+            ))
+        } else {
+            Ok(())
+        }
+    };
+    let value = |index: usize| -> Result<Option<Value>, AssembleError> {
+        arguments
+            .get(index)
+            .map(|argument| parse_expr(location, argument))
+            .transpose()
+    };
+
+    match directive.as_str() {
+        "org" => {
+            count(1, 1)?;
+            Ok(Statement::Org(value(0)?.unwrap()))
+        }
+        "byte" | "text" => {
+            count(1, usize::MAX)?;
+            let mut data = Vec::new();
+            for argument in &arguments {
+                if argument.starts_with('"') {
+                    data.push(Data::String(parse_string(location, argument)?));
+                } else if directive == "text" {
+                    return Err(location.error_at(
+                        argument,
+                        ErrorKind::Syntax,
+                        format!("Expected a string, found '{}'", argument),
+                    ));
+                } else {
+                    data.push(Data::Value(parse_expr(location, argument)?));
+                }
+            }
+            Ok(Statement::Byte(data))
+        }
+        "word" => {
+            count(1, usize::MAX)?;
+            let values = (0..arguments.len())
+                .map(|index| value(index).map(Option::unwrap))
+                .collect::<Result<_, _>>()?;
+            Ok(Statement::Word(values))
+        }
+        "fill" => {
+            count(1, 2)?;
+            Ok(Statement::Fill(value(0)?.unwrap(), value(1)?))
+        }
+        "align" => {
+            count(1, 2)?;
+            Ok(Statement::Align(value(0)?.unwrap(), value(1)?))
+        }
+        "include" => {
+            count(1, 1)?;
+            let path = parse_string(location, arguments[0])?;
+            Ok(Statement::Include(String::from_utf8_lossy(&path).into()))
+        }
+        "macro" => {
+            let (name, parameters) = match text.find(char::is_whitespace) {
+                Some(position) => (&text[..position], split_arguments(&text[position..])),
+                None => (text, Vec::new()),
+            };
+            if !is_identifier(name) {
+                return Err(location.error_at(
+                    name,
+                    ErrorKind::Syntax,
+                    format!("Invalid macro name '{}'", name),
+                ));
+            }
+            let upper = name.to_ascii_uppercase();
+            if (0..=255)
+                .filter_map(Instructions::try_resolve)
+                .any(|instruction| instruction.mnemonic() == upper)
+            {
+                return Err(location.error_at(
+                    name,
+                    ErrorKind::Syntax,
+                    format!("Mnemonic '{}' can not be used as a macro name", upper),
+                ));
+            }
+            for (index, parameter) in parameters.iter().enumerate() {
+                if !is_identifier(parameter) || parameter.starts_with('@') {
+                    return Err(location.error_at(
+                        parameter,
+                        ErrorKind::Syntax,
+                        format!("Invalid macro parameter '{}'", parameter),
+                    ));
+                } else if parameters[..index].contains(parameter) {
+                    return Err(location.error_at(
+                        parameter,
+                        ErrorKind::Syntax,
+                        format!("Duplicate macro parameter '{}'", parameter),
+                    ));
+                }
+            }
+            Ok(Statement::Macro(
+                upper,
+                parameters.into_iter().map(str::to_string).collect(),
+            ))
+        }
+        "endmacro" => {
+            count(0, 0)?;
+            Ok(Statement::EndMacro)
+        }
+        "if" => {
+            count(1, 1)?;
+            Ok(Statement::If(value(0)?.unwrap()))
+        }
+        "repeat" => {
+            count(1, 2)?;
+            let variable = match arguments.get(1) {
+                Some(variable) if !is_identifier(variable) || variable.starts_with('@') => {
+                    return Err(location.error_at(
+                        variable,
+                        ErrorKind::Syntax,
+                        format!("Invalid repeat variable '{}'", variable),
+                    ))
+                }
+                variable => variable.map(|variable| variable.to_string()),
+            };
+            Ok(Statement::Repeat(value(0)?.unwrap(), variable))
+        }
+        "endrepeat" => {
+            count(0, 0)?;
+            Ok(Statement::EndRepeat)
+        }
+        "segment" => {
+            count(1, 1)?;
+            let name = parse_string(location, arguments[0])?;
+            Ok(Statement::Segment(String::from_utf8_lossy(&name).into()))
+        }
+        "code" | "data" | "zeropage" | "bss" => {
+            count(0, 0)?;
+            Ok(Statement::Segment(directive.to_ascii_uppercase()))
+        }
+        "export" | "import" | "importzp" => {
+            count(1, usize::MAX)?;
+            let mut names = Vec::new();
+            for name in arguments {
+                if !is_identifier(name) || name.starts_with('@') {
+                    return Err(location.error_at(
+                        name,
+                        ErrorKind::Syntax,
+                        format!("Invalid symbol name '{}'", name),
+                    ));
+                }
+                names.push(name.to_string());
+            }
+            match directive.as_str() {
+                "export" => Ok(Statement::Export(names)),
+                _ => Ok(Statement::Import(names, directive == "importzp")),
+            }
+        }
+        "incbin" => {
+            count(1, 3)?;
+            let path = parse_string(location, arguments[0])?;
+            Ok(Statement::Incbin(
+                String::from_utf8_lossy(&path).into(),
+                value(1)?,
+                value(2)?,
+            ))
+        }
+        _ => Err(location.error_at(
+            &format!(".{}", directive),
+            ErrorKind::Syntax,
+            format!("Unknown directive '.{}'", directive),
+        )),
+    }
+}
+
+/// Parse a double quoted string with `\n`, `\r`, `\t`, `\0`, `\\` and `\"` escapes
+fn parse_string(location: &Location, text: &str) -> Result<Vec<u8>, AssembleError> {
+    let invalid = || location.error_at(text, ErrorKind::Syntax, format!("Invalid string {}", text));
+    let inner = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .ok_or_else(invalid)?;
+    let mut bytes = Vec::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        let c = if c == '\\' {
+            match chars.next() {
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some('\\') => '\\',
+                Some('"') => '"',
+                _ => return Err(invalid()),
+            }
+        } else {
+            c
+        };
+        if !c.is_ascii() {
+            return Err(invalid());
+        }
+        bytes.push(c as u8);
+    }
+    Ok(bytes)
+}
+
+/// Find the `)` which closes the `(` at the start of the text
+fn closing_paren(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '(') => depth += 1,
+            (None, ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            (None, '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            _ => (),
+        }
+    }
+    None
+}
+
+fn parse_operand(location: &Location, operand: &str) -> Result<Operand, AssembleError> {
+    let upper = operand.to_ascii_uppercase();
+    if operand.is_empty() {
+        return Ok(Operand::None);
+    } else if upper == "A" {
+        return Ok(Operand::Accumulator);
+    } else if let Some(value) = operand.strip_prefix('#') {
+        return Ok(Operand::Immediate(parse_expr(location, value)?));
+    }
+    if let Some(close) = operand
+        .starts_with('(')
+        .then(|| closing_paren(operand))
+        .flatten()
+    {
+        let inner = &operand[1..close];
+        let rest = &upper[close + 1..];
+        if rest.is_empty() && inner.to_ascii_uppercase().ends_with(",X") {
+            let value = &inner[..inner.len() - 2];
+            return Ok(Operand::IndirectX(parse_expr(location, value)?));
+        } else if rest.is_empty() {
+            return Ok(Operand::Indirect(parse_expr(location, inner)?));
+        } else if rest == ",Y" {
+            return Ok(Operand::IndirectY(parse_expr(location, inner)?));
+        }
+    }
+    if upper.ends_with(",X") {
+        let value = &operand[..operand.len() - 2];
+        Ok(Operand::DirectX(parse_expr(location, value)?))
+    } else if upper.ends_with(",Y") {
+        let value = &operand[..operand.len() - 2];
+        Ok(Operand::DirectY(parse_expr(location, value)?))
+    } else {
+        Ok(Operand::Direct(parse_expr(location, operand)?))
+    }
+}
+
+fn parse_expr(location: &Location, text: &str) -> Result<Value, AssembleError> {
+    match expr::parse(text) {
+        Ok(expr) => Ok(Value {
+            expr,
+            text: text.trim().to_string(),
+        }),
+        Err(message) => Err(location.error_at(text, ErrorKind::Syntax, message)),
+    }
+}
+
+/// Error for a failed evaluation, symbol errors span the symbol and other errors `text`
+fn evaluation_error(
+    location: &Location,
+    error: EvalError,
+    first_pass: bool,
+    text: &str,
+) -> AssembleError {
+    match error {
+        EvalError::Undefined(name) if first_pass => location.error_at(
+            &name,
+            ErrorKind::UndefinedSymbol,
+            format!("Symbol '{}' must be defined before it is used here", name),
+        ),
+        EvalError::Undefined(name) => location.error_at(
+            &name,
+            ErrorKind::UndefinedSymbol,
+            format!("Undefined symbol '{}'", name),
+        ),
+        EvalError::Circular(name) => location.error_at(
+            &name,
+            ErrorKind::CircularDefinition,
+            format!("Circular definition of symbol '{}'", name),
+        ),
+        EvalError::DivisionByZero => {
+            location.error_at(text, ErrorKind::InvalidValue, "Division by zero")
+        }
+        EvalError::ShiftOutOfRange(count) => location.error_at(
+            text,
+            ErrorKind::InvalidValue,
+            format!("Shift count {} is out of range", count),
+        ),
+        EvalError::NotRelocatable => location.error_at(
+            text,
+            ErrorKind::InvalidValue,
+            format!("Expression '{}' can not be relocated", text),
+        ),
+    }
+}
+
+fn evaluate(
+    location: &Location,
+    value: &Value,
+    pc: &Relocatable,
+    symbols: &Symbols,
+) -> Result<Relocatable, AssembleError> {
+    symbols
+        .evaluate(&value.expr, pc)
+        .map_err(|error| evaluation_error(location, error, false, &value.text))
+}
+
+/// Evaluate a expression which is needed during the first pass, like an origin or a size
+fn evaluate_now(
+    location: &Location,
+    value: &Value,
+    pc: &Relocatable,
+    symbols: &Symbols,
+) -> Result<i64, AssembleError> {
+    match symbols.evaluate(&value.expr, pc) {
+        Ok(Relocatable::Absolute(number)) => Ok(number),
+        Ok(_) => Err(location.error_at(
+            &value.text,
+            ErrorKind::InvalidValue,
+            format!(
+                "Value of '{}' is not known until the object is linked",
+                value.text
+            ),
+        )),
+        Err(error) => Err(evaluation_error(location, error, true, &value.text)),
+    }
+}
+
+/// Evaluate a byte, addresses in the zero page segment and bytes of addresses
+/// are relocated by the linker
+fn byte(
+    location: &Location,
+    value: &Value,
+    pc: &Relocatable,
+    symbols: &Symbols,
+) -> Result<(u8, Option<Fixup>), AssembleError> {
+    let fixup = |kind, base, addend| Some(Fixup { kind, base, addend });
+    Ok(match evaluate(location, value, pc, symbols)? {
+        Relocatable::Absolute(number) => {
+            if !(-128..=0xFF).contains(&number) {
+                return Err(location.error_at(
+                    &value.text,
+                    ErrorKind::ValueTooLarge,
+                    format!("Value {} does not fit in a byte", number),
+                ));
+            }
+            (number as u8, None)
+        }
+        Relocatable::Relative(base, offset) if base.zero_page => {
+            (offset as u8, fixup(RelocationKind::Byte, base, offset))
+        }
+        Relocatable::Relative(..) => {
+            return Err(location.error_at(
+                &value.text,
+                ErrorKind::ValueTooLarge,
+                format!(
+                    "Address '{}' does not fit in a byte, use < or > for one of its bytes",
+                    value.text
+                ),
+            ))
+        }
+        Relocatable::Low(base, offset) => (offset as u8, fixup(RelocationKind::Low, base, offset)),
+        Relocatable::High(base, offset) => (
+            (offset >> 8) as u8,
+            fixup(RelocationKind::High, base, offset),
+        ),
+    })
+}
+
+/// Evaluate a word, addresses are relocated by the linker
+fn word(
+    location: &Location,
+    value: &Value,
+    pc: &Relocatable,
+    symbols: &Symbols,
+) -> Result<([u8; 2], Option<Fixup>), AssembleError> {
+    Ok(match evaluate(location, value, pc, symbols)? {
+        Relocatable::Absolute(number) => {
+            if !(-32768..=0xFFFF).contains(&number) {
+                return Err(location.error_at(
+                    &value.text,
+                    ErrorKind::ValueTooLarge,
+                    format!("Value {} does not fit in a word", number),
+                ));
+            }
+            ((number as u16).to_le_bytes(), None)
+        }
+        Relocatable::Relative(base, offset) => (
+            (offset as u16).to_le_bytes(),
+            Some(Fixup {
+                kind: RelocationKind::Word,
+                base,
+                addend: offset,
+            }),
+        ),
+        Relocatable::Low(..) | Relocatable::High(..) => {
+            return Err(location.error_at(
+                &value.text,
+                ErrorKind::InvalidValue,
+                format!(
+                    "'{}' is a byte of a address, it can not be a word",
+                    value.text
+                ),
+            ))
+        }
+    })
+}
+
+/// Find the instruction with given mnemonic and addressing mode
+fn find(mnemonic: &str, mode: fn(AddrCode) -> AddrMode) -> Option<Instructions> {
+    let wanted = std::mem::discriminant(&mode(AddrCode {
+        cycles: 0,
+        opcode: 0,
+    }));
+    (0..=255)
+        .filter_map(Instructions::try_resolve)
+        .find(|instruction| {
+            instruction.mnemonic() == mnemonic
+                && std::mem::discriminant(instruction.addr_mode()) == wanted
+        })
+}
+
+/// Select the addressing mode for the instruction
+///
+/// Parentheses around the whole operand always mean indirection, so `LDA ($10)` is a
+/// illegal addressing mode instead of `LDA $10`, only `JMP` has the indirect mode.
+/// ## Arguments
+/// * `mnemonic` - Uppercase mnemonic [`str`]
+/// * `text` - Operand as written [`str`]
+fn select(
+    location: &Location,
+    mnemonic: &str,
+    text: &str,
+    pc: &Relocatable,
+    symbols: &Symbols,
+) -> Result<(Instructions, Encoding), AssembleError> {
+    if !(0..=255)
+        .filter_map(Instructions::try_resolve)
+        .any(|instruction| instruction.mnemonic() == mnemonic)
+    {
+        return Err(location.error_at(
+            mnemonic,
+            ErrorKind::UnknownMnemonic,
+            format!("Unknown mnemonic '{}'", mnemonic),
+        ));
+    }
+    let operand = parse_operand(location, &remove_whitespace(text))?;
+    let illegal = || {
+        location.error_at(
+            if text.is_empty() { mnemonic } else { text },
+            ErrorKind::IllegalAddressingMode,
+            format!("Illegal addressing mode for {}", mnemonic),
+        )
+    };
+
+    //Zero page is only used when the value is already known to fit, forward
+    //references take the absolute form so the size does not change in the second pass
+    let zero_page_or_absolute = |value: Value,
+                                 zero_page: fn(AddrCode) -> AddrMode,
+                                 absolute: fn(AddrCode) -> AddrMode|
+     -> Result<(Instructions, Encoding), AssembleError> {
+        let fits = matches!(
+            symbols.evaluate(&value.expr, pc),
+            Ok(Relocatable::Absolute(0..=0xFF)
+                | Relocatable::Relative(
+                    Base {
+                        zero_page: true,
+                        ..
+                    },
+                    0..=0xFF
+                )
+                | Relocatable::Low(..)
+                | Relocatable::High(..))
+        );
+        match (find(mnemonic, zero_page), find(mnemonic, absolute)) {
+            (Some(instruction), _) if fits => Ok((instruction, Encoding::Byte(value))),
+            (_, Some(instruction)) => Ok((instruction, Encoding::Word(value))),
+            (Some(instruction), None) => Ok((instruction, Encoding::Byte(value))),
+            (None, None) => Err(illegal()),
+        }
+    };
+
+    match operand {
+        Operand::None => find(mnemonic, AddrMode::Implied)
+            .or_else(|| find(mnemonic, AddrMode::Accumulator))
+            .map(|instruction| (instruction, Encoding::None))
+            .ok_or_else(illegal),
+        Operand::Accumulator => find(mnemonic, AddrMode::Accumulator)
+            .map(|instruction| (instruction, Encoding::None))
+            .ok_or_else(illegal),
+        Operand::Immediate(value) => find(mnemonic, AddrMode::Immediate)
+            .map(|instruction| (instruction, Encoding::Byte(value)))
+            .ok_or_else(illegal),
+        Operand::Direct(value) => match find(mnemonic, AddrMode::Relative) {
+            Some(instruction) => Ok((instruction, Encoding::Relative(value))),
+            None => zero_page_or_absolute(value, AddrMode::ZeroPage, AddrMode::Absolute),
+        },
+        Operand::DirectX(value) => {
+            zero_page_or_absolute(value, AddrMode::ZeroPageX, AddrMode::AbsoluteX)
+        }
+        Operand::DirectY(value) => {
+            zero_page_or_absolute(value, AddrMode::ZeroPageY, AddrMode::AbsoluteY)
+        }
+        Operand::Indirect(value) => find(mnemonic, AddrMode::Indirect)
+            .map(|instruction| (instruction, Encoding::Word(value)))
+            .ok_or_else(illegal),
+        Operand::IndirectX(value) => find(mnemonic, AddrMode::IndirectX)
+            .map(|instruction| (instruction, Encoding::Byte(value)))
+            .ok_or_else(illegal),
+        Operand::IndirectY(value) => find(mnemonic, AddrMode::IndirectY)
+            .map(|instruction| (instruction, Encoding::Byte(value)))
+            .ok_or_else(illegal),
+    }
+}
@@ -0,0 +1,457 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use super::{symbols::Symbol, AddrMode, Instructions};
+
+/// Disassembled instruction, or a byte which is not a instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembled {
+    /// Address of the first byte
+    pub address: u16,
+    /// Raw bytes, opcode included
+    pub bytes: Vec<u8>,
+    /// Mnemonic of the instruction, `None` for bytes which can not be decoded
+    pub mnemonic: Option<&'static str>,
+    /// Operand in assembler syntax like `#$01`, `$0200,X` or `($10),Y`, labels are
+    /// substituted for addresses when symbols are given
+    pub operand: String,
+    /// Address referred by the operand, branch targets are resolved
+    pub target: Option<u16>,
+    /// Label defined at the address
+    pub label: Option<String>,
+}
+
+impl Disassembled {
+    /// Instruction in assembler syntax, `.byte $02` for bytes which can not be decoded
+    /// ## Example
+    /// ```
+    /// use rusty_6502::asm;
+    /// let lines = asm::disassemble(&[0x9D, 0x00, 0x02, 0xD0, 0x0E], 0x600, &[]);
+    /// assert_eq!(lines[0].text(), "STA $0200,X");
+    /// assert_eq!(lines[1].text(), "BNE $0613");
+    /// ```
+    pub fn text(&self) -> String {
+        match (self.mnemonic, self.operand.is_empty()) {
+            (Some(mnemonic), true) => mnemonic.to_string(),
+            (Some(mnemonic), false) => format!("{} {}", mnemonic, self.operand),
+            (None, _) => format!(".byte {}", self.operand),
+        }
+    }
+}
+
+impl Display for Disassembled {
+    /// Render the instruction as `address  bytes  instruction`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bytes = self
+            .bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(" ");
+        write!(f, "{:04X}  {:<8}  {}", self.address, bytes, self.text())
+    }
+}
+
+/// Disassembled code, one entry for every instruction
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Disassembly(pub Vec<Disassembled>);
+
+impl std::ops::Deref for Disassembly {
+    type Target = [Disassembled];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Display for Disassembly {
+    /// Render every instruction on its own line, labels are shown on the line before
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.0 {
+            if let Some(label) = &line.label {
+                writeln!(f, "{}:", label)?;
+            }
+            writeln!(f, "{}", line.to_string().trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Disassemble bytes placed at the given address
+///
+/// Bytes which are not a documented opcode, and instructions cut by the end of the
+/// bytes, are shown as `.byte`.
+/// ## Arguments
+/// * `bytes` - Bytes to disassemble [`u8`]
+/// * `base` - Address of the first byte [`u16`]
+/// * `symbols` - Labels to show instead of addresses, may be empty [`Symbol`]
+/// ## Example
+/// ```
+/// use rusty_6502::asm::{self, Symbol};
+/// let lines = asm::disassemble(&[0xA9, 0x01, 0xD0, 0xFC], 0x600, &[Symbol::label("start", 0x600)]);
+/// assert_eq!(lines.to_string(), "start:\n0600  A9 01     LDA #$01\n0602  D0 FC     BNE start\n");
+/// ```
+pub fn disassemble(bytes: &[u8], base: u16, symbols: &[Symbol]) -> Disassembly {
+    //First label at every address
+    let mut labels = BTreeMap::new();
+    for symbol in symbols {
+        if let Some(address) = symbol.address() {
+            labels.entry(address).or_insert(symbol.name.as_str());
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let address = base.wrapping_add(offset as u16);
+        let line = decode(&bytes[offset..], address, &labels);
+        offset += line.bytes.len();
+        lines.push(line);
+    }
+    Disassembly(lines)
+}
+
+/// Disassemble a range of the memory
+/// ## Arguments
+/// * `mem` - Memory to read [`crate::mem::MEM`]
+/// * `start` - First address of the range [`usize`]
+/// * `end` - Address after the range [`usize`]
+/// * `symbols` - Labels to show instead of addresses, may be empty [`Symbol`]
+pub fn disassemble_mem(
+    mem: &crate::mem::MEM,
+    start: usize,
+    end: usize,
+    symbols: &[Symbol],
+) -> Disassembly {
+    disassemble(&mem.data[start..end], start as u16, symbols)
+}
+
+/// Repeated bytes which are written as a single `.fill` row
+const FILL_RUN: usize = 16;
+
+/// Source produced by [`disassemble_flow`], displayed as re-assemblable assembly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowDisassembly {
+    /// Address of the first line
+    pub origin: u16,
+    /// Labels of addresses outside the disassembled range, defined as constants
+    pub equates: Vec<(String, u16)>,
+    /// Instructions reached from the entry points, bytes which were not reached are
+    /// data rows without a mnemonic
+    pub lines: Disassembly,
+}
+
+impl Display for FlowDisassembly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, address) in &self.equates {
+            writeln!(f, "{} = ${:04X}", name, address)?;
+        }
+        if !self.equates.is_empty() {
+            writeln!(f)?;
+        }
+        writeln!(f, ".org ${:04X}", self.origin)?;
+        for line in self.lines.iter() {
+            if let Some(label) = &line.label {
+                writeln!(f, "{}:", label)?;
+            }
+            if line.mnemonic.is_none() && line.bytes.len() >= FILL_RUN {
+                writeln!(f, "    .fill {}, ${:02X}", line.bytes.len(), line.bytes[0])?;
+            } else if shrinks(line) {
+                //Written as bytes so the assembler does not pick zero page
+                let bytes = line
+                    .bytes
+                    .iter()
+                    .map(|byte| format!("${:02X}", byte))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(f, "    .byte {} ; {}", bytes, line.text())?;
+            } else {
+                writeln!(f, "    {}", line.text())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether the instruction uses absolute addressing for a zero page address, which
+/// the assembler would encode shorter
+fn shrinks(line: &Disassembled) -> bool {
+    let instruction = match Instructions::try_resolve(line.bytes[0]) {
+        Some(instruction) if line.mnemonic.is_some() => instruction,
+        _ => return false,
+    };
+    let zero_page: fn(&AddrMode) -> bool = match instruction.addr_mode() {
+        AddrMode::Absolute(_) => |mode| matches!(mode, AddrMode::ZeroPage(_)),
+        AddrMode::AbsoluteX(_) => |mode| matches!(mode, AddrMode::ZeroPageX(_)),
+        AddrMode::AbsoluteY(_) => |mode| matches!(mode, AddrMode::ZeroPageY(_)),
+        _ => return false,
+    };
+    line.bytes[2] == 0
+        && (0..=255)
+            .filter_map(Instructions::try_resolve)
+            .any(|other| other.mnemonic() == instruction.mnemonic() && zero_page(other.addr_mode()))
+}
+
+/// Disassemble a range of the memory by following the flow of the code
+///
+/// Code is followed from the reset, NMI and IRQ vectors and the given entry points
+/// through jumps, subroutine calls and branches, until `RTS`, `RTI`, `JMP` or `BRK`.
+/// Bytes which are never reached are written as `.byte` data. Addresses referred by the
+/// code get `L0600` style labels unless a symbol names them, so the output can be
+/// assembled again into the same bytes.
+/// ## Arguments
+/// * `mem` - Memory to read [`crate::mem::MEM`]
+/// * `start` - First address of the range [`usize`]
+/// * `end` - Address after the range [`usize`]
+/// * `entries` - Entry points besides the vectors [`u16`]
+/// * `symbols` - Names of the addresses, may be empty [`Symbol`]
+/// ## Example
+/// ```
+/// use rusty_6502::{asm, mem};
+/// let mut mem = mem::MEM::new();
+/// asm::Program::new(0x600)
+///     .assemble("JMP start\n.byte 1, 2\nstart: LDX #$02\nloop: DEX\nBNE loop\nRTS")
+///     .unwrap()
+///     .fill_ram(&mut mem);
+/// let flow = asm::disassemble_flow(&mem, 0x600, 0x60B, &[0x600], &[]);
+/// assert_eq!(
+///     flow.to_string(),
+///     ".org $0600
+/// L0600:
+///     JMP L0605
+///     .byte $01, $02
+/// L0605:
+///     LDX #$02
+/// L0607:
+///     DEX
+///     BNE L0607
+///     RTS
+/// "
+/// );
+/// ```
+pub fn disassemble_flow(
+    mem: &crate::mem::MEM,
+    start: usize,
+    end: usize,
+    entries: &[u16],
+    symbols: &[Symbol],
+) -> FlowDisassembly {
+    let in_range = |address: u16| (start..end).contains(&(address as usize));
+    let vector = |address: usize| u16::from_le_bytes([mem[address], mem[address + 1]]);
+
+    //Follow the code from every entry point
+    let mut pending: Vec<u16> = [vector(0xFFFA), vector(0xFFFE), vector(0xFFFC)]
+        .into_iter()
+        .chain(entries.iter().rev().copied())
+        .filter(|address| in_range(*address))
+        .collect();
+    let roots = pending.clone();
+    let mut code = BTreeMap::new();
+    let mut claimed = vec![false; end - start];
+    while let Some(entry) = pending.pop() {
+        let mut address = entry as usize;
+        while (start..end).contains(&address) {
+            let line = decode(&mem.data[address..end], address as u16, &BTreeMap::new());
+            let claim = &mut claimed[address - start..address - start + line.bytes.len()];
+            let mnemonic = match line.mnemonic {
+                Some(mnemonic) if !claim.contains(&true) => mnemonic,
+                _ => break,
+            };
+            claim.fill(true);
+            let relative = matches!(
+                Instructions::resolve(line.bytes[0]).addr_mode(),
+                AddrMode::Relative(_)
+            );
+            if let Some(target) = line.target {
+                if relative || mnemonic == "JSR" || line.bytes[0] == 0x4C {
+                    pending.push(target);
+                }
+            }
+            code.insert(address as u16, line.bytes.len() as u16);
+            if matches!(mnemonic, "RTS" | "RTI" | "JMP" | "BRK") {
+                break;
+            }
+            address += line.bytes.len();
+        }
+    }
+
+    //Name the referred addresses, targets inside a instruction are named from its start
+    let mut names = BTreeMap::new();
+    let mut outside = BTreeMap::new();
+    for symbol in symbols {
+        if let Some(address) = symbol.address() {
+            match in_range(address) {
+                true => names.entry(address).or_insert(symbol.name.clone()),
+                false => outside.entry(address).or_insert(symbol.name.clone()),
+            };
+        }
+    }
+    let targets: BTreeSet<u16> = code
+        .keys()
+        .filter_map(|address| {
+            decode(
+                &mem.data[*address as usize..end],
+                *address,
+                &BTreeMap::new(),
+            )
+            .target
+        })
+        .chain(roots)
+        .collect();
+    let mut equates = Vec::new();
+    for target in targets {
+        if !in_range(target) {
+            if let Some(name) = outside.get(&target) {
+                equates.push((name.clone(), target));
+            }
+            continue;
+        }
+        match code.range(..=target).next_back() {
+            Some((&address, &size)) if target - address < size => {
+                let name = names
+                    .entry(address)
+                    .or_insert_with(|| format!("L{:04X}", address))
+                    .clone();
+                if target != address {
+                    names.insert(target, format!("{}+{}", name, target - address));
+                }
+            }
+            _ => {
+                names
+                    .entry(target)
+                    .or_insert_with(|| format!("L{:04X}", target));
+            }
+        }
+    }
+    let labels: BTreeMap<u16, &str> = names
+        .iter()
+        .chain(equates.iter().map(|(name, address)| (address, name)))
+        .map(|(address, name)| (*address, name.as_str()))
+        .collect();
+
+    let mut lines = Vec::new();
+    let mut address = start;
+    while address < end {
+        if code.contains_key(&(address as u16)) {
+            let mut line = decode(&mem.data[address..end], address as u16, &labels);
+            let zero_page = matches!(
+                Instructions::resolve(line.bytes[0]).addr_mode(),
+                AddrMode::ZeroPage(_)
+                    | AddrMode::ZeroPageX(_)
+                    | AddrMode::ZeroPageY(_)
+                    | AddrMode::IndirectX(_)
+                    | AddrMode::IndirectY(_)
+            );
+            if zero_page && line.target.is_some_and(|target| target >= address as u16) {
+                //Forward labels are assembled as absolute, keep the zero page address
+                line = Disassembled {
+                    label: line.label,
+                    ..decode(&mem.data[address..end], address as u16, &BTreeMap::new())
+                };
+            }
+            if let AddrMode::Relative(_) = Instructions::resolve(line.bytes[0]).addr_mode() {
+                //The assembler does not wrap branches around the address space
+                let offset = 2 + line.bytes[1] as i8 as i32;
+                if !(0..=0xFFFF).contains(&(address as i32 + offset)) {
+                    line.operand = format!("*{:+}", offset);
+                }
+            }
+            address += line.bytes.len();
+            lines.push(line);
+            continue;
+        }
+        //Data rows end at code, labels or after 8 bytes, long runs of a byte are one row
+        let first = address;
+        let data = |address: usize| {
+            address < end
+                && !claimed[address - start]
+                && (address == first || !labels.contains_key(&(address as u16)))
+        };
+        let run = (first..end)
+            .take_while(|address| data(*address) && mem[*address] == mem[first])
+            .count();
+        address += match run >= FILL_RUN {
+            true => run,
+            false => (first..first + 8)
+                .take_while(|address| data(*address))
+                .count(),
+        };
+        let bytes = mem.data[first..address].to_vec();
+        lines.push(Disassembled {
+            address: first as u16,
+            operand: bytes
+                .iter()
+                .map(|byte| format!("${:02X}", byte))
+                .collect::<Vec<_>>()
+                .join(", "),
+            bytes,
+            mnemonic: None,
+            target: None,
+            label: labels.get(&(first as u16)).map(|label| label.to_string()),
+        });
+    }
+    FlowDisassembly {
+        origin: start as u16,
+        equates,
+        lines: Disassembly(lines),
+    }
+}
+
+/// Decode the instruction at the start of the bytes
+fn decode(bytes: &[u8], address: u16, labels: &BTreeMap<u16, &str>) -> Disassembled {
+    let label = labels.get(&address).map(|label| label.to_string());
+    let instruction = Instructions::try_resolve(bytes[0]);
+    let size = instruction
+        .as_ref()
+        .map_or(1, |instruction| instruction.addr_mode().size() as usize);
+    let instruction = match instruction {
+        Some(instruction) if size <= bytes.len() => instruction,
+        _ => {
+            return Disassembled {
+                address,
+                bytes: vec![bytes[0]],
+                mnemonic: None,
+                operand: format!("${:02X}", bytes[0]),
+                target: None,
+                label,
+            }
+        }
+    };
+
+    let byte = bytes.get(1).copied().unwrap_or(0);
+    let word = u16::from_le_bytes([byte, bytes.get(2).copied().unwrap_or(0)]);
+    let name = |target: u16, text: String| match labels.get(&target) {
+        Some(label) => label.to_string(),
+        None => text,
+    };
+    let zero_page = |target: u8| name(target as u16, format!("${:02X}", target));
+    let absolute = |target: u16| name(target, format!("${:04X}", target));
+    let (operand, target) = match instruction.addr_mode() {
+        AddrMode::Accumulator(_) => ("A".to_string(), None),
+        AddrMode::Implied(_) => (String::new(), None),
+        AddrMode::Immediate(_) => (format!("#${:02X}", byte), None),
+        AddrMode::ZeroPage(_) => (zero_page(byte), Some(byte as u16)),
+        AddrMode::ZeroPageX(_) => (format!("{},X", zero_page(byte)), Some(byte as u16)),
+        AddrMode::ZeroPageY(_) => (format!("{},Y", zero_page(byte)), Some(byte as u16)),
+        AddrMode::Absolute(_) => (absolute(word), Some(word)),
+        AddrMode::AbsoluteX(_) => (format!("{},X", absolute(word)), Some(word)),
+        AddrMode::AbsoluteY(_) => (format!("{},Y", absolute(word)), Some(word)),
+        AddrMode::Indirect(_) => (format!("({})", absolute(word)), Some(word)),
+        AddrMode::IndirectX(_) => (format!("({},X)", zero_page(byte)), Some(byte as u16)),
+        AddrMode::IndirectY(_) => (format!("({}),Y", zero_page(byte)), Some(byte as u16)),
+        AddrMode::Relative(_) => {
+            let target = address.wrapping_add(2).wrapping_add(byte as i8 as u16);
+            (absolute(target), Some(target))
+        }
+    };
+    Disassembled {
+        address,
+        bytes: bytes[..size].to_vec(),
+        mnemonic: Some(instruction.mnemonic()),
+        operand,
+        target,
+        label,
+    }
+}
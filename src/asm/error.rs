@@ -0,0 +1,125 @@
+use std::fmt::Display;
+
+/// Kind of a assembler error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Malformed line, expression, directive or block
+    Syntax,
+    /// Mnemonic which is not a 6502 instruction
+    UnknownMnemonic,
+    /// Addressing mode which the instruction does not have
+    IllegalAddressingMode,
+    /// Symbol which is not defined, or not defined yet where its value is needed
+    UndefinedSymbol,
+    /// Symbol or macro which is defined twice
+    DuplicateSymbol,
+    /// Constant which depends on itself
+    CircularDefinition,
+    /// Branch target which is too far for a relative branch
+    BranchOutOfRange,
+    /// Value which does not fit in a byte or a word
+    ValueTooLarge,
+    /// Value which can not be used, like a negative count or a division by zero
+    InvalidValue,
+    /// Code which does not fit in the address space or overlaps other code
+    Layout,
+    /// File which can not be read, or includes which are nested too deeply
+    File,
+}
+
+/// Position of a error in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    /// Source file, `None` for sources given as string
+    pub file: Option<String>,
+    /// Line number starting from 1, 0 if the error is not about a line
+    pub line: usize,
+    /// Column of the first character starting from 1
+    pub column: usize,
+    /// Length in characters
+    pub length: usize,
+}
+
+/// Assembler error
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssembleError {
+    /// Kind of the error [`ErrorKind`]
+    pub kind: ErrorKind,
+    /// Error message
+    pub message: String,
+    /// Position of the error [`Span`]
+    pub span: Span,
+    /// Text of the line of the error, empty if there is no line
+    pub source_line: String,
+}
+
+impl Display for AssembleError {
+    /// Render the error like rustc does, with the offending part of the line underlined
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "error: {}", self.message)?;
+        let file = self.span.file.as_deref().unwrap_or("<source>");
+        if self.span.line == 0 {
+            return write!(f, " --> {}", file);
+        }
+        let number = self.span.line.to_string();
+        let gutter = " ".repeat(number.len());
+        writeln!(
+            f,
+            "{}--> {}:{}:{}",
+            gutter, file, self.span.line, self.span.column
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", number, self.source_line)?;
+        //Tabs are kept so the underline lines up with the source line
+        let indent: String = self
+            .source_line
+            .chars()
+            .take(self.span.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        write!(
+            f,
+            "{} | {}{}",
+            gutter,
+            indent,
+            "^".repeat(self.span.length.max(1))
+        )
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+/// Every error found while assembling, sorted by file, line and column
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssembleErrors(pub Vec<AssembleError>);
+
+impl std::ops::Deref for AssembleErrors {
+    type Target = [AssembleError];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl IntoIterator for AssembleErrors {
+    type Item = AssembleError;
+    type IntoIter = std::vec::IntoIter<AssembleError>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl Display for AssembleErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, error) in self.0.iter().enumerate() {
+            if index > 0 {
+                writeln!(f, "\n")?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AssembleErrors {}
@@ -0,0 +1,372 @@
+use std::{iter::Peekable, str::CharIndices};
+
+/// Expression used as an operand or a directive argument
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expr {
+    Number(i64),
+    Symbol(String),
+    /// Address of the current line, written as `*`
+    Pc,
+    Unary(Unary, Box<Expr>),
+    Binary(Binary, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Unary {
+    Negate,
+    Not,
+    /// Logical not, `!`
+    LogicalNot,
+    /// Low byte, `<`
+    Low,
+    /// High byte, `>`
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Binary {
+    Multiply,
+    Divide,
+    Modulo,
+    Add,
+    Subtract,
+    ShiftLeft,
+    ShiftRight,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+    And,
+    Xor,
+    Or,
+    LogicalAnd,
+    LogicalOr,
+}
+
+impl Binary {
+    /// Binding power of the operator, higher binds tighter
+    fn precedence(self) -> u8 {
+        match self {
+            Binary::Multiply | Binary::Divide | Binary::Modulo => 10,
+            Binary::Add | Binary::Subtract => 9,
+            Binary::ShiftLeft | Binary::ShiftRight => 8,
+            Binary::Less | Binary::LessOrEqual | Binary::Greater | Binary::GreaterOrEqual => 7,
+            Binary::Equal | Binary::NotEqual => 6,
+            Binary::And => 5,
+            Binary::Xor => 4,
+            Binary::Or => 3,
+            Binary::LogicalAnd => 2,
+            Binary::LogicalOr => 1,
+        }
+    }
+}
+
+/// Reason an expression could not be evaluated
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum EvalError {
+    Undefined(String),
+    Circular(String),
+    DivisionByZero,
+    /// Shift count outside of 0 to 63
+    ShiftOutOfRange(i64),
+    /// Relocatable values are used in a way the linker can not resolve
+    NotRelocatable,
+}
+
+/// Segment or imported symbol which a relocatable value is relative to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Base {
+    /// Name of the segment or the imported symbol
+    pub name: String,
+    /// Whether the base is a imported symbol instead of a segment
+    pub import: bool,
+    /// Whether the base is known to be in the zero page
+    pub zero_page: bool,
+}
+
+/// Value of an expression, addresses of relocatable objects are only known
+/// relative to their segment until the object is linked
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Relocatable {
+    Absolute(i64),
+    Relative(Base, i64),
+    /// Low byte of a relative value
+    Low(Base, i64),
+    /// High byte of a relative value
+    High(Base, i64),
+}
+
+impl Relocatable {
+    /// The value as a number, relative values are taken from the start of their base
+    pub(crate) fn number(&self) -> i64 {
+        match self {
+            Relocatable::Absolute(number) | Relocatable::Relative(_, number) => *number,
+            Relocatable::Low(_, offset) => offset & 0xFF,
+            Relocatable::High(_, offset) => (offset >> 8) & 0xFF,
+        }
+    }
+}
+
+impl Unary {
+    fn apply(self, operand: i64) -> i64 {
+        match self {
+            Unary::Negate => operand.wrapping_neg(),
+            Unary::Not => !operand,
+            Unary::LogicalNot => (operand == 0) as i64,
+            Unary::Low => operand & 0xFF,
+            Unary::High => (operand >> 8) & 0xFF,
+        }
+    }
+}
+
+impl Binary {
+    fn apply(self, left: i64, right: i64) -> Result<i64, EvalError> {
+        Ok(match self {
+            Binary::Multiply => left.wrapping_mul(right),
+            Binary::Divide | Binary::Modulo if right == 0 => return Err(EvalError::DivisionByZero),
+            Binary::ShiftLeft | Binary::ShiftRight if !(0..64).contains(&right) => {
+                return Err(EvalError::ShiftOutOfRange(right))
+            }
+            Binary::Divide => left.wrapping_div(right),
+            Binary::Modulo => left.wrapping_rem(right),
+            Binary::Add => left.wrapping_add(right),
+            Binary::Subtract => left.wrapping_sub(right),
+            Binary::ShiftLeft => left << right,
+            Binary::ShiftRight => left >> right,
+            Binary::Less => (left < right) as i64,
+            Binary::LessOrEqual => (left <= right) as i64,
+            Binary::Greater => (left > right) as i64,
+            Binary::GreaterOrEqual => (left >= right) as i64,
+            Binary::Equal => (left == right) as i64,
+            Binary::NotEqual => (left != right) as i64,
+            Binary::And => left & right,
+            Binary::Xor => left ^ right,
+            Binary::Or => left | right,
+            Binary::LogicalAnd => (left != 0 && right != 0) as i64,
+            Binary::LogicalOr => (left != 0 || right != 0) as i64,
+        })
+    }
+}
+
+impl Expr {
+    /// Evaluate the expression
+    ///
+    /// Relative values can be offset by absolute values, subtracted from values with
+    /// the same base and split into their low and high bytes, anything else can not be
+    /// relocated.
+    /// ## Arguments
+    /// * `pc` - Relocatable of `*`
+    /// * `resolve` - Resolves the value of a symbol
+    pub(crate) fn evaluate(
+        &self,
+        pc: &Relocatable,
+        resolve: &mut dyn FnMut(&str) -> Result<Relocatable, EvalError>,
+    ) -> Result<Relocatable, EvalError> {
+        Ok(match self {
+            Expr::Number(number) => Relocatable::Absolute(*number),
+            Expr::Symbol(name) => resolve(name)?,
+            Expr::Pc => pc.clone(),
+            Expr::Unary(operator, operand) => match (operator, operand.evaluate(pc, resolve)?) {
+                (_, Relocatable::Absolute(operand)) => {
+                    Relocatable::Absolute(operator.apply(operand))
+                }
+                (Unary::Low, Relocatable::Relative(base, offset)) => Relocatable::Low(base, offset),
+                (Unary::High, Relocatable::Relative(base, offset)) => {
+                    Relocatable::High(base, offset)
+                }
+                _ => return Err(EvalError::NotRelocatable),
+            },
+            Expr::Binary(operator, left, right) => {
+                let left = left.evaluate(pc, resolve)?;
+                let right = right.evaluate(pc, resolve)?;
+                match (operator, left, right) {
+                    (_, Relocatable::Absolute(left), Relocatable::Absolute(right)) => {
+                        Relocatable::Absolute(operator.apply(left, right)?)
+                    }
+                    (
+                        Binary::Add,
+                        Relocatable::Relative(base, offset),
+                        Relocatable::Absolute(number),
+                    )
+                    | (
+                        Binary::Add,
+                        Relocatable::Absolute(number),
+                        Relocatable::Relative(base, offset),
+                    ) => Relocatable::Relative(base, offset.wrapping_add(number)),
+                    (
+                        Binary::Subtract,
+                        Relocatable::Relative(base, offset),
+                        Relocatable::Absolute(number),
+                    ) => Relocatable::Relative(base, offset.wrapping_sub(number)),
+                    (
+                        Binary::Subtract,
+                        Relocatable::Relative(left, from),
+                        Relocatable::Relative(right, to),
+                    ) if left == right => Relocatable::Absolute(from.wrapping_sub(to)),
+                    _ => return Err(EvalError::NotRelocatable),
+                }
+            }
+        })
+    }
+}
+
+/// Parse an expression
+/// ## Returns
+/// The expression or a error message
+pub(crate) fn parse(text: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        text,
+        chars: text.char_indices().peekable(),
+    };
+    let expr = parser.binary(0)?;
+    parser.skip_whitespace();
+    match parser.chars.peek() {
+        None => Ok(expr),
+        Some((_, c)) => Err(format!("Unexpected '{}' in expression '{}'", c, text)),
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn invalid(&self) -> String {
+        format!("Invalid expression '{}'", self.text)
+    }
+
+    /// Take characters while they match, returns the taken text
+    fn take_while(&mut self, matches: impl Fn(char) -> bool) -> &str {
+        let start = match self.chars.peek() {
+            Some((index, _)) => *index,
+            None => return "",
+        };
+        let mut end = start;
+        while let Some((index, c)) = self.chars.next_if(|(_, c)| matches(*c)) {
+            end = index + c.len_utf8();
+        }
+        &self.text[start..end]
+    }
+
+    /// Peek the next binary operator and its length
+    fn operator(&mut self) -> Option<(Binary, usize)> {
+        self.skip_whitespace();
+        let mut lookahead = self.chars.clone();
+        let first = lookahead.next()?.1;
+        let second = lookahead.next().map(|(_, c)| c);
+        let operator = match (first, second) {
+            ('<', Some('<')) => (Binary::ShiftLeft, 2),
+            ('>', Some('>')) => (Binary::ShiftRight, 2),
+            ('<', Some('=')) => (Binary::LessOrEqual, 2),
+            ('>', Some('=')) => (Binary::GreaterOrEqual, 2),
+            ('=', Some('=')) => (Binary::Equal, 2),
+            ('!', Some('=')) => (Binary::NotEqual, 2),
+            ('&', Some('&')) => (Binary::LogicalAnd, 2),
+            ('|', Some('|')) => (Binary::LogicalOr, 2),
+            ('*', _) => (Binary::Multiply, 1),
+            ('/', _) => (Binary::Divide, 1),
+            ('%', _) => (Binary::Modulo, 1),
+            ('+', _) => (Binary::Add, 1),
+            ('-', _) => (Binary::Subtract, 1),
+            ('<', _) => (Binary::Less, 1),
+            ('>', _) => (Binary::Greater, 1),
+            ('&', _) => (Binary::And, 1),
+            ('^', _) => (Binary::Xor, 1),
+            ('|', _) => (Binary::Or, 1),
+            _ => return None,
+        };
+        Some(operator)
+    }
+
+    /// Precedence climbing over binary operators
+    fn binary(&mut self, min_precedence: u8) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while let Some((operator, length)) = self.operator() {
+            if operator.precedence() < min_precedence {
+                break;
+            }
+            for _ in 0..length {
+                self.chars.next();
+            }
+            let right = self.binary(operator.precedence() + 1)?;
+            left = Expr::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        self.skip_whitespace();
+        let operator = match self.chars.peek() {
+            Some((_, '-')) => Unary::Negate,
+            Some((_, '~')) => Unary::Not,
+            Some((_, '!')) => Unary::LogicalNot,
+            Some((_, '<')) => Unary::Low,
+            Some((_, '>')) => Unary::High,
+            Some((_, '+')) => {
+                self.chars.next();
+                return self.unary();
+            }
+            _ => return self.primary(),
+        };
+        self.chars.next();
+        Ok(Expr::Unary(operator, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        self.skip_whitespace();
+        let c = match self.chars.peek() {
+            Some((_, c)) => *c,
+            None => return Err(self.invalid()),
+        };
+        match c {
+            '(' => {
+                self.chars.next();
+                let expr = self.binary(0)?;
+                self.skip_whitespace();
+                match self.chars.next() {
+                    Some((_, ')')) => Ok(expr),
+                    _ => Err(format!("Missing ')' in expression '{}'", self.text)),
+                }
+            }
+            '*' => {
+                self.chars.next();
+                Ok(Expr::Pc)
+            }
+            '$' | '%' => {
+                self.chars.next();
+                self.skip_whitespace();
+                let radix = if c == '$' { 16 } else { 2 };
+                let digits = self.take_while(|c| c.is_ascii_alphanumeric());
+                i64::from_str_radix(digits, radix)
+                    .map(Expr::Number)
+                    .map_err(|_| format!("Invalid number '{}{}'", c, digits))
+            }
+            '\'' => {
+                self.chars.next();
+                match (self.chars.next(), self.chars.next()) {
+                    (Some((_, c)), Some((_, '\''))) if c.is_ascii() => Ok(Expr::Number(c as i64)),
+                    _ => Err(format!("Invalid character in expression '{}'", self.text)),
+                }
+            }
+            c if c.is_ascii_digit() => {
+                let digits = self.take_while(|c| c.is_ascii_alphanumeric());
+                digits
+                    .parse()
+                    .map(Expr::Number)
+                    .map_err(|_| format!("Invalid number '{}'", digits))
+            }
+            c if c.is_ascii_alphabetic() || c == '_' || c == '@' => {
+                let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '@');
+                Ok(Expr::Symbol(name.to_string()))
+            }
+            _ => Err(self.invalid()),
+        }
+    }
+}
@@ -0,0 +1,766 @@
+use std::{collections::BTreeMap, fmt::Display, iter::Peekable, vec::IntoIter};
+
+use super::{Object, RelocationKind, RelocationTarget, Segment, Symbol, SymbolKind};
+
+/// Memory area which segments are placed in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryArea {
+    /// Name of the area
+    pub name: String,
+    /// Address of the first byte
+    pub start: u16,
+    /// Size in bytes, up to the end of the address space
+    pub size: u32,
+    /// Byte for the unused parts of the area, `None` leaves them out of the image
+    pub fill: Option<u8>,
+}
+
+/// Kind of a placed segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentKind {
+    /// Code or data, written to the image
+    Load,
+    /// Written to the image and must end in the zero page
+    ZeroPage,
+    /// Reserved space, not written to the image
+    Bss,
+}
+
+/// Where the segments with a name are placed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentPlacement {
+    /// Name of the segment
+    pub name: String,
+    /// Name of the memory area [`MemoryArea`]
+    pub area: String,
+    /// Kind of the segment [`SegmentKind`]
+    pub kind: SegmentKind,
+    /// Fixed start address, `None` continues after the previous segment of the area
+    pub start: Option<u16>,
+    /// Alignment of the start address
+    pub align: u32,
+}
+
+/// Memory layout for the linker
+///
+/// Written like a ld65 configuration, numbers are `$hex`, `0xhex`, `%binary` or decimal and
+/// `#` starts a comment:
+/// ```text
+/// MEMORY {
+///     ZP:  start = $0000, size = $0100;
+///     RAM: start = $0600, size = $1000;
+///     ROM: start = $F000, size = $1000, fill = $FF;
+/// }
+/// SEGMENTS {
+///     ZEROPAGE: load = ZP, type = zp;
+///     CODE:     load = RAM, type = ro;
+///     DATA:     load = RAM, type = rw, align = $100;
+///     BSS:      load = RAM, type = bss;
+///     VECTORS:  load = ROM, start = $FFFA;
+/// }
+/// ```
+/// Segment types are `ro` and `rw` for code and data, `zp` and `bss`, the default is `ro`.
+/// Segments are placed in the order they are listed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryConfig {
+    /// Memory areas [`MemoryArea`]
+    pub areas: Vec<MemoryArea>,
+    /// Segments in the order they are placed [`SegmentPlacement`]
+    pub segments: Vec<SegmentPlacement>,
+}
+
+/// Error while reading objects or linking them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    /// Invalid memory configuration, line 0 for configurations which were not parsed
+    Config {
+        /// Line of the error starting from 1
+        line: usize,
+        /// Error message
+        message: String,
+    },
+    /// Object file or object which can not be used
+    InvalidObject(String),
+    /// Segment of a object which the configuration does not place
+    UnknownSegment {
+        /// Name of the object
+        object: String,
+        /// Name of the segment
+        segment: String,
+    },
+    /// Fixed start address of a segment which is behind the previous segments or outside
+    /// of its area
+    InvalidStart {
+        /// Name of the segment
+        segment: String,
+        /// Start address
+        start: u16,
+    },
+    /// Segments do not fit in their memory area or the zero page
+    Overflow {
+        /// Name of the memory area
+        area: String,
+        /// Name of the segment which does not fit
+        segment: String,
+        /// Missing bytes
+        bytes: u32,
+    },
+    /// Symbol exported by more than one object
+    DuplicateExport {
+        /// Name of the symbol
+        name: String,
+        /// Object which exported it first
+        first: String,
+        /// Object which exported it again
+        second: String,
+    },
+    /// Import which no object exports
+    Unresolved {
+        /// Name of the importing object
+        object: String,
+        /// Name of the symbol
+        name: String,
+    },
+    /// Relocated value which does not fit in its bytes
+    Range {
+        /// Name of the object
+        object: String,
+        /// Name of the segment
+        segment: String,
+        /// Offset of the value in the segment
+        offset: u16,
+        /// Relocated value
+        value: i64,
+    },
+}
+
+impl Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkError::Config { line: 0, message } => write!(f, "{}", message),
+            LinkError::Config { line, message } => write!(f, "line {}: {}", line, message),
+            LinkError::InvalidObject(message) => write!(f, "Invalid object: {}", message),
+            LinkError::UnknownSegment { object, segment } => write!(
+                f,
+                "Segment '{}' of '{}' is not in the memory configuration",
+                segment, object
+            ),
+            LinkError::InvalidStart { segment, start } => write!(
+                f,
+                "Segment '{}' can not start at ${:04X} in its memory area",
+                segment, start
+            ),
+            LinkError::Overflow {
+                area,
+                segment,
+                bytes,
+            } => write!(
+                f,
+                "Segment '{}' does not fit in memory area '{}' by {} bytes",
+                segment, area, bytes
+            ),
+            LinkError::DuplicateExport {
+                name,
+                first,
+                second,
+            } => write!(
+                f,
+                "Symbol '{}' is exported by '{}' and '{}'",
+                name, first, second
+            ),
+            LinkError::Unresolved { object, name } => {
+                write!(f, "Unresolved import '{}' in '{}'", name, object)
+            }
+            LinkError::Range {
+                object,
+                segment,
+                offset,
+                value,
+            } => write!(
+                f,
+                "Value ${:04X} at offset ${:04X} of segment '{}' in '{}' does not fit",
+                value, offset, segment, object
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// Segment of a object placed by the linker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placement {
+    /// Name of the object
+    pub object: String,
+    /// Name of the segment
+    pub segment: String,
+    /// Name of the memory area
+    pub area: String,
+    /// Kind of the segment [`SegmentKind`]
+    pub kind: SegmentKind,
+    /// Address of the first byte
+    pub start: u16,
+    /// Size in bytes
+    pub size: usize,
+}
+
+/// Linked program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Linked {
+    /// Bytes of the image, memory areas with a fill byte are written whole [`Segment`]
+    pub segments: Vec<Segment>,
+    /// Exported symbols with their final addresses or values [`Symbol`]
+    pub symbols: Vec<Symbol>,
+    /// Placed segments in the order they were placed [`Placement`]
+    pub placements: Vec<Placement>,
+    /// Memory areas of the configuration [`MemoryArea`]
+    pub areas: Vec<MemoryArea>,
+}
+
+impl MemoryConfig {
+    /// Parse a memory configuration, see [`MemoryConfig`] for the syntax
+    /// ## Arguments
+    /// * `text` - Contents of the configuration [`str`]
+    pub fn parse(text: &str) -> Result<MemoryConfig, LinkError> {
+        let tokens = tokenize(text);
+        let last = tokens.last().map_or(1, |(line, _)| *line);
+        let mut tokens = Tokens {
+            tokens: tokens.into_iter().peekable(),
+            last,
+        };
+        let mut config = MemoryConfig {
+            areas: Vec::new(),
+            segments: Vec::new(),
+        };
+        while let Some((line, section)) = tokens.tokens.next() {
+            let section = section.to_ascii_uppercase();
+            if section != "MEMORY" && section != "SEGMENTS" {
+                return Err(config_error(line, format!("Unknown section '{}'", section)));
+            }
+            tokens.expect("{")?;
+            loop {
+                let (line, name) = tokens.next()?;
+                if name == "}" {
+                    break;
+                }
+                tokens.expect(":")?;
+                let mut attributes = Vec::new();
+                loop {
+                    let (line, key) = tokens.next()?;
+                    if key == ";" {
+                        break;
+                    }
+                    tokens.expect("=")?;
+                    let (_, value) = tokens.next()?;
+                    attributes.push((line, key.to_ascii_lowercase(), value));
+                    match tokens.next()? {
+                        (_, separator) if separator == "," => continue,
+                        (_, separator) if separator == ";" => break,
+                        (line, found) => {
+                            return Err(config_error(
+                                line,
+                                format!("Expected ',' or ';', found '{}'", found),
+                            ))
+                        }
+                    }
+                }
+                if section == "MEMORY" {
+                    config.areas.push(area(line, name, &attributes)?);
+                } else {
+                    config.segments.push(placement(line, name, &attributes)?);
+                }
+            }
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check the names of the areas and segments
+    fn validate(&self) -> Result<(), LinkError> {
+        for (index, area) in self.areas.iter().enumerate() {
+            if self.areas[..index]
+                .iter()
+                .any(|other| other.name == area.name)
+            {
+                return Err(config_error(
+                    0,
+                    format!("Duplicate memory area '{}'", area.name),
+                ));
+            }
+        }
+        for (index, segment) in self.segments.iter().enumerate() {
+            if self.segments[..index]
+                .iter()
+                .any(|other| other.name == segment.name)
+            {
+                return Err(config_error(
+                    0,
+                    format!("Duplicate segment '{}'", segment.name),
+                ));
+            }
+            if !self.areas.iter().any(|area| area.name == segment.area) {
+                return Err(config_error(
+                    0,
+                    format!(
+                        "Segment '{}' is loaded to unknown memory area '{}'",
+                        segment.name, segment.area
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn config_error(line: usize, message: String) -> LinkError {
+    LinkError::Config { line, message }
+}
+
+/// Split a configuration into words and punctuation with their lines
+fn tokenize(text: &str) -> Vec<(usize, String)> {
+    let mut tokens = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let mut word = String::new();
+        for c in line.chars() {
+            if c.is_alphanumeric() || matches!(c, '$' | '%' | '_' | '.') {
+                word.push(c);
+                continue;
+            }
+            if !word.is_empty() {
+                tokens.push((index + 1, std::mem::take(&mut word)));
+            }
+            if !c.is_whitespace() {
+                tokens.push((index + 1, c.to_string()));
+            }
+        }
+        if !word.is_empty() {
+            tokens.push((index + 1, word));
+        }
+    }
+    tokens
+}
+
+struct Tokens {
+    tokens: Peekable<IntoIter<(usize, String)>>,
+    /// Line of the last token, for errors at the end
+    last: usize,
+}
+
+impl Tokens {
+    fn next(&mut self) -> Result<(usize, String), LinkError> {
+        self.tokens
+            .next()
+            .ok_or_else(|| config_error(self.last, "Unexpected end of configuration".to_string()))
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), LinkError> {
+        match self.next()? {
+            (_, token) if token == expected => Ok(()),
+            (line, token) => Err(config_error(
+                line,
+                format!("Expected '{}', found '{}'", expected, token),
+            )),
+        }
+    }
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    if let Some(hex) = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")) {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = text.strip_prefix('%') {
+        u32::from_str_radix(binary, 2).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+/// Number attribute in the given range
+fn number(
+    attributes: &[(usize, String, String)],
+    key: &str,
+    range: std::ops::RangeInclusive<u32>,
+) -> Result<Option<u32>, LinkError> {
+    match attributes.iter().find(|(_, name, _)| name == key) {
+        None => Ok(None),
+        Some((line, _, value)) => match parse_number(value) {
+            Some(number) if range.contains(&number) => Ok(Some(number)),
+            _ => Err(config_error(*line, format!("Invalid {} '{}'", key, value))),
+        },
+    }
+}
+
+/// Check that only known attributes are given
+fn check_keys(attributes: &[(usize, String, String)], keys: &[&str]) -> Result<(), LinkError> {
+    match attributes
+        .iter()
+        .find(|(_, key, _)| !keys.contains(&key.as_str()))
+    {
+        Some((line, key, _)) => Err(config_error(*line, format!("Unknown attribute '{}'", key))),
+        None => Ok(()),
+    }
+}
+
+fn area(
+    line: usize,
+    name: String,
+    attributes: &[(usize, String, String)],
+) -> Result<MemoryArea, LinkError> {
+    check_keys(attributes, &["start", "size", "fill"])?;
+    let missing = |key: &str| config_error(line, format!("Memory area '{}' has no {}", name, key));
+    let start = number(attributes, "start", 0..=0xFFFF)?.ok_or_else(|| missing("start"))?;
+    let size = number(attributes, "size", 0..=0x10000)?.ok_or_else(|| missing("size"))?;
+    if start + size > 0x10000 {
+        return Err(config_error(
+            line,
+            format!("Memory area '{}' ends outside of the address space", name),
+        ));
+    }
+    Ok(MemoryArea {
+        start: start as u16,
+        size,
+        fill: number(attributes, "fill", 0..=0xFF)?.map(|fill| fill as u8),
+        name,
+    })
+}
+
+fn placement(
+    line: usize,
+    name: String,
+    attributes: &[(usize, String, String)],
+) -> Result<SegmentPlacement, LinkError> {
+    check_keys(attributes, &["load", "type", "start", "align"])?;
+    let text = |key: &str| {
+        attributes
+            .iter()
+            .find(|(_, name, _)| name == key)
+            .map(|(line, _, value)| (*line, value.clone()))
+    };
+    let (_, area) = text("load")
+        .ok_or_else(|| config_error(line, format!("Segment '{}' has no load area", name)))?;
+    let kind = match text("type") {
+        None => SegmentKind::Load,
+        Some((line, kind)) => match kind.to_ascii_lowercase().as_str() {
+            "ro" | "rw" => SegmentKind::Load,
+            "zp" => SegmentKind::ZeroPage,
+            "bss" => SegmentKind::Bss,
+            _ => {
+                return Err(config_error(
+                    line,
+                    format!("Unknown segment type '{}'", kind),
+                ))
+            }
+        },
+    };
+    Ok(SegmentPlacement {
+        name,
+        area,
+        kind,
+        start: number(attributes, "start", 0..=0xFFFF)?.map(|start| start as u16),
+        align: number(attributes, "align", 1..=0x10000)?.unwrap_or(1),
+    })
+}
+
+/// Round `address` up to a multiple of `align`
+fn align(address: u32, align: u32) -> u32 {
+    address.div_ceil(align) * align
+}
+
+/// Link relocatable objects into a program
+///
+/// Segments are placed in the order of the configuration, segments with the same name
+/// follow each other in the order of the objects. Imports are resolved from the exports
+/// of every object.
+/// ## Arguments
+/// * `objects` - Objects to link [`Object`]
+/// * `config` - Memory layout [`MemoryConfig`]
+/// ## Example
+/// ```
+/// use rusty_6502::{asm::{self, MemoryConfig, Object}, mem};
+/// let main = Object::assemble("main", ".import print\nstart: JSR print\nJMP start").unwrap();
+/// let lib = Object::assemble("lib", ".export print\nprint: RTS").unwrap();
+/// let config = MemoryConfig::parse(
+///     "MEMORY { RAM: start = $0600, size = $0100; } SEGMENTS { CODE: load = RAM; }",
+/// )
+/// .unwrap();
+/// let linked = asm::link(&[main, lib], &config).unwrap();
+/// let mut mem = mem::MEM::new();
+/// linked.fill_ram(&mut mem);
+/// assert_eq!(mem.hex_dump(0x600, 0x607), "20 06 06 4C 00 06 60 ");
+/// ```
+pub fn link(objects: &[Object], config: &MemoryConfig) -> Result<Linked, LinkError> {
+    config.validate()?;
+    for object in objects {
+        for segment in &object.segments {
+            if !config
+                .segments
+                .iter()
+                .any(|placement| placement.name == segment.name)
+            {
+                return Err(LinkError::UnknownSegment {
+                    object: object.name.clone(),
+                    segment: segment.name.clone(),
+                });
+            }
+        }
+    }
+
+    //Place the segments
+    let mut cursors: Vec<u32> = config.areas.iter().map(|area| area.start as u32).collect();
+    let mut placements = Vec::new();
+    let mut bases = BTreeMap::new();
+    for placement in &config.segments {
+        let index = config
+            .areas
+            .iter()
+            .position(|area| area.name == placement.area)
+            .unwrap();
+        let area = &config.areas[index];
+        let end = area.start as u32 + area.size;
+        let cursor = &mut cursors[index];
+        if let Some(start) = placement.start {
+            if (start as u32) < *cursor || start as u32 > end {
+                return Err(LinkError::InvalidStart {
+                    segment: placement.name.clone(),
+                    start,
+                });
+            }
+            *cursor = start as u32;
+        }
+        *cursor = align(*cursor, placement.align);
+        for (object_index, object) in objects.iter().enumerate() {
+            for segment in object
+                .segments
+                .iter()
+                .filter(|segment| segment.name == placement.name)
+            {
+                *cursor = align(*cursor, segment.align);
+                let start = *cursor;
+                *cursor += segment.data.len() as u32;
+                let limit = match placement.kind {
+                    SegmentKind::ZeroPage => end.min(0x100),
+                    _ => end,
+                };
+                if *cursor > limit {
+                    return Err(LinkError::Overflow {
+                        area: area.name.clone(),
+                        segment: placement.name.clone(),
+                        bytes: *cursor - limit,
+                    });
+                }
+                bases.insert((object_index, segment.name.as_str()), start as i64);
+                placements.push(Placement {
+                    object: object.name.clone(),
+                    segment: segment.name.clone(),
+                    area: area.name.clone(),
+                    kind: placement.kind,
+                    start: start as u16,
+                    size: segment.data.len(),
+                });
+            }
+        }
+    }
+
+    //Resolve the exports
+    let mut exports: BTreeMap<&str, (i64, &str)> = BTreeMap::new();
+    let mut symbols = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        for export in &object.exports {
+            if let Some((_, first)) = exports.get(export.name.as_str()) {
+                return Err(LinkError::DuplicateExport {
+                    name: export.name.clone(),
+                    first: first.to_string(),
+                    second: object.name.clone(),
+                });
+            }
+            let symbol = match &export.segment {
+                Some(segment) => {
+                    let base = bases.get(&(index, segment.as_str())).ok_or_else(|| {
+                        LinkError::InvalidObject(format!(
+                            "Export '{}' of '{}' is in missing segment '{}'",
+                            export.name, object.name, segment
+                        ))
+                    })?;
+                    Symbol::label(&export.name, (base + export.value) as u16)
+                }
+                None => Symbol {
+                    kind: SymbolKind::Constant,
+                    value: export.value,
+                    ..Symbol::label(&export.name, 0)
+                },
+            };
+            exports.insert(&export.name, (symbol.value, &object.name));
+            symbols.push(symbol);
+        }
+    }
+    for object in objects {
+        if let Some(import) = object
+            .imports
+            .iter()
+            .find(|import| !exports.contains_key(import.name.as_str()))
+        {
+            return Err(LinkError::Unresolved {
+                object: object.name.clone(),
+                name: import.name.clone(),
+            });
+        }
+    }
+
+    //Relocate the segments and write the image
+    let mut image: Vec<(u16, Vec<u8>)> = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        for segment in &object.segments {
+            let mut data = segment.data.clone();
+            for relocation in &segment.relocations {
+                let target = match &relocation.target {
+                    RelocationTarget::Segment(name) => {
+                        bases.get(&(index, name.as_str())).copied().ok_or_else(|| {
+                            LinkError::InvalidObject(format!(
+                                "Relocation to missing segment '{}' in '{}'",
+                                name, object.name
+                            ))
+                        })?
+                    }
+                    RelocationTarget::Import(name) => exports
+                        .get(name.as_str())
+                        .map(|(value, _)| *value)
+                        .ok_or_else(|| LinkError::Unresolved {
+                            object: object.name.clone(),
+                            name: name.clone(),
+                        })?,
+                };
+                let value = target + relocation.addend;
+                let range_error = || LinkError::Range {
+                    object: object.name.clone(),
+                    segment: segment.name.clone(),
+                    offset: relocation.offset,
+                    value,
+                };
+                let bytes = match relocation.kind {
+                    RelocationKind::Word if (0..=0xFFFF).contains(&value) => {
+                        (value as u16).to_le_bytes().to_vec()
+                    }
+                    RelocationKind::Byte if (0..=0xFF).contains(&value) => vec![value as u8],
+                    RelocationKind::Word | RelocationKind::Byte => return Err(range_error()),
+                    RelocationKind::Low => vec![value as u8],
+                    RelocationKind::High => vec![(value >> 8) as u8],
+                };
+                let offset = relocation.offset as usize;
+                data.get_mut(offset..offset + bytes.len())
+                    .ok_or_else(|| {
+                        LinkError::InvalidObject(format!(
+                            "Relocation at ${:04X} is outside of segment '{}' in '{}'",
+                            offset, segment.name, object.name
+                        ))
+                    })?
+                    .copy_from_slice(&bytes);
+            }
+            let placement = config
+                .segments
+                .iter()
+                .find(|placement| placement.name == segment.name)
+                .unwrap();
+            if placement.kind != SegmentKind::Bss {
+                image.push((bases[&(index, segment.name.as_str())] as u16, data));
+            }
+        }
+    }
+
+    //Areas with a fill byte are written whole, other bytes are merged where they touch
+    image.sort_by_key(|(start, _)| *start);
+    let mut filled: Vec<Segment> = config
+        .areas
+        .iter()
+        .filter_map(|area| {
+            area.fill.map(|fill| Segment {
+                origin: area.start,
+                data: vec![fill; area.size as usize],
+            })
+        })
+        .collect();
+    let mut segments: Vec<Segment> = Vec::new();
+    for (start, data) in image {
+        let area = filled.iter_mut().find(|segment| {
+            let origin = segment.origin as usize;
+            (origin..origin + segment.data.len()).contains(&(start as usize))
+        });
+        if let Some(segment) = area {
+            let offset = (start - segment.origin) as usize;
+            segment.data[offset..offset + data.len()].copy_from_slice(&data);
+            continue;
+        }
+        match segments.last_mut() {
+            Some(last) if last.origin as usize + last.data.len() == start as usize => {
+                last.data.extend(data)
+            }
+            _ => segments.push(Segment {
+                origin: start,
+                data,
+            }),
+        }
+    }
+    segments.extend(filled);
+    segments.sort_by_key(|segment| segment.origin);
+
+    Ok(Linked {
+        segments,
+        symbols,
+        placements,
+        areas: config.areas.clone(),
+    })
+}
+
+impl Linked {
+    /// Write the image to memory
+    /// ## Arguments
+    /// * `ram` - Memory to write to [`crate::mem::MEM`]
+    pub fn fill_ram(&self, ram: &mut crate::mem::MEM) {
+        for segment in &self.segments {
+            for (i, byte) in segment.data.iter().enumerate() {
+                ram[segment.origin as usize + i] = *byte;
+            }
+        }
+    }
+
+    /// Map file with the memory areas, the placed segments and the exported symbols
+    pub fn map(&self) -> String {
+        let mut map = String::from("Memory areas:\n");
+        map += "Name             Start  End    Size   Used\n";
+        for area in &self.areas {
+            let used: usize = self
+                .placements
+                .iter()
+                .filter(|placement| placement.area == area.name)
+                .map(|placement| placement.size)
+                .sum();
+            map += &format!(
+                "{:<16} ${:04X}  ${:04X}  ${:04X}  ${:04X}\n",
+                area.name,
+                area.start,
+                (area.start as u32 + area.size).saturating_sub(1),
+                area.size,
+                used
+            );
+        }
+        map += "\nSegments:\n";
+        map += "Name             Object           Start  End    Size\n";
+        for placement in &self.placements {
+            map += &format!(
+                "{:<16} {:<16} ${:04X}  ${:04X}  ${:04X}\n",
+                placement.segment,
+                placement.object,
+                placement.start,
+                (placement.start as usize + placement.size).saturating_sub(1),
+                placement.size
+            );
+        }
+        map += "\nExports:\n";
+        for symbol in &self.symbols {
+            match symbol.address() {
+                Some(address) => map += &format!("{:<16} ${:04X}\n", symbol.name, address),
+                None => map += &format!("{:<16} {}\n", symbol.name, symbol.value),
+            }
+        }
+        map
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt::Display;
+
+/// Bytes shown for a line before the rest is left out
+const MAX_BYTES: usize = 12;
+
+/// Bytes shown on a row of the listing
+const ROW_BYTES: usize = 3;
+
+/// Line of a assembly listing
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListingLine {
+    /// Source file, `None` for sources given as string
+    pub file: Option<String>,
+    /// Line number in the source file starting from 1
+    pub line: usize,
+    /// Address of the line
+    pub address: u16,
+    /// Bytes emitted by the line
+    pub bytes: Vec<u8>,
+    /// Base cycle count of the instruction, `None` for lines without an instruction
+    pub cycles: Option<u32>,
+    /// Target address if the instruction is a branch
+    pub branch: Option<u16>,
+    /// Source line, lines of macro and repeat expansions are shown expanded
+    pub source: String,
+}
+
+impl ListingLine {
+    /// Whether the branch crosses a page when it is taken, which costs a extra cycle
+    pub fn crosses_page(&self) -> bool {
+        match self.branch {
+            Some(target) => self.address.wrapping_add(2) & 0xFF00 != target & 0xFF00,
+            None => false,
+        }
+    }
+
+    /// Text of the cycles column, branches show the not taken and taken counts
+    fn cycles_column(&self) -> String {
+        match (self.cycles, self.branch) {
+            (Some(cycles), Some(_)) if self.crosses_page() => {
+                format!("{}/{} page", cycles, cycles + 2)
+            }
+            (Some(cycles), Some(_)) => format!("{}/{}", cycles, cycles + 1),
+            (Some(cycles), None) => cycles.to_string(),
+            (None, _) => String::new(),
+        }
+    }
+}
+
+impl Display for ListingLine {
+    /// Render the line as `address  bytes  cycles  source`, bytes which do not fit
+    /// are continued on the following rows
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let mut rows = self.bytes[..self.bytes.len().min(MAX_BYTES)].chunks(ROW_BYTES);
+        let first = rows.next().unwrap_or(&[]);
+        write!(
+            f,
+            "{:04X}  {:<8}  {:<8}  {}",
+            self.address,
+            hex(first),
+            self.cycles_column(),
+            self.source
+        )?;
+        let mut address = self.address.wrapping_add(first.len() as u16);
+        for row in rows {
+            write!(f, "\n{:04X}  {}", address, hex(row))?;
+            address = address.wrapping_add(row.len() as u16);
+        }
+        if self.bytes.len() > MAX_BYTES {
+            write!(
+                f,
+                "\n{:04X}  ... {} more bytes",
+                address,
+                self.bytes.len() - MAX_BYTES
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Assembly listing, one line for every assembled source line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Listing(pub Vec<ListingLine>);
+
+impl std::ops::Deref for Listing {
+    type Target = [ListingLine];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Display for Listing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.0 {
+            writeln!(f, "{}", line.to_string().trim_end())?;
+        }
+        Ok(())
+    }
+}
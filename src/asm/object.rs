@@ -0,0 +1,293 @@
+use std::{collections::BTreeMap, path::Path};
+
+use super::{assembler, linker::LinkError, read_source, AssembleErrors};
+use crate::json::{self, Json};
+
+/// How a relocated value is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocationKind {
+    /// Little endian address
+    Word,
+    /// Address which must be in the zero page
+    Byte,
+    /// Low byte of a address, written with `<`
+    Low,
+    /// High byte of a address, written with `>`
+    High,
+}
+
+/// What a relocated value is relative to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelocationTarget {
+    /// Start of a segment of the same object
+    Segment(String),
+    /// Address of a imported symbol
+    Import(String),
+}
+
+/// Value which is patched when the object is linked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    /// Offset of the value in its segment
+    pub offset: u16,
+    /// How the value is written [`RelocationKind`]
+    pub kind: RelocationKind,
+    /// What the value is relative to [`RelocationTarget`]
+    pub target: RelocationTarget,
+    /// Value added to the address of the target
+    pub addend: i64,
+}
+
+/// Segment of a relocatable object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectSegment {
+    /// Name of the segment, like `CODE` or `ZEROPAGE`
+    pub name: String,
+    /// Alignment of the start of the segment, the largest `.align` in it
+    pub align: u32,
+    /// Bytes of the segment, relocated values are relative to the start of their target
+    pub data: Vec<u8>,
+    /// Values to patch when the segment is placed [`Relocation`]
+    pub relocations: Vec<Relocation>,
+}
+
+/// Symbol exported by a relocatable object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Export {
+    /// Name of the symbol
+    pub name: String,
+    /// Segment of a label, `None` for constants
+    pub segment: Option<String>,
+    /// Offset of a label in its segment or value of a constant
+    pub value: i64,
+}
+
+/// Symbol imported by a relocatable object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    /// Name of the symbol
+    pub name: String,
+    /// Whether the symbol is imported with `.importzp` and addressed as zero page
+    pub zero_page: bool,
+}
+
+/// Relocatable object, assembled without knowing where its segments are placed
+///
+/// Segments are opened with `.segment "NAME"` or the `.code`, `.data`, `.zeropage` and
+/// `.bss` shortcuts, lines before the first one go to `CODE`. Symbols are shared with
+/// other objects with `.export name, ...` and `.import name, ...`, or `.importzp name, ...`
+/// for zero page symbols. `.org` can not be used, the [`link`](super::link)er places the
+/// segments.
+///
+/// Addresses can be offset by numbers, subtracted from addresses in the same segment and
+/// split with `<` and `>`. A address in a byte must be in the `ZEROPAGE` segment or
+/// imported with `.importzp`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Object {
+    /// Name of the object, the file stem for objects assembled from a file
+    pub name: String,
+    /// Segments which have bytes [`ObjectSegment`]
+    pub segments: Vec<ObjectSegment>,
+    /// Exported symbols [`Export`]
+    pub exports: Vec<Export>,
+    /// Imported symbols [`Import`]
+    pub imports: Vec<Import>,
+}
+
+impl Object {
+    /// Assemble a relocatable object from 6502 assembly source
+    ///
+    /// See [`Program::assemble`](super::Program::assemble) for the syntax.
+    /// ## Parameters
+    /// * `name` - Name of the object [`str`]
+    /// * `source` - Assembly source [`str`]
+    /// ## Example
+    /// ```
+    /// use rusty_6502::asm::{Object, RelocationKind, RelocationTarget};
+    /// let object = Object::assemble("main", ".import print\nstart: JSR print\nJMP start").unwrap();
+    /// let code = &object.segments[0];
+    /// assert_eq!(code.data, [0x20, 0x00, 0x00, 0x4C, 0x00, 0x00]);
+    /// assert_eq!(code.relocations[0].target, RelocationTarget::Import("print".to_string()));
+    /// assert_eq!(code.relocations[1].kind, RelocationKind::Word);
+    /// ```
+    pub fn assemble(name: &str, source: &str) -> Result<Object, AssembleErrors> {
+        Self::assemble_from(name, source, None)
+    }
+
+    /// Assemble a relocatable object from a 6502 assembly source file, named after the file stem
+    /// ## Parameters
+    /// * `path` - Path of the source file [`Path`]
+    pub fn assemble_file(path: impl AsRef<Path>) -> Result<Object, AssembleErrors> {
+        let path = path.as_ref();
+        let source = read_source(path)?;
+        let name = path
+            .file_stem()
+            .map_or(String::new(), |stem| stem.to_string_lossy().into());
+        Self::assemble_from(&name, &source, Some(path))
+    }
+
+    fn assemble_from(
+        name: &str,
+        source: &str,
+        file: Option<&Path>,
+    ) -> Result<Object, AssembleErrors> {
+        let assembled =
+            assembler::assemble(source, file, 0, &BTreeMap::new(), true).map_err(AssembleErrors)?;
+        Ok(Object {
+            name: name.to_string(),
+            ..assembled.object.unwrap()
+        })
+    }
+
+    /// Write the object as JSON, bytes are written as a hex string
+    pub fn write(&self) -> String {
+        let segments = self.segments.iter().map(|segment| {
+            let data: Vec<String> = segment
+                .data
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect();
+            let relocations = segment.relocations.iter().map(|relocation| {
+                let kind = match relocation.kind {
+                    RelocationKind::Word => "word",
+                    RelocationKind::Byte => "byte",
+                    RelocationKind::Low => "low",
+                    RelocationKind::High => "high",
+                };
+                let (target, name) = match &relocation.target {
+                    RelocationTarget::Segment(name) => ("segment", name),
+                    RelocationTarget::Import(name) => ("import", name),
+                };
+                Json::object([
+                    ("offset", (relocation.offset as i64).into()),
+                    ("kind", kind.into()),
+                    (target, name.as_str().into()),
+                    ("addend", relocation.addend.into()),
+                ])
+            });
+            Json::object([
+                ("name", segment.name.as_str().into()),
+                ("align", (segment.align as i64).into()),
+                ("data", data.concat().into()),
+                ("relocations", Json::Array(relocations.collect())),
+            ])
+        });
+        let exports = self.exports.iter().map(|export| {
+            Json::object([
+                ("name", export.name.as_str().into()),
+                ("segment", export.segment.clone().into()),
+                ("value", export.value.into()),
+            ])
+        });
+        let imports = self.imports.iter().map(|import| {
+            Json::object([
+                ("name", import.name.as_str().into()),
+                ("zeropage", import.zero_page.into()),
+            ])
+        });
+        let object = Json::object([
+            ("name", self.name.as_str().into()),
+            ("segments", Json::Array(segments.collect())),
+            ("exports", Json::Array(exports.collect())),
+            ("imports", Json::Array(imports.collect())),
+        ]);
+        format!("{}\n", object)
+    }
+
+    /// Read a object written with [`Object::write`]
+    /// ## Parameters
+    /// * `text` - Contents of the object file [`str`]
+    pub fn read(text: &str) -> Result<Object, LinkError> {
+        let json = json::parse(text).map_err(LinkError::InvalidObject)?;
+        let invalid = |what: &str| LinkError::InvalidObject(format!("Invalid {}", what));
+        let string = |json: &Json, field: &str| -> Result<String, LinkError> {
+            json.get(field)
+                .and_then(Json::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| invalid(field))
+        };
+        let number = |json: &Json, field: &str| json.get(field).and_then(Json::as_i64);
+        let array = |field: &str| -> Result<&[Json], LinkError> {
+            json.get(field)
+                .and_then(Json::as_array)
+                .ok_or_else(|| invalid(field))
+        };
+
+        let mut segments = Vec::new();
+        for segment in array("segments")? {
+            let hex = string(segment, "data")?;
+            let data = (0..hex.len())
+                .step_by(2)
+                .map(|index| {
+                    hex.get(index..index + 2)
+                        .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                })
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| invalid("segment data"))?;
+            let mut relocations = Vec::new();
+            for relocation in segment
+                .get("relocations")
+                .and_then(Json::as_array)
+                .ok_or_else(|| invalid("relocations"))?
+            {
+                let kind = match relocation.get("kind").and_then(Json::as_str) {
+                    Some("word") => RelocationKind::Word,
+                    Some("byte") => RelocationKind::Byte,
+                    Some("low") => RelocationKind::Low,
+                    Some("high") => RelocationKind::High,
+                    _ => return Err(invalid("relocation kind")),
+                };
+                let target = match (string(relocation, "segment"), string(relocation, "import")) {
+                    (Ok(name), _) => RelocationTarget::Segment(name),
+                    (_, Ok(name)) => RelocationTarget::Import(name),
+                    _ => return Err(invalid("relocation target")),
+                };
+                relocations.push(Relocation {
+                    offset: number(relocation, "offset")
+                        .and_then(|offset| u16::try_from(offset).ok())
+                        .ok_or_else(|| invalid("relocation offset"))?,
+                    kind,
+                    target,
+                    addend: number(relocation, "addend").unwrap_or(0),
+                });
+            }
+            segments.push(ObjectSegment {
+                name: string(segment, "name")?,
+                align: number(segment, "align")
+                    .and_then(|align| u32::try_from(align).ok())
+                    .filter(|align| *align > 0)
+                    .unwrap_or(1),
+                data,
+                relocations,
+            });
+        }
+        let exports = array("exports")?
+            .iter()
+            .map(|export| {
+                Ok(Export {
+                    name: string(export, "name")?,
+                    segment: string(export, "segment").ok(),
+                    value: number(export, "value").ok_or_else(|| invalid("export value"))?,
+                })
+            })
+            .collect::<Result<_, LinkError>>()?;
+        let imports = array("imports")?
+            .iter()
+            .map(|import| {
+                Ok(Import {
+                    name: string(import, "name")?,
+                    zero_page: import
+                        .get("zeropage")
+                        .and_then(Json::as_bool)
+                        .unwrap_or(false),
+                })
+            })
+            .collect::<Result<_, LinkError>>()?;
+        Ok(Object {
+            name: string(&json, "name")?,
+            segments,
+            exports,
+            imports,
+        })
+    }
+}
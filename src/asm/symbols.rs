@@ -0,0 +1,258 @@
+use std::fmt::Display;
+
+use crate::json::{self, Json};
+
+/// Kind of a symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// Address of a line, defined with `name:`
+    Label,
+    /// Value defined with `NAME = value`
+    Constant,
+}
+
+/// Scope of a symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolScope {
+    /// Visible in the whole program
+    Global,
+    /// Label starting with `@`, local to a macro or repeat expansion
+    Local,
+}
+
+/// Symbol defined by a assembled program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    /// Name of the symbol
+    pub name: String,
+    /// Address of a label or value of a constant
+    pub value: i64,
+    /// Kind of the symbol [`SymbolKind`]
+    pub kind: SymbolKind,
+    /// Bytes from a label to the next label or the end of its segment, 0 for constants
+    pub size: usize,
+    /// Scope of the symbol [`SymbolScope`]
+    pub scope: SymbolScope,
+    /// Source file of the definition, `None` for sources given as string
+    pub file: Option<String>,
+    /// Line of the definition starting from 1, 0 if it is not known
+    pub line: usize,
+}
+
+impl Symbol {
+    /// Create a global label without a source location, like the ones read from
+    /// VICE or `name = $addr` files
+    /// ## Arguments
+    /// * `name` - Name of the label [`str`]
+    /// * `address` - Address of the label [`u16`]
+    pub fn label(name: &str, address: u16) -> Self {
+        Symbol {
+            name: name.to_string(),
+            value: address as i64,
+            kind: SymbolKind::Label,
+            size: 0,
+            scope: SymbolScope::Global,
+            file: None,
+            line: 0,
+        }
+    }
+
+    /// Address of the symbol if it is a label
+    pub fn address(&self) -> Option<u16> {
+        match self.kind {
+            SymbolKind::Label => Some(self.value as u16),
+            SymbolKind::Constant => None,
+        }
+    }
+}
+
+/// Format of a symbol file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolFormat {
+    /// VICE monitor label file, `al C:0600 .start` lines, local labels are left out
+    Vice,
+    /// `name = $0600` lines
+    Simple,
+    /// JSON array of symbols with their address or value, size, scope and source location
+    Json,
+}
+
+/// Error while reading a symbol file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolError {
+    /// Line of the error starting from 1, 0 for errors in JSON files
+    pub line: usize,
+    /// Error message
+    pub message: String,
+}
+
+impl Display for SymbolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            0 => write!(f, "{}", self.message),
+            line => write!(f, "line {}: {}", line, self.message),
+        }
+    }
+}
+
+impl std::error::Error for SymbolError {}
+
+/// Write symbols in the given format
+/// ## Arguments
+/// * `symbols` - Symbols to write [`Symbol`]
+/// * `format` - Format of the output [`SymbolFormat`]
+/// ## Example
+/// ```
+/// use rusty_6502::asm::{self, SymbolFormat};
+/// let mut program = asm::Program::new(0x600);
+/// program.assemble("start: NOP\nCOUNT = 3").unwrap();
+/// assert_eq!(
+///     asm::write_symbols(&program.symbols, SymbolFormat::Simple),
+///     "start = $0600\nCOUNT = 3\n"
+/// );
+/// assert_eq!(
+///     asm::write_symbols(&program.symbols, SymbolFormat::Vice),
+///     "al C:0600 .start\n"
+/// );
+/// ```
+pub fn write_symbols(symbols: &[Symbol], format: SymbolFormat) -> String {
+    let mut output = String::new();
+    match format {
+        SymbolFormat::Vice => {
+            for symbol in symbols {
+                if let (Some(address), SymbolScope::Global) = (symbol.address(), symbol.scope) {
+                    output += &format!("al C:{:04X} .{}\n", address, symbol.name);
+                }
+            }
+        }
+        SymbolFormat::Simple => {
+            for symbol in symbols {
+                match symbol.address() {
+                    Some(address) => output += &format!("{} = ${:04X}\n", symbol.name, address),
+                    None => output += &format!("{} = {}\n", symbol.name, symbol.value),
+                }
+            }
+        }
+        SymbolFormat::Json => {
+            output += "[";
+            for (index, symbol) in symbols.iter().enumerate() {
+                let (kind, value) = match symbol.kind {
+                    SymbolKind::Label => ("label", "address"),
+                    SymbolKind::Constant => ("constant", "value"),
+                };
+                let scope = match symbol.scope {
+                    SymbolScope::Global => "global",
+                    SymbolScope::Local => "local",
+                };
+                let json = Json::object([
+                    ("name", symbol.name.as_str().into()),
+                    ("kind", kind.into()),
+                    (value, symbol.value.into()),
+                    ("size", (symbol.size as i64).into()),
+                    ("scope", scope.into()),
+                    ("file", symbol.file.clone().into()),
+                    ("line", (symbol.line as i64).into()),
+                ]);
+                output += if index == 0 { "\n  " } else { ",\n  " };
+                output += &json.to_string();
+            }
+            output += "\n]\n";
+        }
+    }
+    output
+}
+
+/// Read symbols from any of the [`SymbolFormat`]s, the format is detected from the text
+/// ## Arguments
+/// * `text` - Contents of the symbol file [`str`]
+pub fn read_symbols(text: &str) -> Result<Vec<Symbol>, SymbolError> {
+    if text.trim_start().starts_with('[') {
+        return read_json(text);
+    }
+    let mut symbols = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let error = |message: String| SymbolError {
+            line: index + 1,
+            message,
+        };
+        let line = line.split(';').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let symbol = if words.next() == Some("al") {
+            //VICE: al C:0600 .name
+            let (address, name) = match (words.next(), words.next()) {
+                (Some(address), Some(name)) => (address, name),
+                _ => return Err(error(format!("Invalid VICE label '{}'", line))),
+            };
+            let address = address.rsplit(':').next().unwrap();
+            let address = u16::from_str_radix(address, 16)
+                .map_err(|_| error(format!("Invalid address '{}'", address)))?;
+            Symbol::label(name.strip_prefix('.').unwrap_or(name), address)
+        } else {
+            //Simple: name = $0600
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("Expected 'name = value', found '{}'", line)))?;
+            let (name, value) = (name.trim(), value.trim());
+            match value.strip_prefix('$') {
+                Some(hex) => {
+                    let address = u16::from_str_radix(hex, 16)
+                        .map_err(|_| error(format!("Invalid address '{}'", value)))?;
+                    Symbol::label(name, address)
+                }
+                None => Symbol {
+                    kind: SymbolKind::Constant,
+                    value: value
+                        .parse()
+                        .map_err(|_| error(format!("Invalid value '{}'", value)))?,
+                    ..Symbol::label(name, 0)
+                },
+            }
+        };
+        symbols.push(symbol);
+    }
+    Ok(symbols)
+}
+
+fn read_json(text: &str) -> Result<Vec<Symbol>, SymbolError> {
+    let error = |message: String| SymbolError { line: 0, message };
+    let json = json::parse(text).map_err(error)?;
+    let entries = json
+        .as_array()
+        .ok_or_else(|| error("Expected a array of symbols".to_string()))?;
+    entries
+        .iter()
+        .map(|entry| {
+            let name = entry
+                .get("name")
+                .and_then(Json::as_str)
+                .ok_or_else(|| error(format!("Symbol without a name: {}", entry)))?;
+            let (kind, value) = match (entry.get("address"), entry.get("value")) {
+                (Some(address), _) => (SymbolKind::Label, address),
+                (None, Some(value)) => (SymbolKind::Constant, value),
+                (None, None) => {
+                    return Err(error(format!("Symbol '{}' has no address or value", name)))
+                }
+            };
+            let value = value
+                .as_i64()
+                .ok_or_else(|| error(format!("Invalid value of symbol '{}'", name)))?;
+            let number =
+                |field: &str| entry.get(field).and_then(Json::as_i64).unwrap_or(0).max(0) as usize;
+            Ok(Symbol {
+                name: name.to_string(),
+                value,
+                kind,
+                size: number("size"),
+                scope: match entry.get("scope").and_then(Json::as_str) {
+                    Some("local") => SymbolScope::Local,
+                    _ => SymbolScope::Global,
+                },
+                file: entry.get("file").and_then(Json::as_str).map(str::to_string),
+                line: number("line"),
+            })
+        })
+        .collect()
+}
@@ -1,61 +1,390 @@
 use rusty_6502::{
-    cpu::{self, Step},
-    debugger, mem,
+    asm, cpu, dap::DapServer, debugger, gdb::GdbServer, image, mem, monitor::Monitor, trace,
 };
-use std::{fs::File, io::Write};
+use std::{
+    fs::File,
+    io::{self, Write},
+    process,
+};
+
+const USAGE: &str = "Usage:
+    main                        Start the machine language monitor, like `main monitor`
+    main disasm <file> [--base ADDR] [--start ADDR] [--end ADDR] [--symbols FILE]
+                [--flow] [--entry ADDR]...
+                                Disassemble a raw, Intel HEX, S-record or PRG image, the
+                                format is picked by extension and raw binaries are loaded
+                                at the base address, --end is the last disassembled byte,
+                                --flow follows the code from the vectors and entry points
+                                and prints source which can be assembled again
+    main trace <file> [--base ADDR] [--start ADDR] [--from ADDR] [--to ADDR]
+                [--skip N] [--count N] [--max N] [--out FILE]
+                                Run a image and write a line for every instruction, only
+                                instructions between --from and --to are written, after
+                                skipping --skip of them and up to --count lines. Runs until
+                                BRK or --max instructions, 1000000 by default
+    main compare <trace> <reference> [--context N] [--no-cycles] [--mask HEX]
+    main compare --run <file> <reference> [--base ADDR] [--context N] [--no-cycles]
+                [--mask HEX]
+                                Find the first instruction where a trace differs from a
+                                reference trace, --mask selects the compared status bits.
+                                --run traces a image from the registers of the first
+                                reference line instead, shows the machine state at the
+                                divergence and starts the monitor there
+    main monitor [file] [--base ADDR]
+                                Start the machine language monitor on stdin and stdout,
+                                optionally loading a image first. Type ? for the commands
+    main gdb <file> [--base ADDR] [--start ADDR] [--port N | --stdio]
+                                Serve the GDB remote protocol for a image on a local port,
+                                1234 by default, or on stdin and stdout. Use
+                                `target remote localhost:1234` or `target remote | main gdb
+                                <file> --stdio` in GDB
+    main dap                    Serve the Debug Adapter Protocol on stdin and stdout for
+                                editors, the program is given by the launch request";
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        None => run_monitor(&[]),
+        Some("disasm") => disasm(&args[1..]),
+        Some("trace") => run_trace(&args[1..]),
+        Some("compare") => compare(&args[1..]),
+        Some("monitor") => run_monitor(&args[1..]),
+        Some("gdb") => run_gdb(&args[1..]),
+        Some("dap") => DapServer::new()
+            .serve(io::stdin().lock(), io::stdout())
+            .map_err(|error| error.to_string()),
+        Some("-h" | "--help") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(command) => Err(format!("Unknown command '{}'\n{}", command, USAGE)),
+    };
+    if let Err(error) = result {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
+
+//Parse $0600, 0x600 or 1536
+fn parse_address(text: &str) -> Result<u16, String> {
+    let number = match text.strip_prefix('$').or_else(|| text.strip_prefix("0x")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    number.map_err(|_| format!("Invalid address '{}'", text))
+}
+
+fn disasm(args: &[String]) -> Result<(), String> {
+    let mut file = None;
+    let mut base = 0;
+    let mut start = None;
+    let mut end = None;
+    let mut symbols = Vec::new();
+    let mut flow = false;
+    let mut entries = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for '{}'", arg))
+        };
+        match arg.as_str() {
+            "--base" => base = parse_address(value()?)?,
+            "--start" => start = Some(parse_address(value()?)? as usize),
+            //The end is inclusive so the last byte of the address space can be reached
+            "--end" => end = Some(parse_address(value()?)? as usize + 1),
+            "--entry" => entries.push(parse_address(value()?)?),
+            "--flow" => flow = true,
+            "--symbols" => {
+                let path = value()?;
+                let text = std::fs::read_to_string(path)
+                    .map_err(|error| format!("Can not read '{}': {}", path, error))?;
+                symbols =
+                    asm::read_symbols(&text).map_err(|error| format!("{}: {}", path, error))?;
+            }
+            _ if file.is_none() => file = Some(arg),
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, USAGE)),
+        }
+    }
+    let file = file.ok_or_else(|| format!("Missing file\n{}", USAGE))?;
     let mut mem = mem::MEM::new();
-    let mut cpu = cpu::CPU::new(&|e| match e {
-        debugger::MessageType::LineExecuted(ins, cocyl) => {
-            println!(
-                "| INS: {:02x} ({:?}), Consumed cycles: {}",
-                ins,
-                rusty_6502::asm::Instructions::resolve(ins),
-                cocyl
-            );
-        }
-        debugger::MessageType::Waiting => todo!(),
-        debugger::MessageType::Overflow => todo!(),
-        debugger::MessageType::BreakpointHit => todo!(),
-    });
-    cpu.step = Step::Supervised;
-
-    cpu.reset(600, &mut mem);
-
-    rusty_6502::asm::Program::new(600)
-        .get_from_str("a2 01 a0 03 8c 02 02 de 01 02")
-        .fill_ram(&mut mem);
-
-    let (cycles, end) = cpu.execute_continuous(&mut mem);
-    println!("\nA: {:02x} X: {:02x} Y: {:02x}", cpu.A, cpu.X, cpu.Y);
-    println!(
-        "| NV-BDIZC |\n| {}{}{}{}{}{}{}{} |",
-        cpu.status_flags.N,
-        cpu.status_flags.V,
-        cpu.status_flags.U,
-        cpu.status_flags.B,
-        cpu.status_flags.D,
-        cpu.status_flags.I,
-        cpu.status_flags.Z,
-        cpu.status_flags.C
-    );
-    //println!("NMI: {:02x}   BRK: {:02x} IRQ: {:02x}", cpu.status_flags.N, cpu.status_flags.B, cpu.IRQ);
-    println!("PC: {} SP: {:02x}", cpu.PC, cpu.SP);
-    println!("PS: {:02x}", cpu.status_flags.get_ps());
-    println!("Program started at PC: {}", 600);
-    println!("Program ended at: {}", end);
-    println!("Program took cycles: {}", cycles);
-    println!(
-        "Program: {}: {}",
-        600,
-        mem.hex_dump(600, 600 + (cycles) as usize)
+    let loaded =
+        image::load_file(&mut mem, file, base).map_err(|error| format!("{}: {}", file, error))?;
+    entries.extend(loaded.entry);
+    let start = start.unwrap_or(
+        loaded
+            .ranges
+            .first()
+            .map_or(0, |range| *range.start() as usize),
     );
-    println!("Memory dumped to mem.dump");
-    File::create("mem.dump")
-        .unwrap()
-        .write_all(&mem.data)
-        .unwrap();
+    let end = end
+        .unwrap_or(
+            loaded
+                .ranges
+                .last()
+                .map_or(0, |range| *range.end() as usize + 1),
+        )
+        .max(start);
+    match flow {
+        true => print!(
+            "{}",
+            asm::disassemble_flow(&mem, start, end, &entries, &symbols)
+        ),
+        false => print!("{}", asm::disassemble_mem(&mem, start, end, &symbols)),
+    }
+    Ok(())
+}
+
+fn run_monitor(args: &[String]) -> Result<(), String> {
+    let mut file = None;
+    let mut base = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--base" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'", arg))?;
+                base = Some(parse_address(value)?);
+            }
+            _ if file.is_none() => file = Some(arg),
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, USAGE)),
+        }
+    }
+    let mut monitor = Monitor::new();
+    let mut out = io::stdout();
+    if let Some(file) = file {
+        let mut command = format!("l {}", file);
+        if let Some(base) = base {
+            command += &format!(" {:04X}", base);
+        }
+        monitor
+            .command(&command, &mut out)
+            .map_err(|error| error.to_string())?;
+    }
+    monitor
+        .run(io::stdin().lock(), out)
+        .map_err(|error| error.to_string())
+}
+
+fn run_gdb(args: &[String]) -> Result<(), String> {
+    let mut file = None;
+    let mut base = 0;
+    let mut start = None;
+    let mut port = 1234;
+    let mut stdio = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for '{}'", arg))
+        };
+        match arg.as_str() {
+            "--base" => base = parse_address(value()?)?,
+            "--start" => start = Some(parse_address(value()?)?),
+            "--port" => {
+                let text = value()?;
+                port = text
+                    .parse()
+                    .map_err(|_| format!("Invalid port '{}'", text))?;
+            }
+            "--stdio" => stdio = true,
+            _ if file.is_none() => file = Some(arg),
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, USAGE)),
+        }
+    }
+    let file = file.ok_or_else(|| format!("Missing file\n{}", USAGE))?;
+    let mut server = GdbServer::new();
+    let loaded = image::load_file(&mut server.mem, file, base)
+        .map_err(|error| format!("{}: {}", file, error))?;
+    server.cpu.PC = start
+        .or(loaded.entry)
+        .or(loaded.ranges.first().map(|range| *range.start()))
+        .unwrap_or(0);
+    let result = match stdio {
+        true => server.serve(io::stdin().lock(), io::stdout()),
+        false => {
+            eprintln!("Waiting for GDB on localhost:{}", port);
+            server.listen(("127.0.0.1", port))
+        }
+    };
+    result.map_err(|error| error.to_string())
+}
+
+//Parse a decimal count
+fn parse_count(text: &str) -> Result<usize, String> {
+    text.parse()
+        .map_err(|_| format!("Invalid number '{}'", text))
+}
+
+fn run_trace(args: &[String]) -> Result<(), String> {
+    let mut file = None;
+    let mut base = 0;
+    let mut start = None;
+    let mut from = 0;
+    let mut to = 0xFFFF;
+    let mut skip = 0;
+    let mut count = None;
+    let mut max = 1_000_000;
+    let mut out = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for '{}'", arg))
+        };
+        match arg.as_str() {
+            "--base" => base = parse_address(value()?)?,
+            "--start" => start = Some(parse_address(value()?)?),
+            "--from" => from = parse_address(value()?)?,
+            "--to" => to = parse_address(value()?)?,
+            "--skip" => skip = parse_count(value()?)?,
+            "--count" => count = Some(parse_count(value()?)?),
+            "--max" => max = parse_count(value()?)?,
+            "--out" => out = Some(value()?),
+            _ if file.is_none() => file = Some(arg),
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, USAGE)),
+        }
+    }
+    let file = file.ok_or_else(|| format!("Missing file\n{}", USAGE))?;
+    let mut mem = mem::MEM::new();
+    let loaded =
+        image::load_file(&mut mem, file, base).map_err(|error| format!("{}: {}", file, error))?;
+    let start = start
+        .or(loaded.entry)
+        .or(loaded.ranges.first().map(|range| *range.start()))
+        .unwrap_or(0);
+    let mut cpu = cpu::CPU::new(|_: debugger::MessageType| {});
+    cpu.PC = start;
+
+    let writer: Box<dyn Write> = match out {
+        Some(path) => {
+            Box::new(io::BufWriter::new(File::create(path).map_err(|error| {
+                format!("Can not create '{}': {}", path, error)
+            })?))
+        }
+        None => Box::new(io::BufWriter::new(io::stdout().lock())),
+    };
+    let mut tracer = trace::Tracer::new(writer);
+    tracer.range(from..=to).skip(skip);
+    if let Some(count) = count {
+        tracer.limit(count);
+    }
+    tracer
+        .run(&mut cpu, &mut mem, max)
+        .map_err(|error| error.to_string())?;
+    Ok(())
+}
+
+fn read_text(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|error| format!("Can not read '{}': {}", path, error))
+}
+
+fn compare(args: &[String]) -> Result<(), String> {
+    let mut files = Vec::new();
+    let mut run = None;
+    let mut base = 0;
+    let mut options = trace::CompareOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for '{}'", arg))
+        };
+        match arg.as_str() {
+            "--run" => run = Some(value()?),
+            "--base" => base = parse_address(value()?)?,
+            "--context" => options.context = parse_count(value()?)?,
+            "--no-cycles" => options.cycles = false,
+            "--mask" => {
+                let mask = value()?;
+                options.status_mask = u8::from_str_radix(mask.trim_start_matches('$'), 16)
+                    .map_err(|_| format!("Invalid mask '{}'", mask))?;
+            }
+            _ => files.push(arg),
+        }
+    }
+    let (actual, reference) = match (run, files.as_slice()) {
+        (None, [actual, reference]) => (read_text(actual)?, read_text(reference)?),
+        (Some(image), [reference]) => {
+            let reference = read_text(reference)?;
+            (run_reference(image, base, &reference, None)?, reference)
+        }
+        _ => return Err(format!("Expected a trace and a reference\n{}", USAGE)),
+    };
+    let Some(divergence) = trace::compare_traces(&actual, &reference, &options) else {
+        println!("Traces match");
+        return Ok(());
+    };
+    print!("{}", divergence);
+    if let Some(image) = run {
+        run_reference(image, base, &reference, Some(divergence.index))?;
+    }
+    Err(format!(
+        "Traces diverge at instruction {}",
+        divergence.index + 1
+    ))
+}
+
+//Trace a image from the state of the first reference line, up to the length of the
+//reference or, when stopping, up to a instruction whose machine state is printed and
+//given to the monitor
+fn run_reference(
+    image: &str,
+    base: u16,
+    reference: &str,
+    stop: Option<usize>,
+) -> Result<String, String> {
+    let entries = trace::parse_trace(reference);
+    let first = entries
+        .first()
+        .ok_or_else(|| "Reference has no instructions".to_string())?;
+    let mut mem = mem::MEM::new();
+    image::load_file(&mut mem, image, base).map_err(|error| format!("{}: {}", image, error))?;
+    let mut cpu = cpu::CPU::new(|_: debugger::MessageType| {});
+    cpu.set_state(&cpu::CpuState {
+        PC: first.pc,
+        SR: first.p,
+        SP: first.sp,
+        A: first.a,
+        X: first.x,
+        Y: first.y,
+        status_flags: cpu::StatusFlags::from_bits(first.p),
+    });
+    let mut tracer = trace::Tracer::new(Vec::new());
+    tracer.cycles(first.cycles.unwrap_or(0));
+    if stop.is_some() {
+        //Only the cycles are needed when replaying
+        tracer.skip(usize::MAX);
+    }
+    let instructions = stop.unwrap_or(entries.len());
+    //Unimplemented instructions panic, the trace ends there
+    let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        tracer.run(&mut cpu, &mut mem, instructions)
+    }));
+    if stop.is_some() {
+        let state = cpu.state();
+        println!("\nMachine state before instruction {}:", instructions + 1);
+        println!("{}", trace::trace_line(&state, &mem, tracer.total_cycles()));
+        println!(
+            "Stack: {}",
+            mem.hex_dump(0x100 + state.SP as usize + 1, 0x200)
+        );
+        println!("Zero page:");
+        for row in (0..0x100).step_by(16) {
+            println!("  {:04X}  {}", row, mem.hex_dump(row, row + 16));
+        }
+
+        println!("\nMonitor at the divergence, type ? for the commands");
+        let mut monitor = Monitor::new();
+        monitor.cpu.set_state(&state);
+        monitor.mem = mem;
+        monitor.cycles = tracer.total_cycles() as u64;
+        monitor
+            .run(io::stdin().lock(), io::stdout())
+            .map_err(|error| error.to_string())?;
+    }
+    String::from_utf8(tracer.into_inner()).map_err(|error| error.to_string())
 }
 
 //let mut mem_viewer = mem_viewer::MemViewer::create_display(mem.data.len());
@@ -8,7 +8,8 @@ use crate::{
 };
 
 /// Status flags for the 6502
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StatusFlags {
     /// Negative flag
     pub N: u8,
@@ -40,6 +41,97 @@ impl StatusFlags {
             | self.Z << 2
             | self.C << 1
     }
+
+    /// Status register with the flags in their 6502 bit positions, `NV1BDIZC`
+    /// ## Example
+    /// ```
+    /// use rusty_6502::cpu::StatusFlags;
+    /// let flags = StatusFlags::from_bits(0xA5);
+    /// assert_eq!((flags.N, flags.U, flags.I, flags.C), (1, 1, 1, 1));
+    /// assert_eq!(flags.bits(), 0xA5);
+    /// ```
+    pub fn bits(&self) -> u8 {
+        self.N << 7
+            | self.V << 6
+            | self.U << 5
+            | self.B << 4
+            | self.D << 3
+            | self.I << 2
+            | self.Z << 1
+            | self.C
+    }
+
+    /// Split a status register into its flags
+    /// ## Arguments
+    /// * `status` - Status register, `NV1BDIZC` [`u8`]
+    pub fn from_bits(status: u8) -> Self {
+        let bit = |bit: u8| (status >> bit) & 1;
+        StatusFlags {
+            N: bit(7),
+            V: bit(6),
+            U: bit(5),
+            B: bit(4),
+            D: bit(3),
+            I: bit(2),
+            Z: bit(1),
+            C: bit(0),
+        }
+    }
+}
+
+/// Registers and flags of the CPU, without its debugger
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CpuState {
+    /// Program counter
+    pub PC: u16,
+    /// Status register
+    pub SR: u8,
+    /// Stack pointer
+    pub SP: u8,
+    /// Accumulator
+    pub A: u8,
+    /// X register
+    pub X: u8,
+    /// Y register
+    pub Y: u8,
+    /// Status flags
+    pub status_flags: StatusFlags,
+}
+
+impl CpuState {
+    /// State of the CPU after [`CPU::reset`]
+    /// ## Arguments
+    /// * `pc` - The start point of the program [`u16`]
+    /// ## Example
+    /// ```
+    /// use rusty_6502::cpu::CpuState;
+    /// let state = CpuState {
+    ///     X: 0x01,
+    ///     ..CpuState::reset(0x600)
+    /// };
+    /// assert_eq!(state.SP, 0xFF);
+    /// ```
+    pub fn reset(pc: u16) -> Self {
+        CpuState {
+            PC: pc,
+            SR: 0xFF,
+            SP: 0xFF,
+            A: 0,
+            X: 0,
+            Y: 0,
+            status_flags: StatusFlags {
+                N: 0,
+                V: 0,
+                U: 1,
+                I: 0,
+                B: 0,
+                D: 0,
+                Z: 0,
+                C: 0,
+            },
+        }
+    }
 }
 
 #[derive(PartialEq, Debug)]
@@ -117,6 +209,32 @@ where
         }
     }
 
+    /// Registers and flags of the CPU
+    pub fn state(&self) -> CpuState {
+        CpuState {
+            PC: self.PC,
+            SR: self.SR,
+            SP: self.SP,
+            A: self.A,
+            X: self.X,
+            Y: self.Y,
+            status_flags: self.status_flags,
+        }
+    }
+
+    /// Set the registers and flags of the CPU, memory is not changed
+    /// ## Arguments
+    /// * `state` - The new state [`CpuState`]
+    pub fn set_state(&mut self, state: &CpuState) {
+        self.PC = state.PC;
+        self.SR = state.SR;
+        self.SP = state.SP;
+        self.A = state.A;
+        self.X = state.X;
+        self.Y = state.Y;
+        self.status_flags = state.status_flags;
+    }
+
     fn emit_debugger(&mut self, message_type: MessageType) {
         if self.step == Step::Supervised {
             (self.messenger.messenger)(message_type);
@@ -152,7 +270,7 @@ where
     /// ## Returns
     /// The fetched byte [`u8`]
     pub fn fetch_byte(&mut self, cycles: &mut u32, mem: &mut MEM) -> u8 {
-        let data: u8 = mem[self.PC.into()];
+        let data: u8 = mem.read(self.PC);
         self.PC += 1;
         *cycles -= 1;
         data
@@ -165,7 +283,7 @@ where
     /// * `address` - The address to write to [`u16`]
     /// * `value` - The data to write [`u8`]
     pub fn write_byte(&mut self, cycles: &mut u32, mem: &mut MEM, address: u16, value: u8) {
-        mem[address.into()] = value;
+        mem.write(address, value);
         *cycles -= 1;
     }
 
@@ -176,8 +294,8 @@ where
     /// * `address` - The address to write to [`u16`]
     /// * `value` - The data to write [`u16`]
     pub fn write_word(&mut self, cycles: &mut u32, mem: &mut MEM, address: u8, value: u16) {
-        mem[address.into()] = (value as u8) & 0xff;
-        mem[(address + 1).into()] = (value >> 8) as u8;
+        mem.write(address.into(), value as u8);
+        mem.write((address + 1).into(), (value >> 8) as u8);
         *cycles -= 2;
     }
 
@@ -189,7 +307,7 @@ where
     /// ## Returns
     /// The read byte [`u8`]
     pub fn read_byte(&mut self, cycles: &mut u32, mem: &mut MEM, address: u16) -> u8 {
-        let data: u8 = mem[address.into()];
+        let data: u8 = mem.read(address);
         *cycles -= 1;
         data
     }
@@ -225,20 +343,15 @@ where
     /// * `mem` - The memory space [`MEM`]
     /// * `value` - The data to push [`u16`]
     pub fn push_word_to_stack(&mut self, cycles: &mut u32, mem: &mut MEM, value: u16) {
+        self.write_byte(cycles, mem, 0x100_u16 | self.SP as u16, (value >> 8) as u8);
+        self.SP = self.SP.wrapping_sub(1);
         self.write_byte(
             cycles,
             mem,
-            (0x100_u16 | self.SP as u16) as u16,
-            (value >> 8) as u8,
-        );
-        self.SP -= 1;
-        self.write_byte(
-            cycles,
-            mem,
-            (0x100_u16 | self.SP as u16) as u16,
+            0x100_u16 | self.SP as u16,
             (value & 0xff) as u8,
         );
-        self.SP -= 1;
+        self.SP = self.SP.wrapping_sub(1);
     }
 
     /// Push byte to stack
@@ -247,16 +360,17 @@ where
     /// * `mem` - The memory space [`MEM`]
     /// * `value` - The data to push [`u8`]
     pub fn push_byte_to_stack(&mut self, cycles: &mut u32, mem: &mut MEM, value: u8) {
-        self.write_byte(cycles, mem, (0x100 as u16 | self.SP as u16) as u16, value);
-        self.SP -= 1;
+        self.write_byte(cycles, mem, 0x100_u16 | self.SP as u16, value);
+        self.SP = self.SP.wrapping_sub(1);
     }
 
     /// Execute sized until cycles consumed
     /// ## Returns
     /// [`u16`] Ending location of the program counter
     pub fn execute_sized(&mut self, cycles: &mut u32, mem: &mut MEM) -> u16 {
-        while cycles > &mut 0 {
-            let (_, _, halted) = self.execute_instruction(&mut 9, mem);
+        while *cycles > 0 {
+            let (_, consumed, halted) = self.execute_instruction(&mut 9, mem);
+            *cycles = cycles.saturating_sub(consumed);
             if halted {
                 break;
             }
@@ -264,6 +378,18 @@ where
         self.PC
     }
 
+    /// Execute a single instruction
+    /// ## Arguments
+    /// * `mem` - The memory space [`MEM`]
+    /// ## Returns
+    /// (opcode: u8, cycles: u32, halted: bool)
+    /// The executed opcode, the cycles it took and whether it halted the CPU.
+    pub fn step(&mut self, mem: &mut MEM) -> (u8, u32, bool) {
+        let (instruction, consumed, complete) = self.execute_instruction(&mut 9, mem);
+        self.emit_debugger(MessageType::LineExecuted(instruction, consumed));
+        (instruction, consumed, complete)
+    }
+
     /// Execute continuously until the CPU is halted.
     /// ## Returns
     /// (cycles: usize, ending_pc: u16)
@@ -285,7 +411,7 @@ where
     }
 
     fn execute_instruction(&mut self, cycles: &mut u32, mem: &mut MEM) -> (u8, u32, bool) {
-        let old_cycles = cycles.clone();
+        let old_cycles = *cycles;
         let instruction = self.fetch_byte(cycles, mem);
         let resolved_instruction = Instructions::resolve(instruction);
         let mut complete = false;
@@ -303,12 +429,8 @@ where
                 }
                 _ => panic!("Wrong addressing mode"),
             },
-            Instructions::BVC(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
-            Instructions::BVS(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
+            Instructions::BVC(_) => panic!("Wrong addressing mode"),
+            Instructions::BVS(_) => panic!("Wrong addressing mode"),
             Instructions::CLC(address_mode) => match address_mode {
                 crate::asm::AddrMode::Implied(_) => {
                     self.status_flags.C = 0;
@@ -337,15 +459,9 @@ where
                 }
                 _ => panic!("Wrong addressing mode"),
             },
-            Instructions::CMP(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
-            Instructions::CPX(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
-            Instructions::CPY(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
+            Instructions::CMP(_) => panic!("Wrong addressing mode"),
+            Instructions::CPX(_) => panic!("Wrong addressing mode"),
+            Instructions::CPY(_) => panic!("Wrong addressing mode"),
             Instructions::DEC(address_mode) => match address_mode {
                 crate::asm::AddrMode::ZeroPage(_) => {
                     let address = self.fetch_byte(cycles, mem);
@@ -375,11 +491,11 @@ where
                 }
                 crate::asm::AddrMode::Absolute(_) => {
                     let address = self.fetch_word(cycles, mem);
-                    let value = self.read_byte(cycles, mem, address as u16);
+                    let value = self.read_byte(cycles, mem, address);
                     self.write_byte(
                         cycles,
                         mem,
-                        address.into(),
+                        address,
                         if value == 0 { 255 } else { value - 1 },
                     );
                     self.status_flags.Z = if value == 0 { 1 } else { 0 };
@@ -389,11 +505,11 @@ where
                 crate::asm::AddrMode::AbsoluteX(_) => {
                     let mut address = self.fetch_word(cycles, mem);
                     address += self.X as u16;
-                    let value = self.read_byte(cycles, mem, address as u16);
+                    let value = self.read_byte(cycles, mem, address);
                     self.write_byte(
                         cycles,
                         mem,
-                        address.into(),
+                        address,
                         if value == 0 { 255 } else { value - 1 },
                     );
                     self.status_flags.Z = if value == 0 { 1 } else { 0 };
@@ -420,9 +536,7 @@ where
                 }
                 _ => panic!("Wrong addressing mode"),
             },
-            Instructions::EOR(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
+            Instructions::EOR(_) => panic!("Wrong addressing mode"),
             Instructions::INC(address_mode) => match address_mode {
                 crate::asm::AddrMode::ZeroPage(_) => {
                     let address = self.fetch_byte(cycles, mem);
@@ -452,11 +566,11 @@ where
                 }
                 crate::asm::AddrMode::Absolute(_) => {
                     let address = self.fetch_word(cycles, mem);
-                    let value = self.read_byte(cycles, mem, address as u16);
+                    let value = self.read_byte(cycles, mem, address);
                     self.write_byte(
                         cycles,
                         mem,
-                        address.into(),
+                        address,
                         if value == 255 { 0 } else { value + 1 },
                     );
                     self.status_flags.Z = if value == 0 { 1 } else { 0 };
@@ -466,11 +580,11 @@ where
                 crate::asm::AddrMode::AbsoluteX(_) => {
                     let mut address = self.fetch_word(cycles, mem);
                     address += self.X as u16;
-                    let value = self.read_byte(cycles, mem, address as u16);
+                    let value = self.read_byte(cycles, mem, address);
                     self.write_byte(
                         cycles,
                         mem,
-                        address.into(),
+                        address,
                         if value == 255 { 0 } else { value + 1 },
                     );
                     self.status_flags.Z = if value == 0 { 1 } else { 0 };
@@ -497,10 +611,15 @@ where
                 }
                 _ => panic!("Wrong addressing mode"),
             },
-            Instructions::JMP(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
+            Instructions::JMP(_) => panic!("Wrong addressing mode"),
             Instructions::JSR(address_mode) => match address_mode {
+                crate::asm::AddrMode::Absolute(_) => {
+                    let address = self.fetch_word(cycles, mem);
+                    //The return address is the last byte of the JSR
+                    self.push_word_to_stack(cycles, mem, self.PC.wrapping_sub(1));
+                    self.PC = address;
+                    *cycles -= 1;
+                }
                 _ => panic!("Wrong addressing mode"),
             },
             Instructions::LDX(address_mode) => {
@@ -626,42 +745,28 @@ where
                 self.status_flags.Z = if self.Y == 0 { 1 } else { 0 };
                 self.status_flags.N = if (self.Y & 0b10000000) > 0 { 1 } else { 0 };
             }
-            Instructions::LSR(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
-            Instructions::NOP(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
-            Instructions::ORA(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
-            Instructions::PHA(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
-            Instructions::PHP(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
-            Instructions::PLA(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
-            Instructions::PLP(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
-            Instructions::ROL(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
-            Instructions::ROR(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
-            Instructions::RTI(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
+            Instructions::LSR(_) => panic!("Wrong addressing mode"),
+            Instructions::NOP(_) => panic!("Wrong addressing mode"),
+            Instructions::ORA(_) => panic!("Wrong addressing mode"),
+            Instructions::PHA(_) => panic!("Wrong addressing mode"),
+            Instructions::PHP(_) => panic!("Wrong addressing mode"),
+            Instructions::PLA(_) => panic!("Wrong addressing mode"),
+            Instructions::PLP(_) => panic!("Wrong addressing mode"),
+            Instructions::ROL(_) => panic!("Wrong addressing mode"),
+            Instructions::ROR(_) => panic!("Wrong addressing mode"),
+            Instructions::RTI(_) => panic!("Wrong addressing mode"),
             Instructions::RTS(address_mode) => match address_mode {
+                crate::asm::AddrMode::Implied(_) => {
+                    self.SP = self.SP.wrapping_add(1);
+                    let low = self.read_byte(cycles, mem, 0x100_u16 | self.SP as u16);
+                    self.SP = self.SP.wrapping_add(1);
+                    let high = self.read_byte(cycles, mem, 0x100_u16 | self.SP as u16);
+                    self.PC = u16::from_le_bytes([low, high]).wrapping_add(1);
+                    *cycles -= 3;
+                }
                 _ => panic!("Wrong addressing mode"),
             },
-            Instructions::SBC(address_mode) => match address_mode {
-                _ => panic!("Wrong addressing mode"),
-            },
+            Instructions::SBC(_) => panic!("Wrong addressing mode"),
             Instructions::SEC(address_mode) => match address_mode {
                 crate::asm::AddrMode::Implied(_) => {
                     self.status_flags.C = 1;
@@ -737,7 +842,7 @@ where
                 }
                 crate::asm::AddrMode::Absolute(_) => {
                     let address = self.fetch_word(cycles, mem);
-                    self.write_byte(cycles, mem, address as u16, self.X);
+                    self.write_byte(cycles, mem, address, self.X);
                 }
                 _ => panic!("Wrong addressing mode"),
             },
@@ -753,7 +858,7 @@ where
                 }
                 crate::asm::AddrMode::Absolute(_) => {
                     let address = self.fetch_word(cycles, mem);
-                    self.write_byte(cycles, mem, address as u16, self.Y);
+                    self.write_byte(cycles, mem, address, self.Y);
                 }
                 _ => panic!("Wrong addressing mode"),
             },
@@ -0,0 +1,699 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, Write},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    asm::Program,
+    cpu::CPU,
+    debugger::{ignore, MessageType},
+    image,
+    json::{self, Json},
+    mem::MEM,
+    testing::panic_message,
+};
+
+/// Thread id of the CPU, DAP requires threads
+const THREAD: i64 = 1;
+/// Variables reference of the registers scope
+const REGISTERS: i64 = 1;
+/// Variables reference of the memory scope, its pages follow
+const MEMORY: i64 = 2;
+/// Variables reference of the first memory page
+const PAGES: i64 = 0x100;
+
+/// Opcode of `JSR $nnnn`
+const JSR: u8 = 0x20;
+/// Opcode of `RTS`
+const RTS: u8 = 0x60;
+
+/// A subroutine call on the reconstructed call stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallFrame {
+    /// Address of the `JSR`
+    pub call: u16,
+    /// Address of the called subroutine
+    pub routine: u16,
+}
+
+/// Source line of a address, from the assembler listing or a JSON symbol file
+#[derive(Debug, Clone)]
+struct LineEntry {
+    file: String,
+    line: usize,
+    address: u16,
+}
+
+/// How far a run goes before it stops
+#[derive(Debug, Clone, Copy)]
+enum Run {
+    /// Until a breakpoint
+    Continue,
+    /// A single instruction
+    Step,
+    /// Until the call depth is back to the given one
+    Over(usize),
+    /// Until the call depth is below the given one
+    Out(usize),
+}
+
+/// Debug Adapter Protocol server
+///
+/// `launch` takes a `program`, a assembly source which is assembled at `origin` or a
+/// binary image loaded there, a optional assembler `symbols` file, the `start` address or
+/// label and `stopOnEntry`. Source breakpoints are mapped through the assembler listing,
+/// or the labels of a JSON symbol file for images. The call stack is reconstructed from
+/// the executed `JSR` and `RTS` instructions, a `BRK` ends the program.
+/// ## Example
+/// ```
+/// use rusty_6502::dap::DapServer;
+/// let mut server = DapServer::new();
+/// let messages = server.handle(r#"{"seq":1,"type":"request","command":"initialize"}"#);
+/// assert!(messages[0].contains(r#""success":true"#));
+/// let messages = server.handle(r#"{"seq":2,"type":"request","command":"launch"}"#);
+/// assert!(messages[0].contains(r#""message":"Missing program""#));
+/// ```
+#[allow(missing_debug_implementations)]
+pub struct DapServer {
+    /// The CPU, breakpoints and symbols are kept by its debugger
+    pub cpu: CPU<fn(MessageType)>,
+    /// The memory space
+    pub mem: MEM,
+    /// Cycles executed by the program
+    pub cycles: u64,
+    /// Maximum number of instructions executed before a run pauses, 10000000 by default
+    pub max_instructions: usize,
+    lines: Vec<LineEntry>,
+    frames: Vec<CallFrame>,
+    entry: u16,
+    source_breakpoints: BTreeMap<String, Vec<u16>>,
+    stop_on_entry: bool,
+    ended: bool,
+    done: bool,
+    seq: i64,
+    events: Vec<Json>,
+}
+
+impl Default for DapServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DapServer {
+    /// Create a server, nothing runs before `launch`
+    pub fn new() -> Self {
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(ignore as fn(MessageType));
+        cpu.reset(0x600, &mut mem);
+        DapServer {
+            cpu,
+            mem,
+            cycles: 0,
+            max_instructions: 10_000_000,
+            lines: Vec::new(),
+            frames: Vec::new(),
+            entry: 0x600,
+            source_breakpoints: BTreeMap::new(),
+            stop_on_entry: false,
+            ended: false,
+            done: false,
+            seq: 0,
+            events: Vec::new(),
+        }
+    }
+
+    /// Subroutine calls of the program, the innermost last
+    pub fn call_stack(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
+    /// Whether the client disconnected
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Serve messages with `Content-Length` headers until the client disconnects or the
+    /// input ends
+    /// ## Arguments
+    /// * `input` - Where requests are read from, like stdin [`BufRead`]
+    /// * `output` - Where responses and events are written, like stdout [`Write`]
+    pub fn serve(&mut self, mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        while !self.done {
+            let mut length = None;
+            loop {
+                let mut header = String::new();
+                if input.read_line(&mut header)? == 0 {
+                    return Ok(());
+                }
+                let header = header.trim_end();
+                if header.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("Content-Length") {
+                        length = value.trim().parse::<usize>().ok();
+                    }
+                }
+            }
+            let Some(length) = length else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Missing Content-Length header",
+                ));
+            };
+            let mut body = vec![0; length];
+            input.read_exact(&mut body)?;
+            for message in self.handle(&String::from_utf8_lossy(&body)) {
+                write!(
+                    output,
+                    "Content-Length: {}\r\n\r\n{}",
+                    message.len(),
+                    message
+                )?;
+            }
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Handle a request
+    /// ## Arguments
+    /// * `request` - JSON text of the request [`str`]
+    /// ## Returns
+    /// JSON texts of the response and the events which follow it
+    pub fn handle(&mut self, request: &str) -> Vec<String> {
+        let request = match json::parse(request) {
+            Ok(request) => request,
+            Err(error) => {
+                let body = Json::object([
+                    ("category", "stderr".into()),
+                    ("output", format!("Invalid request: {}\n", error).into()),
+                ]);
+                let event = Json::object([("event", "output".into()), ("body", body)]);
+                return vec![self.event_message(event)];
+            }
+        };
+        let command = request
+            .get("command")
+            .and_then(Json::as_str)
+            .unwrap_or("")
+            .to_string();
+        let arguments = request
+            .get("arguments")
+            .cloned()
+            .unwrap_or(Json::object([]));
+        let result = self.request(&command, &arguments);
+        self.seq += 1;
+        let mut response = vec![
+            ("seq", self.seq.into()),
+            ("type", "response".into()),
+            (
+                "request_seq",
+                request
+                    .get("seq")
+                    .and_then(Json::as_i64)
+                    .unwrap_or(0)
+                    .into(),
+            ),
+            ("success", result.is_ok().into()),
+            ("command", command.into()),
+        ];
+        match result {
+            Ok(body) => response.push(("body", body)),
+            Err(message) => response.push(("message", message.into())),
+        }
+        let mut messages = vec![Json::object(response).to_string()];
+        for event in std::mem::take(&mut self.events) {
+            messages.push(self.event_message(event));
+        }
+        messages
+    }
+
+    /// Queue a event, sequence numbers are given when it is sent
+    fn event(&mut self, event: &str, body: Json) {
+        self.events
+            .push(Json::object([("event", event.into()), ("body", body)]));
+    }
+
+    fn event_message(&mut self, event: Json) -> String {
+        self.seq += 1;
+        let Json::Object(members) = event else {
+            unreachable!()
+        };
+        let mut message = vec![
+            ("seq".to_string(), self.seq.into()),
+            ("type".to_string(), "event".into()),
+        ];
+        message.extend(members);
+        Json::Object(message).to_string()
+    }
+
+    fn request(&mut self, command: &str, arguments: &Json) -> Result<Json, String> {
+        match command {
+            "initialize" => Ok(Json::object([
+                ("supportsConfigurationDoneRequest", true.into()),
+                ("supportsSteppingGranularity", false.into()),
+            ])),
+            //Breakpoints are set after the initialized event, once the line table is known
+            "launch" => {
+                self.launch(arguments)?;
+                self.event("initialized", Json::object([]));
+                Ok(Json::object([]))
+            }
+            "setBreakpoints" => self.set_breakpoints(arguments),
+            "setExceptionBreakpoints" => Ok(Json::object([])),
+            "configurationDone" => {
+                match self.stop_on_entry {
+                    true => self.stopped("entry", None),
+                    false => self.run(Run::Continue),
+                }
+                Ok(Json::object([]))
+            }
+            "threads" => Ok(Json::object([(
+                "threads",
+                Json::Array(vec![Json::object([
+                    ("id", THREAD.into()),
+                    ("name", "6502".into()),
+                ])]),
+            )])),
+            "stackTrace" => Ok(self.stack_trace()),
+            "scopes" => Ok(Json::object([(
+                "scopes",
+                Json::Array(vec![
+                    Json::object([
+                        ("name", "Registers".into()),
+                        ("presentationHint", "registers".into()),
+                        ("variablesReference", REGISTERS.into()),
+                        ("expensive", false.into()),
+                    ]),
+                    Json::object([
+                        ("name", "Memory".into()),
+                        ("variablesReference", MEMORY.into()),
+                        ("expensive", true.into()),
+                    ]),
+                ]),
+            )])),
+            "variables" => {
+                let reference = arguments
+                    .get("variablesReference")
+                    .and_then(Json::as_i64)
+                    .ok_or("Missing variablesReference")?;
+                Ok(Json::object([(
+                    "variables",
+                    Json::Array(self.variables(reference)),
+                )]))
+            }
+            "continue" | "next" | "stepIn" | "stepOut" => {
+                if self.ended {
+                    return Err("The program has ended".to_string());
+                }
+                let depth = self.frames.len();
+                self.run(match command {
+                    "continue" => Run::Continue,
+                    "next" => Run::Over(depth),
+                    "stepIn" => Run::Step,
+                    _ => Run::Out(depth),
+                });
+                Ok(Json::object([("allThreadsContinued", true.into())]))
+            }
+            //Runs finish before the next request is read, the program is already stopped
+            "pause" => {
+                if !self.ended {
+                    self.stopped("pause", None);
+                }
+                Ok(Json::object([]))
+            }
+            "disconnect" | "terminate" => {
+                self.done = true;
+                Ok(Json::object([]))
+            }
+            _ => Err(format!("Unsupported request '{}'", command)),
+        }
+    }
+
+    fn launch(&mut self, arguments: &Json) -> Result<(), String> {
+        let program = arguments
+            .get("program")
+            .and_then(Json::as_str)
+            .ok_or("Missing program")?;
+        let origin = match arguments.get("origin") {
+            Some(origin) => self.address(origin)?,
+            None => 0x600,
+        };
+        self.cpu.reset(origin, &mut self.mem);
+        self.cpu.messenger.symbols.clear();
+        self.cpu.messenger.breakpoints.clear();
+        self.source_breakpoints.clear();
+        self.lines.clear();
+        self.frames.clear();
+        self.cycles = 0;
+        self.ended = false;
+        let extension = Path::new(program)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let mut entry = origin;
+        match extension.as_str() {
+            "s" | "asm" | "a65" | "inc" => {
+                let mut assembled = Program::new(origin as usize);
+                assembled
+                    .assemble_file(program)
+                    .map_err(|errors| errors.to_string().trim_end().to_string())?;
+                assembled.fill_ram(&mut self.mem);
+                self.lines = assembled
+                    .listing
+                    .iter()
+                    .filter(|line| line.cycles.is_some())
+                    .filter_map(|line| {
+                        Some(LineEntry {
+                            file: line.file.clone()?,
+                            line: line.line,
+                            address: line.address,
+                        })
+                    })
+                    .collect();
+                self.cpu.messenger.symbols = assembled.symbols.clone();
+            }
+            _ => {
+                let loaded = image::load_file(&mut self.mem, program, origin)
+                    .map_err(|error| format!("{}: {}", program, error))?;
+                entry = loaded
+                    .entry
+                    .or(loaded.ranges.first().map(|range| *range.start()))
+                    .unwrap_or(origin);
+            }
+        }
+        if let Some(path) = arguments.get("symbols").and_then(Json::as_str) {
+            let text = std::fs::read_to_string(path)
+                .map_err(|error| format!("Can not read '{}': {}", path, error))?;
+            let imported = self.cpu.messenger.symbols.len();
+            self.cpu
+                .messenger
+                .import_symbols(&text)
+                .map_err(|error| format!("{}: {}", path, error))?;
+            //Labels with a source location map lines of images without a listing
+            if self.lines.is_empty() {
+                self.lines = self.cpu.messenger.symbols[imported..]
+                    .iter()
+                    .filter_map(|symbol| {
+                        Some(LineEntry {
+                            file: symbol.file.clone()?,
+                            line: symbol.line,
+                            address: symbol.address()?,
+                        })
+                        .filter(|entry| entry.line > 0)
+                    })
+                    .collect();
+            }
+        }
+        if let Some(start) = arguments.get("start") {
+            entry = self.address(start)?;
+        }
+        self.entry = entry;
+        self.cpu.PC = entry;
+        self.stop_on_entry = arguments
+            .get("stopOnEntry")
+            .and_then(Json::as_bool)
+            .unwrap_or(false);
+        Ok(())
+    }
+
+    /// A address given as number, `$0600`, `0x600` or a symbol name
+    fn address(&self, value: &Json) -> Result<u16, String> {
+        if let Some(number) = value.as_i64() {
+            return u16::try_from(number).map_err(|_| format!("Invalid address {}", number));
+        }
+        let text = value.as_str().ok_or("Expected a address")?;
+        let hex = text.strip_prefix('$').or_else(|| text.strip_prefix("0x"));
+        hex.and_then(|hex| u16::from_str_radix(hex, 16).ok())
+            .or_else(|| {
+                self.cpu
+                    .messenger
+                    .lookup(text)
+                    .and_then(|value| u16::try_from(value).ok())
+            })
+            .ok_or_else(|| format!("Invalid address '{}'", text))
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Json) -> Result<Json, String> {
+        let path = arguments
+            .get("source")
+            .and_then(|source| source.get("path"))
+            .and_then(Json::as_str)
+            .ok_or("Missing source path")?;
+        let requested: Vec<usize> = arguments
+            .get("breakpoints")
+            .and_then(Json::as_array)
+            .unwrap_or(&[])
+            .iter()
+            .filter_map(|breakpoint| breakpoint.get("line").and_then(Json::as_i64))
+            .map(|line| line.max(0) as usize)
+            .collect();
+        let old = self.source_breakpoints.remove(path).unwrap_or_default();
+        self.cpu
+            .messenger
+            .breakpoints
+            .retain(|address| !old.contains(address));
+        let file = canonical(path);
+        let mut addresses = Vec::new();
+        let mut breakpoints = Vec::new();
+        for line in requested {
+            //The first instruction at or after the line
+            let entry = self
+                .lines
+                .iter()
+                .filter(|entry| entry.line >= line && canonical(&entry.file) == file)
+                .min_by_key(|entry| entry.line);
+            let breakpoint = match entry {
+                Some(entry) => {
+                    addresses.push(entry.address);
+                    if !self.cpu.messenger.breakpoints.contains(&entry.address) {
+                        self.cpu.messenger.breakpoints.push(entry.address);
+                    }
+                    Json::object([
+                        ("verified", true.into()),
+                        ("line", (entry.line as i64).into()),
+                        (
+                            "instructionReference",
+                            format!("0x{:04X}", entry.address).into(),
+                        ),
+                    ])
+                }
+                None => Json::object([
+                    ("verified", false.into()),
+                    ("line", (line as i64).into()),
+                    ("message", "No instruction at or after this line".into()),
+                ]),
+            };
+            breakpoints.push(breakpoint);
+        }
+        self.source_breakpoints.insert(path.to_string(), addresses);
+        Ok(Json::object([("breakpoints", Json::Array(breakpoints))]))
+    }
+
+    /// Execute until the run stops, queueing the events
+    fn run(&mut self, run: Run) {
+        let mut executed = 0;
+        loop {
+            let pc = self.cpu.PC;
+            let opcode = self.mem[pc as usize];
+            let state = self.cpu.state();
+            let (cpu, mem) = (&mut self.cpu, &mut self.mem);
+            let (_, cycles, halted) = match panic::catch_unwind(AssertUnwindSafe(|| cpu.step(mem)))
+            {
+                Ok(result) => result,
+                Err(error) => {
+                    //Unimplemented instructions stop before them
+                    self.cpu.set_state(&state);
+                    let text = format!(
+                        "CPU stopped at ${:04X}: {}",
+                        pc,
+                        panic_message(error.as_ref())
+                    );
+                    self.stopped("exception", Some(&text));
+                    return;
+                }
+            };
+            self.cycles += cycles as u64;
+            executed += 1;
+            if halted {
+                self.ended = true;
+                self.event("exited", Json::object([("exitCode", 0.into())]));
+                self.event("terminated", Json::object([]));
+                return;
+            }
+            match opcode {
+                JSR => self.frames.push(CallFrame {
+                    call: pc,
+                    routine: self.cpu.PC,
+                }),
+                RTS => {
+                    self.frames.pop();
+                }
+                _ => {}
+            }
+            let depth = self.frames.len();
+            let stepped = match run {
+                Run::Continue => false,
+                Run::Step => true,
+                Run::Over(start) => depth <= start,
+                Run::Out(start) => depth < start,
+            };
+            if stepped {
+                self.stopped("step", None);
+                return;
+            }
+            if self.cpu.messenger.breakpoints.contains(&self.cpu.PC) {
+                self.stopped("breakpoint", None);
+                return;
+            }
+            if executed >= self.max_instructions {
+                let text = format!("Paused after {} instructions", executed);
+                self.stopped("pause", Some(&text));
+                return;
+            }
+        }
+    }
+
+    fn stopped(&mut self, reason: &str, text: Option<&str>) {
+        let mut body = vec![
+            ("reason", reason.into()),
+            ("threadId", THREAD.into()),
+            ("allThreadsStopped", true.into()),
+        ];
+        if let Some(text) = text {
+            body.push(("text", text.into()));
+        }
+        self.event("stopped", Json::object(body));
+    }
+
+    fn stack_trace(&self) -> Json {
+        //The top frame is at the program counter, the others at their JSR
+        let mut locations = vec![self.cpu.PC];
+        locations.extend(self.frames.iter().rev().map(|frame| frame.call));
+        let mut routines: Vec<u16> = self
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| frame.routine)
+            .collect();
+        routines.push(self.entry);
+        let frames: Vec<Json> = locations
+            .iter()
+            .zip(routines)
+            .enumerate()
+            .map(|(index, (address, routine))| {
+                let name = match self.cpu.messenger.symbol_at(routine) {
+                    Some(name) => name.to_string(),
+                    None => format!("${:04X}", routine),
+                };
+                let mut frame = vec![
+                    ("id", (index as i64).into()),
+                    ("name", name.into()),
+                    ("line", 0.into()),
+                    ("column", 0.into()),
+                    (
+                        "instructionPointerReference",
+                        format!("0x{:04X}", address).into(),
+                    ),
+                ];
+                if let Some(entry) = self.lines.iter().find(|entry| entry.address == *address) {
+                    let name = Path::new(&entry.file)
+                        .file_name()
+                        .map_or(entry.file.clone(), |name| {
+                            name.to_string_lossy().to_string()
+                        });
+                    frame[2] = ("line", (entry.line as i64).into());
+                    frame[3] = ("column", 1.into());
+                    frame.push((
+                        "source",
+                        Json::object([("name", name.into()), ("path", entry.file.as_str().into())]),
+                    ));
+                }
+                Json::object(frame)
+            })
+            .collect();
+        let total = frames.len() as i64;
+        Json::object([
+            ("stackFrames", Json::Array(frames)),
+            ("totalFrames", total.into()),
+        ])
+    }
+
+    fn variables(&self, reference: i64) -> Vec<Json> {
+        let variable = |name: String, value: String, reference: i64| {
+            Json::object([
+                ("name", name.into()),
+                ("value", value.into()),
+                ("variablesReference", reference.into()),
+            ])
+        };
+        let cpu = &self.cpu;
+        match reference {
+            REGISTERS => {
+                let status = cpu.status_flags.bits();
+                let flags: String = "NV-BDIZC"
+                    .chars()
+                    .enumerate()
+                    .map(|(bit, flag)| match status & (0x80 >> bit) {
+                        0 => flag.to_ascii_lowercase(),
+                        _ => flag,
+                    })
+                    .collect();
+                vec![
+                    variable("A".into(), format!("${:02X}", cpu.A), 0),
+                    variable("X".into(), format!("${:02X}", cpu.X), 0),
+                    variable("Y".into(), format!("${:02X}", cpu.Y), 0),
+                    variable("SP".into(), format!("${:02X}", cpu.SP), 0),
+                    variable("PC".into(), format!("${:04X}", cpu.PC), 0),
+                    variable("P".into(), format!("${:02X} {}", status, flags), 0),
+                    variable("Cycles".into(), self.cycles.to_string(), 0),
+                ]
+            }
+            MEMORY => (0..0x100)
+                .map(|page| {
+                    let value = match page {
+                        0x00 => "Zero page",
+                        0x01 => "Stack",
+                        _ => "",
+                    };
+                    variable(
+                        format!("${:02X}00", page),
+                        value.into(),
+                        PAGES + page as i64,
+                    )
+                })
+                .collect(),
+            reference if (PAGES..PAGES + 0x100).contains(&reference) => {
+                let page = ((reference - PAGES) as usize) << 8;
+                (page..page + 0x100)
+                    .step_by(16)
+                    .map(|row| {
+                        let bytes = &self.mem.data[row..row + 16];
+                        let text: String = bytes
+                            .iter()
+                            .map(|byte| match byte {
+                                0x20..=0x7E => *byte as char,
+                                _ => '.',
+                            })
+                            .collect();
+                        let value = format!("{} {}", self.mem.hex_dump(row, row + 16), text);
+                        variable(format!("${:04X}", row), value, 0)
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Path of a file for comparisons, the path itself if it does not exist
+fn canonical(path: &str) -> PathBuf {
+    Path::new(path)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(path))
+}
@@ -1,5 +1,8 @@
+use crate::asm::{read_symbols, Symbol, SymbolError};
+
 /// Debugger message type
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MessageType {
     /// Line executed (instruction, consumed cycles)
     LineExecuted(u8, u32),
@@ -11,6 +14,9 @@ pub enum MessageType {
     BreakpointHit,
 }
 
+/// Callback of a CPU whose messages are not used, like the one of the monitor
+pub(crate) fn ignore(_: MessageType) {}
+
 /// Debugger
 #[allow(missing_debug_implementations)]
 pub struct Debugger<E> {
@@ -26,6 +32,8 @@ pub struct Debugger<E> {
     /// Breakpoints
     #[allow(dead_code)]
     pub breakpoints: Vec<u16>,
+    /// Symbols of the program, see [`Debugger::import_symbols`]
+    pub symbols: Vec<Symbol>,
 }
 
 impl<E> Debugger<E> {
@@ -39,6 +47,48 @@ impl<E> Debugger<E> {
             wait_next_instruction: false,
             halt: false,
             breakpoints: Vec::new(),
+            symbols: Vec::new(),
         }
     }
+
+    /// Import symbols from a VICE label file, a `name = $addr` file or a JSON symbol file
+    /// written by [`crate::asm::write_symbols`]
+    /// ## Arguments
+    /// * `text` - Contents of the symbol file [`str`]
+    /// ## Returns
+    /// Count of imported symbols
+    /// ## Example
+    /// ```
+    /// use rusty_6502::debugger::{Debugger, MessageType};
+    /// let mut debugger = Debugger::new(|_: MessageType| {});
+    /// debugger.import_symbols("al C:0600 .start").unwrap();
+    /// assert_eq!(debugger.symbol_at(0x600), Some("start"));
+    /// assert_eq!(debugger.lookup("start"), Some(0x600));
+    /// ```
+    pub fn import_symbols(&mut self, text: &str) -> Result<usize, SymbolError> {
+        let symbols = read_symbols(text)?;
+        let count = symbols.len();
+        self.symbols.extend(symbols);
+        Ok(count)
+    }
+
+    /// Name of the first label at the address
+    /// ## Arguments
+    /// * `address` - Address of the label [`u16`]
+    pub fn symbol_at(&self, address: u16) -> Option<&str> {
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.address() == Some(address))
+            .map(|symbol| symbol.name.as_str())
+    }
+
+    /// Address of a label or value of a constant
+    /// ## Arguments
+    /// * `name` - Name of the symbol [`str`]
+    pub fn lookup(&self, name: &str) -> Option<i64> {
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.name == name)
+            .map(|symbol| symbol.value)
+    }
 }
@@ -0,0 +1,482 @@
+use std::{
+    fmt::Write as _,
+    io::{self, BufReader, Read, Write},
+    net::{TcpListener, ToSocketAddrs},
+    panic::{self, AssertUnwindSafe},
+};
+
+use crate::{
+    cpu::{StatusFlags, CPU},
+    debugger::{ignore, MessageType},
+    mem::{Access, MEM},
+};
+
+/// Target description sent to GDB, the registers in the order of the `g` packet
+pub const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.rusty6502.cpu">
+    <flags id="status" size="1">
+      <field name="C" start="0" end="0"/>
+      <field name="Z" start="1" end="1"/>
+      <field name="I" start="2" end="2"/>
+      <field name="D" start="3" end="3"/>
+      <field name="B" start="4" end="4"/>
+      <field name="V" start="6" end="6"/>
+      <field name="N" start="7" end="7"/>
+    </flags>
+    <reg name="a" bitsize="8" regnum="0" type="uint8"/>
+    <reg name="x" bitsize="8" regnum="1" type="uint8"/>
+    <reg name="y" bitsize="8" regnum="2" type="uint8"/>
+    <reg name="p" bitsize="8" regnum="3" type="status"/>
+    <reg name="sp" bitsize="8" regnum="4" type="uint8"/>
+    <reg name="pc" bitsize="16" regnum="5" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+/// Sizes in bytes of the registers of [`TARGET_XML`]
+const REGISTERS: [usize; 6] = [1, 1, 1, 1, 1, 2];
+
+/// Kind of a watchpoint, the `Z2`, `Z3` and `Z4` packets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    /// Stop after a write
+    Write,
+    /// Stop after a read
+    Read,
+    /// Stop after a read or a write
+    Access,
+}
+
+/// A watched memory range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    /// What stops the program
+    pub kind: WatchKind,
+    /// First watched address
+    pub address: u16,
+    /// Watched bytes
+    pub len: u16,
+}
+
+impl Watchpoint {
+    /// Whether the access stops the program
+    fn stops(&self, access: &Access) -> bool {
+        let kind_matches = match self.kind {
+            WatchKind::Write => access.write,
+            WatchKind::Read => !access.write,
+            WatchKind::Access => true,
+        };
+        kind_matches && access.address.wrapping_sub(self.address) < self.len.max(1)
+    }
+
+    /// Name of the kind in a stop reply
+    fn reason(&self) -> &'static str {
+        match self.kind {
+            WatchKind::Write => "watch",
+            WatchKind::Read => "rwatch",
+            WatchKind::Access => "awatch",
+        }
+    }
+}
+
+/// Server for the GDB remote serial protocol
+///
+/// GDB has no 6502 architecture, the registers are described by [`TARGET_XML`] which
+/// frontends read with `qXfer:features:read`. The program is stopped between packets,
+/// continuing runs until a breakpoint from [`crate::debugger::Debugger::breakpoints`], a
+/// watchpoint, a `BRK` or [`GdbServer::max_instructions`], GDB can not interrupt a
+/// running program.
+///
+/// Watchpoints see every read and write of a instruction on the bus, the opcode and operand
+/// fetches and the stack too. A instruction which panics is undone, its writes are put back.
+/// ## Example
+/// ```
+/// use rusty_6502::gdb::GdbServer;
+/// let mut server = GdbServer::new();
+/// server.mem.load(0x600, &[0xE8, 0xE8, 0x00]);
+/// assert_eq!(server.handle("Z0,601,1").as_deref(), Some("OK"));
+/// assert_eq!(server.handle("c").as_deref(), Some("T05swbreak:;"));
+/// assert_eq!(server.handle("p1").as_deref(), Some("01"));
+/// ```
+#[allow(missing_debug_implementations)]
+pub struct GdbServer {
+    /// The CPU, breakpoints are kept by its debugger
+    pub cpu: CPU<fn(MessageType)>,
+    /// The memory space
+    pub mem: MEM,
+    /// Watched memory ranges
+    pub watchpoints: Vec<Watchpoint>,
+    /// Cycles executed by the program
+    pub cycles: u64,
+    /// Maximum number of instructions executed by a continue, 10000000 by default
+    pub max_instructions: usize,
+    stop: String,
+    attached: bool,
+    acks: bool,
+}
+
+impl Default for GdbServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GdbServer {
+    /// Create a server with empty memory, the program counter is at `$0600`
+    pub fn new() -> Self {
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(ignore as fn(MessageType));
+        cpu.reset(0x600, &mut mem);
+        GdbServer {
+            cpu,
+            mem,
+            watchpoints: Vec::new(),
+            cycles: 0,
+            max_instructions: 10_000_000,
+            stop: "S05".to_string(),
+            attached: true,
+            acks: true,
+        }
+    }
+
+    /// Whether GDB is still attached, false after a `k` or `D` packet
+    pub fn is_attached(&self) -> bool {
+        self.attached
+    }
+
+    /// Accept a single GDB connection and serve it until GDB detaches
+    /// ## Arguments
+    /// * `address` - Local address to listen on, like `127.0.0.1:1234` [`ToSocketAddrs`]
+    pub fn listen(&mut self, address: impl ToSocketAddrs) -> io::Result<()> {
+        let listener = TcpListener::bind(address)?;
+        let (stream, _) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        self.serve(stream.try_clone()?, stream)
+    }
+
+    /// Serve packets until GDB detaches or the input ends
+    /// ## Arguments
+    /// * `input` - Where packets are read from, like the TCP stream or stdin [`Read`]
+    /// * `output` - Where replies are written [`Write`]
+    pub fn serve(&mut self, input: impl Read, mut output: impl Write) -> io::Result<()> {
+        let mut bytes = BufReader::new(input).bytes();
+        let mut last = Vec::new();
+        while self.attached {
+            let Some(byte) = bytes.next().transpose()? else {
+                return Ok(());
+            };
+            match byte {
+                b'$' => {}
+                //GDB did not receive the last reply
+                b'-' if !last.is_empty() => {
+                    output.write_all(&last)?;
+                    output.flush()?;
+                    continue;
+                }
+                //Acks and interrupts of a stopped program
+                _ => continue,
+            }
+            let mut packet = Vec::new();
+            loop {
+                match bytes.next().transpose()? {
+                    Some(b'#') => break,
+                    Some(byte) => packet.push(byte),
+                    None => return Ok(()),
+                }
+            }
+            let checksum = [bytes.next().transpose()?, bytes.next().transpose()?];
+            let valid = match checksum {
+                [Some(high), Some(low)] => {
+                    let text = [high, low];
+                    std::str::from_utf8(&text)
+                        .ok()
+                        .and_then(|text| u8::from_str_radix(text, 16).ok())
+                        == Some(checksum_of(&packet))
+                }
+                _ => return Ok(()),
+            };
+            let acks = self.acks;
+            if acks {
+                output.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if !valid && acks {
+                output.flush()?;
+                continue;
+            }
+            if let Some(reply) = self.handle(&String::from_utf8_lossy(&packet)) {
+                last = encode(&reply);
+                output.write_all(&last)?;
+            }
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Handle the contents of a packet
+    /// ## Arguments
+    /// * `packet` - The packet without the `$` and checksum [`str`]
+    /// ## Returns
+    /// The reply, none for packets without one
+    pub fn handle(&mut self, packet: &str) -> Option<String> {
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            "?" => self.stop.clone(),
+            "g" => {
+                let mut reply = String::new();
+                for register in 0..REGISTERS.len() {
+                    reply += &self.register(register);
+                }
+                reply
+            }
+            "G" => self
+                .write_registers(args)
+                .map_or_else(|| "E01".to_string(), |_| "OK".to_string()),
+            "p" => usize::from_str_radix(args, 16)
+                .ok()
+                .filter(|register| *register < REGISTERS.len())
+                .map_or_else(|| "E01".to_string(), |register| self.register(register)),
+            "P" => args
+                .split_once('=')
+                .and_then(|(register, value)| {
+                    let register = usize::from_str_radix(register, 16).ok()?;
+                    let bytes = decode_hex(value)?;
+                    (bytes.len() == *REGISTERS.get(register)?).then(|| {
+                        self.set_register(register, &bytes);
+                    })
+                })
+                .map_or_else(|| "E01".to_string(), |_| "OK".to_string()),
+            "m" => self.read_memory(args).unwrap_or_else(|| "E01".to_string()),
+            "M" => self
+                .write_memory(args)
+                .map_or_else(|| "E01".to_string(), |_| "OK".to_string()),
+            "Z" | "z" => self
+                .breakpoint(command == "Z", args)
+                .unwrap_or_else(|| "E01".to_string()),
+            "s" | "c" => {
+                if !args.is_empty() {
+                    match u16::from_str_radix(args, 16) {
+                        Ok(address) => self.cpu.PC = address,
+                        Err(_) => return Some("E01".to_string()),
+                    }
+                }
+                self.stop = self.resume(command == "s");
+                self.stop.clone()
+            }
+            "H" | "T" => "OK".to_string(),
+            "k" => {
+                self.attached = false;
+                return None;
+            }
+            "D" => {
+                self.attached = false;
+                "OK".to_string()
+            }
+            _ => self.query(packet).unwrap_or_default(),
+        };
+        Some(reply)
+    }
+
+    /// General queries, none for unsupported packets
+    fn query(&mut self, packet: &str) -> Option<String> {
+        if packet.starts_with("qSupported") {
+            return Some(
+                "PacketSize=4000;qXfer:features:read+;QStartNoAckMode+;swbreak+;hwbreak+"
+                    .to_string(),
+            );
+        }
+        if let Some(args) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let (offset, len) = args.split_once(',')?;
+            let offset = usize::from_str_radix(offset, 16)
+                .ok()?
+                .min(TARGET_XML.len());
+            let len = usize::from_str_radix(len, 16).ok()?;
+            let chunk = &TARGET_XML[offset..(offset + len).min(TARGET_XML.len())];
+            let more = offset + chunk.len() < TARGET_XML.len();
+            return Some(format!("{}{}", if more { 'm' } else { 'l' }, chunk));
+        }
+        let reply = match packet {
+            "QStartNoAckMode" => {
+                self.acks = false;
+                "OK"
+            }
+            "qAttached" => "1",
+            "qC" => "QC1",
+            "qfThreadInfo" => "m1",
+            "qsThreadInfo" => "l",
+            "qOffsets" => "Text=0;Data=0;Bss=0",
+            _ => return None,
+        };
+        Some(reply.to_string())
+    }
+
+    /// Hex of a register in target byte order
+    fn register(&self, register: usize) -> String {
+        let cpu = &self.cpu;
+        match register {
+            0 => format!("{:02x}", cpu.A),
+            1 => format!("{:02x}", cpu.X),
+            2 => format!("{:02x}", cpu.Y),
+            3 => format!("{:02x}", cpu.status_flags.bits()),
+            4 => format!("{:02x}", cpu.SP),
+            _ => encode_hex(&cpu.PC.to_le_bytes()),
+        }
+    }
+
+    fn set_register(&mut self, register: usize, bytes: &[u8]) {
+        let cpu = &mut self.cpu;
+        match register {
+            0 => cpu.A = bytes[0],
+            1 => cpu.X = bytes[0],
+            2 => cpu.Y = bytes[0],
+            3 => cpu.status_flags = StatusFlags::from_bits(bytes[0]),
+            4 => cpu.SP = bytes[0],
+            _ => cpu.PC = u16::from_le_bytes([bytes[0], bytes[1]]),
+        }
+    }
+
+    fn write_registers(&mut self, hex: &str) -> Option<()> {
+        let bytes = decode_hex(hex)?;
+        if bytes.len() != REGISTERS.iter().sum() {
+            return None;
+        }
+        let mut offset = 0;
+        for (register, size) in REGISTERS.iter().enumerate() {
+            self.set_register(register, &bytes[offset..offset + size]);
+            offset += size;
+        }
+        Some(())
+    }
+
+    /// Parse `addr,length`
+    fn memory_range(&self, args: &str) -> Option<(usize, usize)> {
+        let (address, len) = args.split_once(',')?;
+        let address = usize::from_str_radix(address, 16).ok()?;
+        let len = usize::from_str_radix(len, 16).ok()?;
+        (address < self.mem.data.len()).then_some((address, len))
+    }
+
+    fn read_memory(&self, args: &str) -> Option<String> {
+        let (address, len) = self.memory_range(args)?;
+        let end = address.saturating_add(len).min(self.mem.data.len());
+        Some(encode_hex(&self.mem.data[address..end]))
+    }
+
+    fn write_memory(&mut self, args: &str) -> Option<()> {
+        let (range, data) = args.split_once(':')?;
+        let (address, len) = self.memory_range(range)?;
+        let bytes = decode_hex(data)?;
+        (bytes.len() == len && self.mem.try_load(address, &bytes)).then_some(())
+    }
+
+    /// `Z` and `z` packets, none for malformed ones
+    fn breakpoint(&mut self, insert: bool, args: &str) -> Option<String> {
+        let mut fields = args.split(',');
+        let kind = fields.next()?;
+        let address = u16::from_str_radix(fields.next()?, 16).ok()?;
+        let len = u16::from_str_radix(fields.next()?, 16).ok()?;
+        let kind = match kind {
+            //Software and hardware breakpoints are the same to the emulator
+            "0" | "1" => {
+                let breakpoints = &mut self.cpu.messenger.breakpoints;
+                match insert {
+                    true if !breakpoints.contains(&address) => breakpoints.push(address),
+                    true => {}
+                    false => breakpoints.retain(|other| *other != address),
+                }
+                return Some("OK".to_string());
+            }
+            "2" => WatchKind::Write,
+            "3" => WatchKind::Read,
+            "4" => WatchKind::Access,
+            _ => return Some(String::new()),
+        };
+        let watchpoint = Watchpoint { kind, address, len };
+        match insert {
+            true => self.watchpoints.push(watchpoint),
+            false => self.watchpoints.retain(|other| *other != watchpoint),
+        }
+        Some("OK".to_string())
+    }
+
+    /// Step or continue, returning the stop reply
+    fn resume(&mut self, single: bool) -> String {
+        let mut executed = 0;
+        loop {
+            if let Some(reply) = self.step() {
+                return reply;
+            }
+            executed += 1;
+            if single {
+                return "S05".to_string();
+            }
+            if self.cpu.messenger.breakpoints.contains(&self.cpu.PC) {
+                return "T05swbreak:;".to_string();
+            }
+            if executed >= self.max_instructions {
+                return "S02".to_string();
+            }
+        }
+    }
+
+    /// Execute a instruction, a stop reply when it halts, panics or hits a watchpoint
+    fn step(&mut self) -> Option<String> {
+        let state = self.cpu.state();
+        let (cpu, mem) = (&mut self.cpu, &mut self.mem);
+        mem.record_accesses();
+        let step = panic::catch_unwind(AssertUnwindSafe(|| cpu.step(mem)));
+        let accesses = self.mem.take_accesses();
+        let Ok((_, cycles, halted)) = step else {
+            //Unimplemented instructions stop with SIGILL before them
+            self.cpu.set_state(&state);
+            for access in accesses.iter().rev().filter(|access| access.write) {
+                self.mem[access.address as usize] = access.previous;
+            }
+            return Some("S04".to_string());
+        };
+        self.cycles += cycles as u64;
+        for access in &accesses {
+            if let Some(watch) = self.watchpoints.iter().find(|watch| watch.stops(access)) {
+                return Some(format!("T05{}:{:04x};", watch.reason(), access.address));
+            }
+        }
+        halted.then(|| "S05".to_string())
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// Frame a reply as `$data#checksum`, escaping the framing characters
+fn encode(reply: &str) -> Vec<u8> {
+    let mut data = Vec::with_capacity(reply.len());
+    for byte in reply.bytes() {
+        match byte {
+            b'$' | b'#' | b'}' | b'*' => data.extend([b'}', byte ^ 0x20]),
+            _ => data.push(byte),
+        }
+    }
+    let mut packet = vec![b'$'];
+    packet.extend(&data);
+    packet.extend(format!("#{:02x}", checksum_of(&data)).bytes());
+    packet
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).ok())
+        .collect()
+}
@@ -0,0 +1,569 @@
+use std::{fmt::Display, ops::RangeInclusive, path::Path};
+
+use crate::{
+    asm::{Program, Segment},
+    mem::MEM,
+};
+
+/// Size of the address space
+const MEMORY_SIZE: usize = 0x10000;
+
+/// Format of a binary image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Bytes without addresses, placed at a given base address
+    Raw,
+    /// Intel HEX records, `:10060000...`
+    IntelHex,
+    /// Motorola S-records, `S1130600...`
+    SRecord,
+    /// Commodore PRG, a little endian load address followed by the bytes
+    Prg,
+}
+
+impl ImageFormat {
+    /// Guess the format from the extension of a file, unknown extensions are raw
+    /// ## Arguments
+    /// * `path` - Path of the file [`Path`]
+    /// ## Example
+    /// ```
+    /// use rusty_6502::image::ImageFormat;
+    /// assert_eq!(ImageFormat::from_path("rom.hex"), ImageFormat::IntelHex);
+    /// assert_eq!(ImageFormat::from_path("game.PRG"), ImageFormat::Prg);
+    /// assert_eq!(ImageFormat::from_path("dump.bin"), ImageFormat::Raw);
+    /// ```
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match extension.as_str() {
+            "hex" | "ihx" | "ihex" => ImageFormat::IntelHex,
+            "srec" | "s19" | "s28" | "s37" | "mot" => ImageFormat::SRecord,
+            "prg" => ImageFormat::Prg,
+            _ => ImageFormat::Raw,
+        }
+    }
+}
+
+/// Error while loading a binary image
+#[derive(Debug)]
+pub enum LoadError {
+    /// File could not be read
+    Io(std::io::Error),
+    /// Line of a text format is not a valid record
+    InvalidRecord {
+        /// Line of the record starting from 1
+        line: usize,
+        /// What is wrong with the record
+        message: String,
+    },
+    /// Checksum of a record does not match its contents
+    Checksum {
+        /// Line of the record starting from 1
+        line: usize,
+        /// Checksum calculated from the record
+        expected: u8,
+        /// Checksum written in the record
+        found: u8,
+    },
+    /// Data does not fit in the 64K address space
+    OutOfMemory {
+        /// First address outside of the memory
+        address: usize,
+    },
+    /// Intel HEX file without a end of file record
+    MissingEnd,
+    /// PRG file shorter than its load address
+    MissingLoadAddress,
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Io(error) => write!(f, "Can not read image: {}", error),
+            LoadError::InvalidRecord { line, message } => write!(f, "line {}: {}", line, message),
+            LoadError::Checksum {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: Checksum is ${:02X}, expected ${:02X}",
+                line, found, expected
+            ),
+            LoadError::OutOfMemory { address } => {
+                write!(f, "Data at ${:X} does not fit in the memory", address)
+            }
+            LoadError::MissingEnd => write!(f, "Missing end of file record"),
+            LoadError::MissingLoadAddress => write!(f, "PRG file has no load address"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<std::io::Error> for LoadError {
+    fn from(error: std::io::Error) -> Self {
+        LoadError::Io(error)
+    }
+}
+
+/// Result of loading a image into the memory
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadedImage {
+    /// Loaded address ranges, sorted and merged when they touch
+    pub ranges: Vec<RangeInclusive<u16>>,
+    /// Entry point written in the image, like the start address record of Intel HEX,
+    /// the termination record of S-records or the `SYS` line of a PRG BASIC stub
+    pub entry: Option<u16>,
+}
+
+impl LoadedImage {
+    /// Place bytes into the memory and record their range
+    fn write(&mut self, mem: &mut MEM, address: usize, bytes: &[u8]) -> Result<(), LoadError> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        if address + bytes.len() > MEMORY_SIZE {
+            return Err(LoadError::OutOfMemory {
+                address: address.max(MEMORY_SIZE),
+            });
+        }
+        mem.data[address..address + bytes.len()].copy_from_slice(bytes);
+        self.ranges
+            .push(address as u16..=(address + bytes.len() - 1) as u16);
+        Ok(())
+    }
+
+    /// Sort and merge the recorded ranges
+    fn finish(mut self) -> Self {
+        self.ranges.sort_by_key(|range| *range.start());
+        let mut merged: Vec<RangeInclusive<u16>> = Vec::new();
+        for range in self.ranges {
+            match merged.last_mut() {
+                Some(last) if *range.start() as usize <= *last.end() as usize + 1 => {
+                    *last = *last.start()..=*last.end().max(range.end());
+                }
+                _ => merged.push(range),
+            }
+        }
+        self.ranges = merged;
+        self
+    }
+}
+
+/// Load a image in the given format
+/// ## Arguments
+/// * `mem` - Memory to fill [`MEM`]
+/// * `data` - Contents of the image [`u8`]
+/// * `format` - Format of the image [`ImageFormat`]
+/// * `base` - Address of raw images, other formats have their own addresses [`u16`]
+pub fn load(
+    mem: &mut MEM,
+    data: &[u8],
+    format: ImageFormat,
+    base: u16,
+) -> Result<LoadedImage, LoadError> {
+    let text = || String::from_utf8_lossy(data);
+    match format {
+        ImageFormat::Raw => load_raw(mem, data, base),
+        ImageFormat::IntelHex => load_ihex(mem, &text()),
+        ImageFormat::SRecord => load_srec(mem, &text()),
+        ImageFormat::Prg => load_prg(mem, data),
+    }
+}
+
+/// Load a image file, the format is guessed from the extension with [`ImageFormat::from_path`]
+/// ## Arguments
+/// * `mem` - Memory to fill [`MEM`]
+/// * `path` - Path of the image [`Path`]
+/// * `base` - Address of raw images [`u16`]
+pub fn load_file(
+    mem: &mut MEM,
+    path: impl AsRef<Path>,
+    base: u16,
+) -> Result<LoadedImage, LoadError> {
+    let data = std::fs::read(path.as_ref())?;
+    load(mem, &data, ImageFormat::from_path(path), base)
+}
+
+/// Load raw bytes at the base address
+/// ## Arguments
+/// * `mem` - Memory to fill [`MEM`]
+/// * `data` - Bytes to load [`u8`]
+/// * `base` - Address of the first byte [`u16`]
+/// ## Example
+/// ```
+/// use rusty_6502::{image, mem};
+/// let mut mem = mem::MEM::new();
+/// let loaded = image::load_raw(&mut mem, &[0xA9, 0x01], 0x600).unwrap();
+/// assert_eq!(loaded.ranges, vec![0x600..=0x601]);
+/// assert_eq!(mem[0x601], 0x01);
+/// ```
+pub fn load_raw(mem: &mut MEM, data: &[u8], base: u16) -> Result<LoadedImage, LoadError> {
+    let mut loaded = LoadedImage::default();
+    loaded.write(mem, base as usize, data)?;
+    Ok(loaded.finish())
+}
+
+/// Load a PRG file, the first two bytes are the little endian load address
+///
+/// Files loaded at `$0801` with a BASIC `SYS` line report its address as the entry point.
+/// ## Arguments
+/// * `mem` - Memory to fill [`MEM`]
+/// * `data` - Contents of the file [`u8`]
+pub fn load_prg(mem: &mut MEM, data: &[u8]) -> Result<LoadedImage, LoadError> {
+    if data.len() < 2 {
+        return Err(LoadError::MissingLoadAddress);
+    }
+    let address = u16::from_le_bytes([data[0], data[1]]);
+    let mut loaded = LoadedImage::default();
+    loaded.write(mem, address as usize, &data[2..])?;
+    if address == 0x0801 {
+        loaded.entry = sys_address(&data[2..]);
+    }
+    Ok(loaded.finish())
+}
+
+/// Address of a `10 SYS 2064` BASIC line, the line is skipped by its link and
+/// number and `SYS` is the token `$9E`
+fn sys_address(basic: &[u8]) -> Option<u16> {
+    let line = basic.get(4..)?;
+    let digits = line.strip_prefix(&[0x9E])?;
+    let digits: String = digits
+        .iter()
+        .skip_while(|byte| **byte == b' ')
+        .take_while(|byte| byte.is_ascii_digit())
+        .map(|byte| *byte as char)
+        .collect();
+    digits.parse().ok()
+}
+
+/// Parse the hex digits of a record into bytes
+fn record_bytes(line: usize, digits: &str) -> Result<Vec<u8>, LoadError> {
+    let invalid = |message: &str| LoadError::InvalidRecord {
+        line,
+        message: message.to_string(),
+    };
+    if !digits.len().is_multiple_of(2) {
+        return Err(invalid("Record has a odd number of hex digits"));
+    }
+    //Checked on the bytes, so the digit pairs below are never split inside a character
+    if !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(invalid("Record contains a invalid hex digit"));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|index| {
+            u8::from_str_radix(&digits[index..index + 2], 16)
+                .map_err(|_| invalid("Record contains a invalid hex digit"))
+        })
+        .collect()
+}
+
+/// Load a Intel HEX file
+///
+/// Data, end of file, extended segment and linear address records are supported, the
+/// start address records set the entry point.
+/// ## Arguments
+/// * `mem` - Memory to fill [`MEM`]
+/// * `text` - Contents of the file [`str`]
+/// ## Example
+/// ```
+/// use rusty_6502::{image, mem};
+/// let mut mem = mem::MEM::new();
+/// let loaded = image::load_ihex(&mut mem, ":02060000A9014E\n:00000001FF").unwrap();
+/// assert_eq!(loaded.ranges, vec![0x600..=0x601]);
+/// assert_eq!(mem[0x600], 0xA9);
+/// ```
+pub fn load_ihex(mem: &mut MEM, text: &str) -> Result<LoadedImage, LoadError> {
+    let mut loaded = LoadedImage::default();
+    let mut base = 0;
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let invalid = |message: String| LoadError::InvalidRecord {
+            line: number,
+            message,
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let digits = line
+            .strip_prefix(':')
+            .ok_or_else(|| invalid("Record does not start with ':'".to_string()))?;
+        let bytes = record_bytes(number, digits)?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(invalid(
+                "Record length does not match its byte count".to_string(),
+            ));
+        }
+        let (record, found) = bytes.split_at(bytes.len() - 1);
+        let expected = record
+            .iter()
+            .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+            .wrapping_neg();
+        if expected != found[0] {
+            return Err(LoadError::Checksum {
+                line: number,
+                expected,
+                found: found[0],
+            });
+        }
+        let offset = u16::from_be_bytes([record[1], record[2]]) as usize;
+        let data = &record[4..];
+        let value = data
+            .iter()
+            .fold(0usize, |value, byte| value << 8 | *byte as usize);
+        match (record[3], data.len()) {
+            (0x00, _) => loaded.write(mem, base + offset, data)?,
+            (0x01, _) => return Ok(loaded.finish()),
+            (0x02, 2) => base = value << 4,
+            (0x04, 2) => base = value << 16,
+            (0x03, 4) => loaded.entry = Some(((value >> 16 << 4) + (value & 0xFFFF)) as u16),
+            (0x05, 4) => loaded.entry = Some(value as u16),
+            (0x02..=0x05, _) => {
+                return Err(invalid(format!(
+                    "Record type {:02X} has {} bytes of data",
+                    record[3],
+                    data.len()
+                )))
+            }
+            (kind, _) => return Err(invalid(format!("Unknown record type {:02X}", kind))),
+        }
+    }
+    Err(LoadError::MissingEnd)
+}
+
+/// Load a Motorola S-record file
+///
+/// `S1`, `S2` and `S3` data records are loaded, `S7`, `S8` and `S9` set the entry point,
+/// header and count records are skipped.
+/// ## Arguments
+/// * `mem` - Memory to fill [`MEM`]
+/// * `text` - Contents of the file [`str`]
+/// ## Example
+/// ```
+/// use rusty_6502::{image, mem};
+/// let mut mem = mem::MEM::new();
+/// let loaded = image::load_srec(&mut mem, "S1050600A9014A\nS9030600F6").unwrap();
+/// assert_eq!(loaded.ranges, vec![0x600..=0x601]);
+/// assert_eq!(loaded.entry, Some(0x600));
+/// ```
+pub fn load_srec(mem: &mut MEM, text: &str) -> Result<LoadedImage, LoadError> {
+    let mut loaded = LoadedImage::default();
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let invalid = |message: String| LoadError::InvalidRecord {
+            line: number,
+            message,
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (kind, digits) = match line.strip_prefix('S').map(|rest| rest.split_at_checked(1)) {
+            Some(Some((kind, digits))) => (kind, digits),
+            _ => return Err(invalid("Record does not start with 'S'".to_string())),
+        };
+        let address_size = match kind {
+            "0" | "1" | "5" | "9" => 2,
+            "2" | "6" | "8" => 3,
+            "3" | "7" => 4,
+            _ => return Err(invalid(format!("Unknown record type S{}", kind))),
+        };
+        let bytes = record_bytes(number, digits)?;
+        if bytes.len() < address_size + 2 || bytes.len() != bytes[0] as usize + 1 {
+            return Err(invalid(
+                "Record length does not match its byte count".to_string(),
+            ));
+        }
+        let (record, found) = bytes.split_at(bytes.len() - 1);
+        let expected = !record.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        if expected != found[0] {
+            return Err(LoadError::Checksum {
+                line: number,
+                expected,
+                found: found[0],
+            });
+        }
+        let address = record[1..=address_size]
+            .iter()
+            .fold(0usize, |address, byte| address << 8 | *byte as usize);
+        let data = &record[address_size + 1..];
+        match kind {
+            "1" | "2" | "3" => loaded.write(mem, address, data)?,
+            "7" | "8" | "9" if address < MEMORY_SIZE => loaded.entry = Some(address as u16),
+            "7" | "8" | "9" => return Err(LoadError::OutOfMemory { address }),
+            _ => {}
+        }
+    }
+    Ok(loaded.finish())
+}
+
+/// Bytes written in a data record of the text formats
+const RECORD_BYTES: usize = 16;
+
+/// Write segments as a image
+///
+/// Raw and PRG images are a single block starting at the lowest segment, gaps between
+/// segments are filled with zeros and bytes of overlapping segments are taken from the
+/// later one. Intel HEX and S-record files have a record for every 16 bytes and leave
+/// gaps out. The entry point is written as a start linear address record to Intel HEX
+/// and as the `S9` record to S-records, which is `$0000` without a entry point. Raw and
+/// PRG images can not store it.
+/// ## Arguments
+/// * `segments` - Segments to write [`Segment`]
+/// * `format` - Format of the image [`ImageFormat`]
+/// * `entry` - Entry point [`u16`]
+/// ## Example
+/// ```
+/// use rusty_6502::{asm::Segment, image::{self, ImageFormat}};
+/// let segments = [Segment { origin: 0x600, data: vec![0xA9, 0x01] }];
+/// assert_eq!(
+///     image::write_segments(&segments, ImageFormat::SRecord, Some(0x600)),
+///     b"S0030000FC\nS1050600A9014A\nS5030001FB\nS9030600F6\n"
+/// );
+/// ```
+pub fn write_segments(segments: &[Segment], format: ImageFormat, entry: Option<u16>) -> Vec<u8> {
+    let segments: Vec<&Segment> = segments
+        .iter()
+        .filter(|segment| !segment.data.is_empty())
+        .collect();
+    match format {
+        ImageFormat::Raw => flatten(&segments).1,
+        ImageFormat::Prg => {
+            let (origin, data) = flatten(&segments);
+            let mut image = origin.to_le_bytes().to_vec();
+            image.extend(data);
+            image
+        }
+        ImageFormat::IntelHex => {
+            let mut text = String::new();
+            for (address, data) in records(&segments) {
+                text += &ihex_record(address, 0x00, data);
+            }
+            if let Some(entry) = entry {
+                text += &ihex_record(0, 0x05, &(entry as u32).to_be_bytes());
+            }
+            text += &ihex_record(0, 0x01, &[]);
+            text.into_bytes()
+        }
+        ImageFormat::SRecord => {
+            let mut text = srec_record('0', 0, &[]);
+            let records = records(&segments);
+            for (address, data) in &records {
+                text += &srec_record('1', *address, data);
+            }
+            if let Ok(count) = u16::try_from(records.len()) {
+                text += &srec_record('5', count, &[]);
+            }
+            text += &srec_record('9', entry.unwrap_or(0), &[]);
+            text.into_bytes()
+        }
+    }
+}
+
+/// Write a assembled program as a image, see [`write_segments`]
+/// ## Arguments
+/// * `program` - Assembled program [`Program`]
+/// * `format` - Format of the image [`ImageFormat`]
+/// * `entry` - Entry point, usually the start address of the program [`u16`]
+pub fn write_program(program: &Program, format: ImageFormat, entry: Option<u16>) -> Vec<u8> {
+    let mut segments = vec![Segment {
+        origin: program.start_addr as u16,
+        data: program.lines.clone(),
+    }];
+    segments.extend(program.segments.iter().cloned());
+    write_segments(&segments, format, entry)
+}
+
+/// Write a range of the memory as a image, see [`write_segments`]
+/// ## Arguments
+/// * `mem` - Memory to read [`MEM`]
+/// * `start` - First address of the range [`usize`]
+/// * `end` - Address after the range [`usize`]
+/// * `format` - Format of the image [`ImageFormat`]
+/// * `entry` - Entry point [`u16`]
+pub fn write_mem(
+    mem: &MEM,
+    start: usize,
+    end: usize,
+    format: ImageFormat,
+    entry: Option<u16>,
+) -> Vec<u8> {
+    let segment = Segment {
+        origin: start as u16,
+        data: mem.data[start..end].to_vec(),
+    };
+    write_segments(&[segment], format, entry)
+}
+
+/// Join the segments into one block, returns its address and bytes
+fn flatten(segments: &[&Segment]) -> (u16, Vec<u8>) {
+    let start = match segments.iter().map(|segment| segment.origin).min() {
+        Some(start) => start as usize,
+        None => return (0, Vec::new()),
+    };
+    let end = segments
+        .iter()
+        .map(|segment| segment.origin as usize + segment.data.len())
+        .max()
+        .unwrap_or(start);
+    let mut data = vec![0; end - start];
+    for segment in segments {
+        let offset = segment.origin as usize - start;
+        data[offset..offset + segment.data.len()].copy_from_slice(&segment.data);
+    }
+    (start as u16, data)
+}
+
+/// Split the segments into data records
+fn records<'a>(segments: &[&'a Segment]) -> Vec<(u16, &'a [u8])> {
+    segments
+        .iter()
+        .flat_map(|segment| {
+            segment
+                .data
+                .chunks(RECORD_BYTES)
+                .enumerate()
+                .map(|(index, data)| {
+                    (
+                        segment.origin.wrapping_add((index * RECORD_BYTES) as u16),
+                        data,
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Intel HEX record with its checksum
+fn ihex_record(address: u16, kind: u8, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend(address.to_be_bytes());
+    bytes.push(kind);
+    bytes.extend(data);
+    let checksum = bytes
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+        .wrapping_neg();
+    bytes.push(checksum);
+    format!(":{}\n", hex(&bytes))
+}
+
+/// S-record with a 16 bit address and its checksum
+fn srec_record(kind: char, address: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8 + 3];
+    bytes.extend(address.to_be_bytes());
+    bytes.extend(data);
+    let checksum = !bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    bytes.push(checksum);
+    format!("S{}{}\n", kind, hex(&bytes))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
@@ -65,3 +65,23 @@ pub mod mem;
 pub mod asm;
 ///Debugger
 pub mod debugger;
+///Binary image loaders and writers
+pub mod image;
+///Declarative test harness for programs
+pub mod testing;
+///Execution tracer
+pub mod trace;
+///Save states
+pub mod state;
+///Reverse execution
+pub mod rewind;
+///Input recording and replay
+pub mod replay;
+///Machine language monitor
+pub mod monitor;
+///GDB remote serial protocol server
+pub mod gdb;
+///Debug Adapter Protocol server
+pub mod dap;
+///JSON reading and writing
+mod json;
@@ -7,27 +7,51 @@ const MAX_MEM: usize = 1024 * 64;
 pub struct MEM {
     /// Memory data
     pub data: [u8; MAX_MEM],
+    /// Accesses of the CPU while they are recorded
+    accesses: Option<Vec<Access>>,
+}
+
+/// Read or write of the CPU, recorded after [`MEM::record_accesses`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    /// Address on the bus
+    pub address: u16,
+    /// Value read or written
+    pub value: u8,
+    /// Value before the access, the same as `value` for reads
+    pub previous: u8,
+    /// Whether the access writes
+    pub write: bool,
 }
 
 //Read 1 byte
 impl Index<usize> for MEM {
     type Output = u8;
-    fn index<'a>(&'a self, i: usize) -> &'a u8 {
+    fn index(&self, i: usize) -> &u8 {
         &self.data[i]
     }
 }
 
 //Write 1 byte
 impl IndexMut<usize> for MEM {
-    fn index_mut<'a>(&'a mut self, i: usize) -> &'a mut u8 {
+    fn index_mut(&mut self, i: usize) -> &mut u8 {
         &mut self.data[i]
     }
 }
 
+impl Default for MEM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MEM {
     /// Create a new memory
     pub fn new() -> MEM {
-        MEM { data: [0; MAX_MEM] }
+        MEM {
+            data: [0; MAX_MEM],
+            accesses: None,
+        }
     }
 
     /// Fill memory with data
@@ -41,11 +65,80 @@ impl MEM {
     /// * `value` - Value to write [`u8`]
     /// * `address` - Address to write [`u32`]
     pub fn write_word(&mut self, cycles: &mut u32, value: u16, address: u32) {
-        self[address as usize] = (value as u8) & 0xff;
+        self[address as usize] = value as u8;
         self[(address + 1) as usize] = (value >> 8) as u8;
         *cycles -= 2;
     }
 
+    /// Read a byte for the CPU, the read is recorded when accesses are recorded
+    /// ## Arguments
+    /// * `address` - Address to read [`u16`]
+    pub fn read(&mut self, address: u16) -> u8 {
+        let value = self.data[address as usize];
+        if let Some(accesses) = &mut self.accesses {
+            accesses.push(Access {
+                address,
+                value,
+                previous: value,
+                write: false,
+            });
+        }
+        value
+    }
+
+    /// Write a byte for the CPU, the write is recorded when accesses are recorded
+    /// ## Arguments
+    /// * `address` - Address to write [`u16`]
+    /// * `value` - Value to write [`u8`]
+    pub fn write(&mut self, address: u16, value: u8) {
+        let previous = std::mem::replace(&mut self.data[address as usize], value);
+        if let Some(accesses) = &mut self.accesses {
+            accesses.push(Access {
+                address,
+                value,
+                previous,
+                write: true,
+            });
+        }
+    }
+
+    /// Record every read and write of the CPU until [`MEM::take_accesses`], indexing
+    /// the memory directly is not recorded
+    pub fn record_accesses(&mut self) {
+        self.accesses = Some(Vec::new());
+    }
+
+    /// Stop recording and return the recorded accesses in their order [`Access`]
+    pub fn take_accesses(&mut self) -> Vec<Access> {
+        self.accesses.take().unwrap_or_default()
+    }
+
+    /// Copy bytes to memory, like the ones from [`asm6502!`](crate::asm6502)
+    /// ## Arguments
+    /// * `address` - Address of the first byte [`usize`]
+    /// * `bytes` - Bytes to copy [`u8`]
+    /// ## Panics
+    /// When the bytes do not fit in the memory, [`MEM::try_load`] checks it instead
+    pub fn load(&mut self, address: usize, bytes: &[u8]) {
+        self.data[address..address + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Copy bytes to memory when they fit in it
+    /// ## Arguments
+    /// * `address` - Address of the first byte [`usize`]
+    /// * `bytes` - Bytes to copy [`u8`]
+    /// ## Returns
+    /// Whether the bytes fit, nothing is copied when they do not
+    pub fn try_load(&mut self, address: usize, bytes: &[u8]) -> bool {
+        let fits = address
+            .checked_add(bytes.len())
+            .is_some_and(|end| end <= MAX_MEM);
+        if fits {
+            self.load(address, bytes);
+        }
+        fits
+    }
+
     /// Hex dump memory
     pub fn hex_dump(&self, start: usize, end: usize) -> String {
         let mut dump = String::new();
@@ -56,3 +149,123 @@ impl MEM {
         dump
     }
 }
+
+/// Gap of zeros which ends a block of the serialized memory
+#[cfg(feature = "serde")]
+const BLOCK_GAP: usize = 8;
+
+/// Bytes of the serialized memory at their address, zeros between blocks are left out
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Block {
+    address: u16,
+    data: String,
+}
+
+/// Human readable formats get a list of blocks with hex data, like
+/// `[{"address": 1536, "data": "A901"}]`, binary formats get the 65536 bytes
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl serde::Serialize for MEM {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return serializer.serialize_bytes(&self.data);
+        }
+        let mut blocks = Vec::new();
+        let mut address = 0;
+        while address < MAX_MEM {
+            if self.data[address] == 0 {
+                address += 1;
+                continue;
+            }
+            //Extend the block until a long enough gap of zeros
+            let start = address;
+            let mut end = address + 1;
+            while end < MAX_MEM {
+                let gap = self.data[end..]
+                    .iter()
+                    .take(BLOCK_GAP)
+                    .take_while(|byte| **byte == 0)
+                    .count();
+                if gap == BLOCK_GAP || end + gap == MAX_MEM {
+                    break;
+                }
+                end += gap.max(1);
+            }
+            blocks.push(Block {
+                address: start as u16,
+                data: self.data[start..end]
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect(),
+            });
+            address = end;
+        }
+        blocks.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<'de> serde::Deserialize<'de> for MEM {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let mut mem = MEM::new();
+        if !deserializer.is_human_readable() {
+            let bytes = deserializer.deserialize_bytes(BytesVisitor)?;
+            if bytes.len() != MAX_MEM {
+                return Err(D::Error::invalid_length(bytes.len(), &"65536 bytes"));
+            }
+            mem.data.copy_from_slice(&bytes);
+            return Ok(mem);
+        }
+        for block in Vec::<Block>::deserialize(deserializer)? {
+            let data = (0..block.data.len())
+                .step_by(2)
+                .map(|index| {
+                    block
+                        .data
+                        .get(index..index + 2)
+                        .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                })
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| D::Error::custom("invalid hex data in memory block"))?;
+            let address = block.address as usize;
+            if address + data.len() > MAX_MEM {
+                return Err(D::Error::custom("memory block ends outside of the memory"));
+            }
+            mem.load(address, &data);
+        }
+        Ok(mem)
+    }
+}
+
+/// Reads the memory of binary formats, as bytes or a sequence of bytes
+#[cfg(feature = "serde")]
+struct BytesVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "65536 bytes")
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+        Ok(bytes.to_vec())
+    }
+
+    fn visit_byte_buf<E: serde::de::Error>(self, bytes: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(bytes)
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(MAX_MEM);
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+}
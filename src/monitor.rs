@@ -0,0 +1,500 @@
+use std::{
+    io::{self, BufRead, Write},
+    panic::{self, AssertUnwindSafe},
+};
+
+use crate::{
+    asm::{self, Program},
+    cpu::{StatusFlags, CPU},
+    debugger::{ignore, MessageType},
+    image::{self, ImageFormat},
+    mem::MEM,
+    testing::panic_message,
+    trace,
+};
+
+const HELP: &str = "\
+r                       Show the registers
+r a=10 x=ff pc=0600     Set registers (pc a x y sp p) or flags (n v b d i z c)
+m [start] [end]         Show memory, 128 bytes by default
+m <addr> = <bytes>      Write bytes to memory
+d [start] [end]         Disassemble, 16 instructions by default
+a <addr> [instruction]  Assemble a instruction, or every line until a empty one
+b [addr]                Toggle a breakpoint, or list them
+t [count]               Execute instructions and trace them
+g [addr]                Run until a breakpoint or BRK
+l <file> [addr]         Load a image, raw images at the address
+s <file> <start> <end>  Save memory, the format is picked by extension
+sym <file>              Load symbols, they can be used instead of addresses
+x                       Quit
+Numbers are hex, `$` and `0x` prefixes are allowed";
+
+/// Rows of 16 bytes shown by `m` without a end
+const MEMORY_ROWS: usize = 8;
+/// Instructions shown by `d` without a end
+const DISASSEMBLY_LINES: usize = 16;
+
+/// Machine language monitor, a command line to inspect and run the machine
+///
+/// Commands are read a line at a time, see `?` for the list. Numbers are hex and
+/// addresses can also be symbols loaded with `sym`.
+/// ## Example
+/// ```
+/// use rusty_6502::monitor::Monitor;
+/// let mut monitor = Monitor::new();
+/// let mut out = Vec::new();
+/// monitor.command("a 0600 LDX #$05", &mut out).unwrap();
+/// monitor.command("t", &mut out).unwrap();
+/// monitor.command("r", &mut out).unwrap();
+/// assert!(String::from_utf8(out).unwrap().ends_with("PC:0602 A:00 X:05 Y:00 P:20 SP:FF NV-BDIZC:00100000 CYC:2\n"));
+/// ```
+#[allow(missing_debug_implementations)]
+pub struct Monitor {
+    /// The CPU, breakpoints and symbols are kept by its debugger
+    pub cpu: CPU<fn(MessageType)>,
+    /// The memory space
+    pub mem: MEM,
+    /// Cycles executed by `t` and `g`
+    pub cycles: u64,
+    /// Maximum number of instructions executed by `g`, 1000000 by default
+    pub max_instructions: usize,
+    next_memory: u16,
+    next_disassembly: Option<u16>,
+    assembling: Option<u16>,
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Monitor {
+    /// Create a monitor with empty memory, the program counter is at `$0600`
+    pub fn new() -> Self {
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(ignore as fn(MessageType));
+        cpu.reset(0x600, &mut mem);
+        Monitor {
+            cpu,
+            mem,
+            cycles: 0,
+            max_instructions: 1_000_000,
+            next_memory: 0x600,
+            next_disassembly: None,
+            assembling: None,
+        }
+    }
+
+    /// Prompt for the next line, the address while assembling
+    pub fn prompt(&self) -> String {
+        match self.assembling {
+            Some(address) => format!("{:04X}  ", address),
+            None => ". ".to_string(),
+        }
+    }
+
+    /// Read commands until `x` or the end of the input
+    /// ## Arguments
+    /// * `input` - Where commands are read from [`BufRead`]
+    /// * `output` - Where results are written [`Write`]
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        write!(output, "{}", self.prompt())?;
+        output.flush()?;
+        for line in input.lines() {
+            if !self.command(&line?, &mut output)? {
+                return Ok(());
+            }
+            write!(output, "{}", self.prompt())?;
+            output.flush()?;
+        }
+        writeln!(output)
+    }
+
+    /// Execute a command, errors are written as `? message`
+    /// ## Arguments
+    /// * `line` - The command [`str`]
+    /// * `output` - Where the result is written [`Write`]
+    /// ## Returns
+    /// Whether the monitor continues, false after `x`
+    pub fn command(&mut self, line: &str, output: &mut impl Write) -> io::Result<bool> {
+        if let Some(address) = self.assembling {
+            if line.trim().is_empty() {
+                self.assembling = None;
+            } else if let Err(error) = self.assemble(address, line, output) {
+                writeln!(output, "? {}", error)?;
+            }
+            return Ok(true);
+        }
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(true);
+        };
+        let args: Vec<&str> = words.collect();
+        let result = match command.to_ascii_lowercase().as_str() {
+            "x" | "q" => return Ok(false),
+            "?" | "h" | "help" => writeln!(output, "{}", HELP).map_err(|error| error.to_string()),
+            "r" => self.registers(&args, output),
+            "m" => self.memory(&args, output),
+            "d" => self.disassemble(&args, output),
+            "a" => match args.split_first() {
+                Some((address, [])) => self.address(address).map(|address| {
+                    self.assembling = Some(address);
+                }),
+                Some((address, instruction)) => self
+                    .address(address)
+                    .and_then(|address| self.assemble(address, &instruction.join(" "), output)),
+                None => Err("Missing address".to_string()),
+            },
+            "b" => self.breakpoint(&args, output),
+            "t" => self.trace(&args, output),
+            "g" => self.go(&args, output),
+            "l" => self.load(&args, output),
+            "s" => self.save(&args, output),
+            "sym" => self.symbols(&args, output),
+            _ => Err(format!(
+                "Unknown command '{}', ? lists the commands",
+                command
+            )),
+        };
+        if let Err(error) = result {
+            writeln!(output, "? {}", error)?;
+        }
+        Ok(true)
+    }
+
+    /// Parse a symbol or a hex number, symbols like `add` win over the hex number
+    fn address(&self, text: &str) -> Result<u16, String> {
+        if let Some(value) = self.cpu.messenger.lookup(text) {
+            return u16::try_from(value).map_err(|_| format!("Invalid address '{}'", text));
+        }
+        let hex = text
+            .strip_prefix('$')
+            .or_else(|| text.strip_prefix("0x"))
+            .unwrap_or(text);
+        u16::from_str_radix(hex, 16).map_err(|_| format!("Invalid address '{}'", text))
+    }
+
+    fn byte(&self, text: &str) -> Result<u8, String> {
+        self.address(text)
+            .ok()
+            .and_then(|value| u8::try_from(value).ok())
+            .ok_or_else(|| format!("Invalid byte '{}'", text))
+    }
+
+    /// Parse the optional start and end of a range
+    fn range(&self, args: &[&str]) -> Result<(Option<u16>, Option<u16>), String> {
+        match args {
+            [] => Ok((None, None)),
+            [start] => Ok((Some(self.address(start)?), None)),
+            [start, end] => Ok((Some(self.address(start)?), Some(self.address(end)?))),
+            _ => Err("Expected a start and a end".to_string()),
+        }
+    }
+
+    fn write_registers(&self, output: &mut impl Write) -> io::Result<()> {
+        let cpu = &self.cpu;
+        writeln!(
+            output,
+            "PC:{:04X} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} NV-BDIZC:{:08b} CYC:{}",
+            cpu.PC,
+            cpu.A,
+            cpu.X,
+            cpu.Y,
+            cpu.status_flags.bits(),
+            cpu.SP,
+            cpu.status_flags.bits(),
+            self.cycles
+        )
+    }
+
+    fn registers(&mut self, args: &[&str], output: &mut impl Write) -> Result<(), String> {
+        for arg in args {
+            let (name, value) = arg
+                .split_once('=')
+                .ok_or_else(|| format!("Expected register=value, found '{}'", arg))?;
+            let flag = |value: &str| match value {
+                "0" => Ok(0),
+                "1" => Ok(1),
+                _ => Err(format!("Flag {} must be 0 or 1", name)),
+            };
+            let name = name.to_ascii_lowercase();
+            if let "pc" | "a" | "x" | "y" | "sp" | "p" = name.as_str() {
+                let address = self.address(value)?;
+                let byte =
+                    || u8::try_from(address).map_err(|_| format!("Invalid byte '{}'", value));
+                match name.as_str() {
+                    "pc" => self.cpu.PC = address,
+                    "a" => self.cpu.A = byte()?,
+                    "x" => self.cpu.X = byte()?,
+                    "y" => self.cpu.Y = byte()?,
+                    "sp" => self.cpu.SP = byte()?,
+                    _ => self.cpu.status_flags = StatusFlags::from_bits(byte()?),
+                }
+                continue;
+            }
+            let flags = &mut self.cpu.status_flags;
+            match name.as_str() {
+                "n" => flags.N = flag(value)?,
+                "v" => flags.V = flag(value)?,
+                "b" => flags.B = flag(value)?,
+                "d" => flags.D = flag(value)?,
+                "i" => flags.I = flag(value)?,
+                "z" => flags.Z = flag(value)?,
+                "c" => flags.C = flag(value)?,
+                _ => return Err(format!("Unknown register '{}'", name)),
+            }
+        }
+        self.next_disassembly = None;
+        self.write_registers(output)
+            .map_err(|error| error.to_string())
+    }
+
+    fn memory(&mut self, args: &[&str], output: &mut impl Write) -> Result<(), String> {
+        if let Some(position) = args.iter().position(|arg| *arg == "=") {
+            let [address] = args[..position] else {
+                return Err("Expected m <addr> = <bytes>".to_string());
+            };
+            let address = self.address(address)? as usize;
+            let bytes = args[position + 1..]
+                .iter()
+                .map(|byte| self.byte(byte))
+                .collect::<Result<Vec<u8>, String>>()?;
+            if !self.mem.try_load(address, &bytes) {
+                return Err("Bytes do not fit in the memory".to_string());
+            }
+            return Ok(());
+        }
+        let (start, end) = self.range(args)?;
+        let start = start.unwrap_or(self.next_memory) as usize;
+        let end = end
+            .map_or(start + MEMORY_ROWS * 16 - 1, |end| end as usize)
+            .min(0xFFFF);
+        if end < start {
+            return Err("End is before the start".to_string());
+        }
+        let mut row = start;
+        while row <= end {
+            let last = (row + 15).min(end);
+            let bytes = &self.mem.data[row..=last];
+            let text: String = bytes
+                .iter()
+                .map(|byte| match byte {
+                    0x20..=0x7E => *byte as char,
+                    _ => '.',
+                })
+                .collect();
+            writeln!(
+                output,
+                "{:04X}  {:<48} {}",
+                row,
+                self.mem.hex_dump(row, last + 1),
+                text
+            )
+            .map_err(|error| error.to_string())?;
+            row = last + 1;
+        }
+        self.next_memory = (end + 1) as u16;
+        Ok(())
+    }
+
+    fn disassemble(&mut self, args: &[&str], output: &mut impl Write) -> Result<(), String> {
+        let (start, end) = self.range(args)?;
+        let mut address = start.or(self.next_disassembly).unwrap_or(self.cpu.PC) as usize;
+        let symbols = &self.cpu.messenger.symbols;
+        let mut lines = 0;
+        loop {
+            match end {
+                Some(end) if address > end as usize => break,
+                None if lines == DISASSEMBLY_LINES => break,
+                _ if address > 0xFFFF => break,
+                _ => {}
+            }
+            let bytes = &self.mem.data[address..(address + 3).min(0x10000)];
+            let line = asm::disassemble(bytes, address as u16, symbols).0.remove(0);
+            let marker = match self.cpu.messenger.breakpoints.contains(&line.address) {
+                true => '*',
+                false => ' ',
+            };
+            if let Some(label) = &line.label {
+                writeln!(output, "{}:", label).map_err(|error| error.to_string())?;
+            }
+            writeln!(output, "{}{}", marker, line.to_string().trim_end())
+                .map_err(|error| error.to_string())?;
+            address += line.bytes.len();
+            lines += 1;
+        }
+        self.next_disassembly = Some(address as u16);
+        Ok(())
+    }
+
+    fn assemble(
+        &mut self,
+        address: u16,
+        line: &str,
+        output: &mut impl Write,
+    ) -> Result<(), String> {
+        let mut program = Program::new(address as usize);
+        program
+            .assemble(line)
+            .map_err(|errors| errors.to_string().trim_end().to_string())?;
+        let bytes = &program.lines;
+        if !self.mem.try_load(address as usize, bytes) {
+            return Err("Instruction does not fit in the memory".to_string());
+        }
+        let next = address.wrapping_add(bytes.len() as u16);
+        if self.assembling.is_some() {
+            self.assembling = Some(next);
+        }
+        let disassembly = asm::disassemble(bytes, address, &[]);
+        for line in disassembly.iter() {
+            writeln!(output, " {}", line.to_string().trim_end())
+                .map_err(|error| error.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn breakpoint(&mut self, args: &[&str], output: &mut impl Write) -> Result<(), String> {
+        let breakpoints = &mut self.cpu.messenger.breakpoints;
+        match args {
+            [] if breakpoints.is_empty() => {
+                writeln!(output, "No breakpoints").map_err(|error| error.to_string())
+            }
+            [] => {
+                let list: Vec<String> = breakpoints
+                    .iter()
+                    .map(|address| format!("{:04X}", address))
+                    .collect();
+                writeln!(output, "{}", list.join(" ")).map_err(|error| error.to_string())
+            }
+            [address] => {
+                let address = self.address(address)?;
+                let breakpoints = &mut self.cpu.messenger.breakpoints;
+                let message = match breakpoints.iter().position(|other| *other == address) {
+                    Some(index) => {
+                        breakpoints.remove(index);
+                        "removed"
+                    }
+                    None => {
+                        breakpoints.push(address);
+                        breakpoints.sort();
+                        "set"
+                    }
+                };
+                writeln!(output, "Breakpoint at {:04X} {}", address, message)
+                    .map_err(|error| error.to_string())
+            }
+            _ => Err("Expected one address".to_string()),
+        }
+    }
+
+    /// Execute a instruction, panics of the CPU are returned as errors and leave the
+    /// registers as they were before the instruction
+    fn step(&mut self) -> Result<bool, String> {
+        let state = self.cpu.state();
+        let (cpu, mem) = (&mut self.cpu, &mut self.mem);
+        let (_, cycles, halted) = match panic::catch_unwind(AssertUnwindSafe(|| cpu.step(mem))) {
+            Ok(result) => result,
+            Err(error) => {
+                //Unimplemented instructions stop before them
+                self.cpu.set_state(&state);
+                return Err(format!("CPU stopped: {}", panic_message(error.as_ref())));
+            }
+        };
+        self.cycles += cycles as u64;
+        self.next_disassembly = None;
+        Ok(halted)
+    }
+
+    fn trace(&mut self, args: &[&str], output: &mut impl Write) -> Result<(), String> {
+        let count = match args {
+            [] => 1,
+            [count] => usize::from_str_radix(count, 16)
+                .map_err(|_| format!("Invalid count '{}'", count))?,
+            _ => return Err("Expected a count".to_string()),
+        };
+        for _ in 0..count {
+            let line = trace::trace_line(&self.cpu.state(), &self.mem, self.cycles as usize);
+            writeln!(output, "{}", line).map_err(|error| error.to_string())?;
+            if self.step()? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn go(&mut self, args: &[&str], output: &mut impl Write) -> Result<(), String> {
+        match args {
+            [] => {}
+            [address] => self.cpu.PC = self.address(address)?,
+            _ => return Err("Expected one address".to_string()),
+        }
+        let mut executed = 0;
+        let reason = loop {
+            if executed == self.max_instructions {
+                break format!("Stopped after {} instructions", executed);
+            }
+            if self.step()? {
+                break "BRK".to_string();
+            }
+            executed += 1;
+            if self.cpu.messenger.breakpoints.contains(&self.cpu.PC) {
+                break format!("Breakpoint at {:04X}", self.cpu.PC);
+            }
+        };
+        writeln!(output, "{}", reason).map_err(|error| error.to_string())?;
+        self.write_registers(output)
+            .map_err(|error| error.to_string())
+    }
+
+    fn load(&mut self, args: &[&str], output: &mut impl Write) -> Result<(), String> {
+        let (path, base) = match args {
+            [path] => (path, 0x600),
+            [path, address] => (path, self.address(address)?),
+            _ => return Err("Expected a file and a address".to_string()),
+        };
+        let loaded = image::load_file(&mut self.mem, path, base)
+            .map_err(|error| format!("{}: {}", path, error))?;
+        for range in &loaded.ranges {
+            writeln!(output, "Loaded {:04X}-{:04X}", range.start(), range.end())
+                .map_err(|error| error.to_string())?;
+        }
+        if let Some(entry) = loaded.entry {
+            self.cpu.PC = entry;
+            writeln!(output, "PC set to {:04X}", entry).map_err(|error| error.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn save(&mut self, args: &[&str], output: &mut impl Write) -> Result<(), String> {
+        let [path, start, end] = args else {
+            return Err("Expected a file, a start and a end".to_string());
+        };
+        let (start, end) = (self.address(start)? as usize, self.address(end)? as usize);
+        if end < start {
+            return Err("End is before the start".to_string());
+        }
+        let image = image::write_mem(
+            &self.mem,
+            start,
+            end + 1,
+            ImageFormat::from_path(path),
+            None,
+        );
+        std::fs::write(path, image).map_err(|error| format!("{}: {}", path, error))?;
+        writeln!(output, "Saved {:04X}-{:04X}", start, end).map_err(|error| error.to_string())
+    }
+
+    fn symbols(&mut self, args: &[&str], output: &mut impl Write) -> Result<(), String> {
+        let [path] = args else {
+            return Err("Expected a file".to_string());
+        };
+        let text = std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+        let count = self
+            .cpu
+            .messenger
+            .import_symbols(&text)
+            .map_err(|error| format!("{}: {}", path, error))?;
+        writeln!(output, "Loaded {} symbols", count).map_err(|error| error.to_string())
+    }
+}
@@ -0,0 +1,441 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{cpu::CPU, debugger::MessageType, mem::MEM};
+
+/// First line of a recording, followed by the format version
+const HEADER: &str = "rusty_6502 recording";
+/// Version of the recording format written by this crate
+pub const VERSION: u32 = 1;
+
+/// Input fed to the machine from outside of the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    /// Byte written by a device, like a key in a keyboard register
+    Write {
+        /// Address of the register
+        address: u16,
+        /// Written value
+        value: u8,
+    },
+    /// Interrupt request, ignored while the interrupt flag is set
+    Irq,
+    /// Non maskable interrupt
+    Nmi,
+}
+
+/// Input with the cycle it was fed at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    /// Cycles executed before the input
+    pub cycle: u64,
+    /// The input [`Input`]
+    pub input: Input,
+    /// Hash of the machine before the input, see [`state_hash`]
+    pub hash: u64,
+}
+
+/// Inputs of a run, with the hash of the machine at its end
+///
+/// Recordings are text, a header line and a line for every input:
+/// ```text
+/// rusty_6502 recording 1
+/// 120 write $DC01 $41 hash 3B1F0C6A9E2D7754
+/// 300 irq hash 0D5E7A8B6C4F3122
+/// end 512 hash 91C2D3E4F5A6B7C8
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    /// Inputs in the order they were fed [`InputEvent`]
+    pub events: Vec<InputEvent>,
+    /// Cycles of the whole run
+    pub cycles: u64,
+    /// Hash of the machine at the end of the run
+    pub hash: u64,
+}
+
+/// Replay which does not match its recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Desync {
+    /// Cycle where the difference was found
+    pub cycle: u64,
+    /// What differs
+    pub message: String,
+}
+
+impl Display for Desync {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Desync at cycle {}: {}", self.cycle, self.message)
+    }
+}
+
+impl std::error::Error for Desync {}
+
+/// Error while reading a recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordingError {
+    /// Text does not start with the recording header
+    NotARecording,
+    /// Recording was written by a newer format
+    UnsupportedVersion(u32),
+    /// Line which is not a input or the end
+    InvalidLine {
+        /// Line starting from 1
+        line: usize,
+        /// What is wrong with the line
+        message: String,
+    },
+    /// Recording has no `end` line
+    MissingEnd,
+}
+
+impl Display for RecordingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordingError::NotARecording => write!(f, "Not a input recording"),
+            RecordingError::UnsupportedVersion(version) => write!(
+                f,
+                "Recording version {} is newer than the supported version {}",
+                version, VERSION
+            ),
+            RecordingError::InvalidLine { line, message } => {
+                write!(f, "line {}: {}", line, message)
+            }
+            RecordingError::MissingEnd => write!(f, "Recording has no end"),
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {}
+
+/// FNV-1a hash of the registers, the status flags and the whole memory
+/// ## Arguments
+/// * `cpu` - The CPU [`CPU`]
+/// * `mem` - The memory space [`MEM`]
+pub fn state_hash<E>(cpu: &CPU<E>, mem: &MEM) -> u64
+where
+    E: FnOnce(MessageType) + Copy + Sized,
+{
+    let registers = [
+        cpu.PC as u8,
+        (cpu.PC >> 8) as u8,
+        cpu.SP,
+        cpu.A,
+        cpu.X,
+        cpu.Y,
+        cpu.status_flags.bits(),
+    ];
+    registers
+        .iter()
+        .chain(mem.data.iter())
+        .fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01B3)
+        })
+}
+
+/// Feed a input to the machine
+/// ## Returns
+/// Cycles taken by a interrupt
+fn apply<E>(cpu: &mut CPU<E>, mem: &mut MEM, input: Input) -> u32
+where
+    E: FnOnce(MessageType) + Copy + Sized,
+{
+    let vector = match input {
+        Input::Write { address, value } => {
+            mem[address as usize] = value;
+            return 0;
+        }
+        Input::Irq if cpu.status_flags.I == 1 => return 0,
+        Input::Irq => 0xFFFE,
+        Input::Nmi => 0xFFFA,
+    };
+    //Push the program counter and the status without the break flag, then jump
+    let mut push = |value: u8| {
+        mem[0x100 + cpu.SP as usize] = value;
+        cpu.SP = cpu.SP.wrapping_sub(1);
+    };
+    push((cpu.PC >> 8) as u8);
+    push(cpu.PC as u8);
+    push(cpu.status_flags.bits() & !0x10 | 0x20);
+    cpu.status_flags.I = 1;
+    cpu.PC = u16::from_le_bytes([mem[vector], mem[vector + 1]]);
+    7
+}
+
+/// Runs a machine and records every input fed to it
+/// ## Example
+/// ```
+/// use rusty_6502::{asm6502, cpu::CPU, debugger::MessageType, mem::MEM, replay::{Input, Player, Recorder}};
+/// let program = asm6502! { LDA $00FF; STA $0200; LDX $00FF };
+/// let mut mem = MEM::new();
+/// let mut cpu = CPU::new(|_: MessageType| {});
+/// cpu.reset(0x600, &mut mem);
+/// mem.load(0x600, &program);
+/// let mut recorder = Recorder::new();
+/// recorder.input(&mut cpu, &mut mem, Input::Write { address: 0xFF, value: 0x41 });
+/// recorder.step(&mut cpu, &mut mem);
+/// recorder.step(&mut cpu, &mut mem);
+/// let recording = recorder.finish(&cpu, &mem);
+///
+/// cpu.reset(0x600, &mut mem);
+/// mem.load(0x600, &program);
+/// let mut player = Player::new(recording);
+/// player.run(&mut cpu, &mut mem).unwrap();
+/// assert_eq!(mem[0x200], 0x41);
+/// ```
+#[derive(Debug, Default)]
+pub struct Recorder {
+    events: Vec<InputEvent>,
+    cycles: u64,
+}
+
+impl Recorder {
+    /// Create a recorder, the cycles start at 0
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cycles executed so far
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Feed a input to the machine and record it
+    /// ## Arguments
+    /// * `cpu` - The CPU [`CPU`]
+    /// * `mem` - The memory space [`MEM`]
+    /// * `input` - The input [`Input`]
+    pub fn input<E>(&mut self, cpu: &mut CPU<E>, mem: &mut MEM, input: Input)
+    where
+        E: FnOnce(MessageType) + Copy + Sized,
+    {
+        self.events.push(InputEvent {
+            cycle: self.cycles,
+            input,
+            hash: state_hash(cpu, mem),
+        });
+        self.cycles += apply(cpu, mem, input) as u64;
+    }
+
+    /// Execute a instruction with [`CPU::step`]
+    /// ## Arguments
+    /// * `cpu` - The CPU [`CPU`]
+    /// * `mem` - The memory space [`MEM`]
+    /// ## Returns
+    /// (opcode: u8, cycles: u32, halted: bool) of the executed instruction
+    pub fn step<E>(&mut self, cpu: &mut CPU<E>, mem: &mut MEM) -> (u8, u32, bool)
+    where
+        E: FnOnce(MessageType) + Copy + Sized,
+    {
+        let result = cpu.step(mem);
+        self.cycles += result.1 as u64;
+        result
+    }
+
+    /// End the recording with the hash of the machine
+    /// ## Arguments
+    /// * `cpu` - The CPU [`CPU`]
+    /// * `mem` - The memory space [`MEM`]
+    pub fn finish<E>(self, cpu: &CPU<E>, mem: &MEM) -> Recording
+    where
+        E: FnOnce(MessageType) + Copy + Sized,
+    {
+        Recording {
+            events: self.events,
+            cycles: self.cycles,
+            hash: state_hash(cpu, mem),
+        }
+    }
+}
+
+/// Replays a recording against a machine started like the recorded one
+#[derive(Debug)]
+pub struct Player {
+    recording: Recording,
+    next: usize,
+    cycles: u64,
+}
+
+impl Player {
+    /// Create a player, the cycles start at 0
+    /// ## Arguments
+    /// * `recording` - The recording [`Recording`]
+    pub fn new(recording: Recording) -> Self {
+        Player {
+            recording,
+            next: 0,
+            cycles: 0,
+        }
+    }
+
+    /// Cycles executed so far
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Whether the end of the recording is reached
+    pub fn is_done(&self) -> bool {
+        self.cycles >= self.recording.cycles && self.next == self.recording.events.len()
+    }
+
+    /// Feed the inputs recorded at the current cycle, then execute a instruction
+    /// ## Arguments
+    /// * `cpu` - The CPU [`CPU`]
+    /// * `mem` - The memory space [`MEM`]
+    /// ## Returns
+    /// (opcode: u8, cycles: u32, halted: bool) of the executed instruction, or the desync
+    /// when the machine differs from the recording
+    pub fn step<E>(&mut self, cpu: &mut CPU<E>, mem: &mut MEM) -> Result<(u8, u32, bool), Desync>
+    where
+        E: FnOnce(MessageType) + Copy + Sized,
+    {
+        self.feed(cpu, mem)?;
+        let result = cpu.step(mem);
+        self.cycles += result.1 as u64;
+        Ok(result)
+    }
+
+    fn feed<E>(&mut self, cpu: &mut CPU<E>, mem: &mut MEM) -> Result<(), Desync>
+    where
+        E: FnOnce(MessageType) + Copy + Sized,
+    {
+        while let Some(event) = self.recording.events.get(self.next) {
+            if event.cycle > self.cycles {
+                break;
+            }
+            if event.cycle < self.cycles {
+                return Err(Desync {
+                    cycle: self.cycles,
+                    message: format!("input of cycle {} falls inside a instruction", event.cycle),
+                });
+            }
+            if state_hash(cpu, mem) != event.hash {
+                return Err(Desync {
+                    cycle: self.cycles,
+                    message: "machine state differs before a input".to_string(),
+                });
+            }
+            self.cycles += apply(cpu, mem, event.input) as u64;
+            self.next += 1;
+        }
+        Ok(())
+    }
+
+    /// Replay the whole recording and compare the hash at its end
+    /// ## Arguments
+    /// * `cpu` - The CPU [`CPU`]
+    /// * `mem` - The memory space [`MEM`]
+    /// ## Returns
+    /// The cycles of the run, or the desync
+    pub fn run<E>(&mut self, cpu: &mut CPU<E>, mem: &mut MEM) -> Result<u64, Desync>
+    where
+        E: FnOnce(MessageType) + Copy + Sized,
+    {
+        while self.cycles < self.recording.cycles {
+            self.step(cpu, mem)?;
+        }
+        self.feed(cpu, mem)?;
+        if self.cycles != self.recording.cycles {
+            return Err(Desync {
+                cycle: self.cycles,
+                message: format!("run ends at cycle {}", self.recording.cycles),
+            });
+        }
+        if state_hash(cpu, mem) != self.recording.hash {
+            return Err(Desync {
+                cycle: self.cycles,
+                message: "final machine state differs".to_string(),
+            });
+        }
+        Ok(self.cycles)
+    }
+}
+
+impl Display for Recording {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} {}", HEADER, VERSION)?;
+        for event in &self.events {
+            match event.input {
+                Input::Write { address, value } => {
+                    write!(f, "{} write ${:04X} ${:02X}", event.cycle, address, value)?
+                }
+                Input::Irq => write!(f, "{} irq", event.cycle)?,
+                Input::Nmi => write!(f, "{} nmi", event.cycle)?,
+            }
+            writeln!(f, " hash {:016X}", event.hash)?;
+        }
+        writeln!(f, "end {} hash {:016X}", self.cycles, self.hash)
+    }
+}
+
+impl FromStr for Recording {
+    type Err = RecordingError;
+
+    /// Parse a recording written with its [`Display`]
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()));
+        let version = lines
+            .next()
+            .and_then(|(_, line)| line.strip_prefix(HEADER))
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or(RecordingError::NotARecording)?;
+        if version > VERSION {
+            return Err(RecordingError::UnsupportedVersion(version));
+        }
+
+        let mut events = Vec::new();
+        for (line, text) in lines {
+            let invalid = |message: &str| RecordingError::InvalidLine {
+                line,
+                message: message.to_string(),
+            };
+            let words: Vec<&str> = text.split_whitespace().collect();
+            let hash = |words: &[&str]| match words {
+                ["hash", hash] => {
+                    u64::from_str_radix(hash, 16).map_err(|_| invalid("Invalid hash"))
+                }
+                _ => Err(invalid("Expected 'hash' and the state hash")),
+            };
+            let hex = |word: &str| {
+                word.strip_prefix('$')
+                    .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+            };
+            match words.as_slice() {
+                [] => continue,
+                ["end", cycles, rest @ ..] => {
+                    return Ok(Recording {
+                        events,
+                        cycles: cycles.parse().map_err(|_| invalid("Invalid cycle"))?,
+                        hash: hash(rest)?,
+                    })
+                }
+                [cycle, kind, rest @ ..] => {
+                    let cycle = cycle.parse().map_err(|_| invalid("Invalid cycle"))?;
+                    let (input, rest) = match (*kind, rest) {
+                        ("write", [address, value, rest @ ..]) => (
+                            Input::Write {
+                                address: hex(address).ok_or_else(|| invalid("Invalid address"))?,
+                                value: hex(value)
+                                    .and_then(|value| u8::try_from(value).ok())
+                                    .ok_or_else(|| invalid("Invalid value"))?,
+                            },
+                            rest,
+                        ),
+                        ("irq", rest) => (Input::Irq, rest),
+                        ("nmi", rest) => (Input::Nmi, rest),
+                        _ => return Err(invalid(&format!("Unknown input '{}'", kind))),
+                    };
+                    events.push(InputEvent {
+                        cycle,
+                        input,
+                        hash: hash(rest)?,
+                    });
+                }
+                _ => return Err(invalid("Expected a input")),
+            }
+        }
+        Err(RecordingError::MissingEnd)
+    }
+}
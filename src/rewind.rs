@@ -0,0 +1,337 @@
+use std::collections::VecDeque;
+
+use crate::{
+    cpu::{CpuState, CPU},
+    debugger::MessageType,
+    mem::MEM,
+    state::SaveState,
+};
+
+/// Memory used by a snapshot, its memory and registers
+const SNAPSHOT_SIZE: usize = 0x10000 + std::mem::size_of::<SaveState>();
+/// Memory used by a journal entry without its writes
+const ENTRY_SIZE: usize = std::mem::size_of::<Entry>();
+/// Memory used by a write of a journal entry
+const WRITE_SIZE: usize = std::mem::size_of::<Write>();
+
+/// Write of a byte of memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Write {
+    /// Written address
+    pub address: u16,
+    /// Value before the write
+    pub old: u8,
+    /// Value after the write
+    pub new: u8,
+}
+
+/// Instruction which wrote to a address, found with [`History::last_write`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteRecord {
+    /// Index of the instruction, see [`History::index`]
+    pub index: u64,
+    /// Address of the instruction
+    pub pc: u16,
+    /// The write [`Write`]
+    pub write: Write,
+}
+
+/// Executed instruction, with what is needed to undo it
+#[derive(Debug, Clone)]
+struct Entry {
+    index: u64,
+    state: CpuState,
+    cycles: u64,
+    writes: Vec<Write>,
+}
+
+/// History of the execution, to step backwards
+///
+/// Instructions executed with [`History::step`] are written to a journal with the registers
+/// before them and the bytes they changed, which undoes them. Every `interval`
+/// instructions a snapshot of the whole machine is taken. When the history grows over its
+/// memory budget the oldest journal entries and snapshots are dropped, states before the
+/// journal are reached by restoring a snapshot and executing again up to them.
+///
+/// Writes are recorded by the memory while the instruction executes, see
+/// [`MEM::record_accesses`], so recording costs a push per write. Writes which do not
+/// change a byte are in the journal too, changes made by indexing the memory between
+/// steps are not and are not undone.
+/// ## Example
+/// ```
+/// use rusty_6502::{asm6502, cpu::CPU, debugger::MessageType, mem::MEM, rewind::History};
+/// let mut mem = MEM::new();
+/// let mut cpu = CPU::new(|_: MessageType| {});
+/// cpu.reset(0x600, &mut mem);
+/// mem.load(0x600, &asm6502! { LDA #$01; STA $0200; INC $0200 });
+/// let mut history = History::new(1 << 20);
+/// for _ in 0..3 {
+///     history.step(&mut cpu, &mut mem);
+/// }
+/// assert_eq!(history.last_write(0x200).unwrap().pc, 0x605);
+/// history.step_back(&mut cpu, &mut mem);
+/// assert_eq!((cpu.PC, mem[0x200]), (0x605, 0x01));
+/// ```
+#[derive(Debug)]
+pub struct History {
+    budget: usize,
+    interval: u64,
+    snapshots: VecDeque<(u64, SaveState)>,
+    journal: VecDeque<Entry>,
+    journal_size: usize,
+    index: u64,
+    cycles: u64,
+}
+
+impl History {
+    /// Create a empty history
+    /// ## Arguments
+    /// * `budget` - Bytes the snapshots and the journal may use [`usize`]
+    pub fn new(budget: usize) -> Self {
+        History {
+            budget,
+            interval: 10_000,
+            snapshots: VecDeque::new(),
+            journal: VecDeque::new(),
+            journal_size: 0,
+            index: 0,
+            cycles: 0,
+        }
+    }
+
+    /// Set the instructions between snapshots, 10000 by default
+    /// ## Arguments
+    /// * `instructions` - Instructions between snapshots [`u64`]
+    pub fn interval(&mut self, instructions: u64) -> &mut Self {
+        self.interval = instructions.max(1);
+        self
+    }
+
+    /// Number of instructions executed since the history started
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Cycles executed since the history started
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Index of the oldest instruction which can be reached
+    pub fn oldest(&self) -> u64 {
+        let snapshot = self.snapshots.front().map(|(index, _)| *index);
+        let journal = self.journal.front().map(|entry| entry.index);
+        snapshot
+            .into_iter()
+            .chain(journal)
+            .min()
+            .unwrap_or(self.index)
+    }
+
+    /// Bytes used by the snapshots and the journal
+    pub fn memory_used(&self) -> usize {
+        self.snapshots.len() * SNAPSHOT_SIZE + self.journal_size
+    }
+
+    /// Execute a instruction with [`CPU::step`] and record it
+    ///
+    /// The accesses of the memory are recorded during the instruction, which ends any
+    /// recording started before it.
+    /// ## Arguments
+    /// * `cpu` - The CPU [`CPU`]
+    /// * `mem` - The memory space [`MEM`]
+    /// ## Returns
+    /// (opcode: u8, cycles: u32, halted: bool) of the executed instruction
+    pub fn step<E>(&mut self, cpu: &mut CPU<E>, mem: &mut MEM) -> (u8, u32, bool)
+    where
+        E: FnOnce(MessageType) + Copy + Sized,
+    {
+        if self.index.is_multiple_of(self.interval)
+            && self.snapshots.back().map(|(index, _)| *index) != Some(self.index)
+        {
+            self.snapshots
+                .push_back((self.index, SaveState::capture(cpu, mem, self.cycles)));
+        }
+        let state = cpu.state();
+        mem.record_accesses();
+        let result = cpu.step(mem);
+        let writes: Vec<Write> = mem
+            .take_accesses()
+            .into_iter()
+            .filter(|access| access.write)
+            .map(|access| Write {
+                address: access.address,
+                old: access.previous,
+                new: access.value,
+            })
+            .collect();
+        self.journal_size += ENTRY_SIZE + writes.len() * WRITE_SIZE;
+        self.journal.push_back(Entry {
+            index: self.index,
+            state,
+            cycles: self.cycles,
+            writes,
+        });
+        self.index += 1;
+        self.cycles += result.1 as u64;
+        self.trim();
+        result
+    }
+
+    /// Drop the oldest history until it fits in the budget, the newest snapshot is kept
+    fn trim(&mut self) {
+        while self.memory_used() > self.budget {
+            let snapshot = self.snapshots.front().map(|(index, _)| *index);
+            let journal = self.journal.front().map(|entry| entry.index);
+            match (snapshot, journal) {
+                (Some(snapshot), Some(journal))
+                    if snapshot <= journal && self.snapshots.len() > 1 =>
+                {
+                    self.snapshots.pop_front();
+                }
+                (_, Some(_)) => self.pop_journal(),
+                (Some(_), None) if self.snapshots.len() > 1 => {
+                    self.snapshots.pop_front();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn pop_journal(&mut self) {
+        if let Some(entry) = self.journal.pop_front() {
+            self.journal_size -= ENTRY_SIZE + entry.writes.len() * WRITE_SIZE;
+        }
+    }
+
+    fn pop_back(&mut self) -> Option<Entry> {
+        let entry = self.journal.pop_back()?;
+        self.journal_size -= ENTRY_SIZE + entry.writes.len() * WRITE_SIZE;
+        Some(entry)
+    }
+
+    /// Go back to the state before a instruction of the history, newer history is dropped
+    /// ## Arguments
+    /// * `cpu` - The CPU [`CPU`]
+    /// * `mem` - The memory space [`MEM`]
+    /// * `index` - Index of the instruction, see [`History::index`] [`u64`]
+    /// ## Returns
+    /// Whether the instruction could be reached, it must be between [`History::oldest`]
+    /// and [`History::index`]
+    pub fn seek<E>(&mut self, cpu: &mut CPU<E>, mem: &mut MEM, index: u64) -> bool
+    where
+        E: FnOnce(MessageType) + Copy + Sized,
+    {
+        if index > self.index || index < self.oldest() {
+            return false;
+        }
+        let journal = self.journal.front().map_or(self.index, |entry| entry.index);
+        if index < journal {
+            //Restore the newest snapshot before the instruction and execute up to it
+            while self
+                .snapshots
+                .back()
+                .is_some_and(|(snapshot, _)| *snapshot > index)
+            {
+                self.snapshots.pop_back();
+            }
+            let Some((snapshot, state)) = self.snapshots.back() else {
+                return false;
+            };
+            state.restore(cpu, mem);
+            self.index = *snapshot;
+            self.cycles = state.cycles;
+            self.journal.clear();
+            self.journal_size = 0;
+            while self.index < index {
+                self.step(cpu, mem);
+            }
+            return true;
+        }
+        while self.index > index {
+            let entry = self.pop_back().unwrap();
+            for write in entry.writes.iter().rev() {
+                mem[write.address as usize] = write.old;
+            }
+            cpu.set_state(&entry.state);
+            self.index = entry.index;
+            self.cycles = entry.cycles;
+        }
+        while self
+            .snapshots
+            .back()
+            .is_some_and(|(snapshot, _)| *snapshot > index)
+        {
+            self.snapshots.pop_back();
+        }
+        true
+    }
+
+    /// Undo the last instruction
+    /// ## Arguments
+    /// * `cpu` - The CPU [`CPU`]
+    /// * `mem` - The memory space [`MEM`]
+    /// ## Returns
+    /// Whether there was a instruction to undo
+    pub fn step_back<E>(&mut self, cpu: &mut CPU<E>, mem: &mut MEM) -> bool
+    where
+        E: FnOnce(MessageType) + Copy + Sized,
+    {
+        self.index > 0 && self.seek(cpu, mem, self.index - 1)
+    }
+
+    /// Step back until the program counter is at a address, at least one instruction
+    /// ## Arguments
+    /// * `cpu` - The CPU [`CPU`]
+    /// * `mem` - The memory space [`MEM`]
+    /// * `pc` - Address to stop at [`u16`]
+    /// ## Returns
+    /// Whether the address was found in the journal, nothing changes when it was not
+    pub fn run_back_to<E>(&mut self, cpu: &mut CPU<E>, mem: &mut MEM, pc: u16) -> bool
+    where
+        E: FnOnce(MessageType) + Copy + Sized,
+    {
+        match self.journal.iter().rev().find(|entry| entry.state.PC == pc) {
+            Some(entry) => self.seek(cpu, mem, entry.index),
+            None => false,
+        }
+    }
+
+    /// Newest instruction in the journal which changed a address
+    /// ## Arguments
+    /// * `address` - The address [`u16`]
+    pub fn last_write(&self, address: u16) -> Option<WriteRecord> {
+        self.journal.iter().rev().find_map(|entry| {
+            entry
+                .writes
+                .iter()
+                .rev()
+                .find(|write| write.address == address)
+                .map(|write| WriteRecord {
+                    index: entry.index,
+                    pc: entry.state.PC,
+                    write: *write,
+                })
+        })
+    }
+
+    /// Step back to just before the newest instruction which changed a address
+    /// ## Arguments
+    /// * `cpu` - The CPU [`CPU`]
+    /// * `mem` - The memory space [`MEM`]
+    /// * `address` - The address [`u16`]
+    /// ## Returns
+    /// The write, or `None` when no instruction in the journal changed the address
+    pub fn back_to_last_write<E>(
+        &mut self,
+        cpu: &mut CPU<E>,
+        mem: &mut MEM,
+        address: u16,
+    ) -> Option<WriteRecord>
+    where
+        E: FnOnce(MessageType) + Copy + Sized,
+    {
+        let record = self.last_write(address)?;
+        self.seek(cpu, mem, record.index).then_some(record)
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    fmt::Display,
+    io::{self, Read, Write},
+};
+
+use crate::{
+    cpu::{CpuState, StatusFlags, CPU},
+    debugger::MessageType,
+    mem::MEM,
+};
+
+/// First bytes of every save state
+pub const MAGIC: [u8; 8] = *b"R6502SAV";
+/// Version of the save state format written by this crate
+pub const VERSION: u16 = 1;
+
+/// Tag of the chunk with the registers
+const CPU_CHUNK: [u8; 4] = *b"CPU ";
+/// Tag of the chunk with the cycle counter
+const CYCLES_CHUNK: [u8; 4] = *b"CYC ";
+/// Tag of the chunk with the whole memory
+const MEMORY_CHUNK: [u8; 4] = *b"MEM ";
+/// Size of the memory chunk
+const MEMORY_SIZE: usize = 0x10000;
+
+/// Error while reading a save state
+#[derive(Debug)]
+pub enum StateError {
+    /// Save state could not be read
+    Io(io::Error),
+    /// Data does not start with [`MAGIC`]
+    NotASaveState,
+    /// Save state was written by a newer format
+    UnsupportedVersion {
+        /// Version of the save state
+        found: u16,
+        /// Newest version which can be read, [`VERSION`]
+        supported: u16,
+    },
+    /// Save state ends in the middle of a chunk
+    Truncated,
+    /// Chunk has the wrong size
+    InvalidChunk {
+        /// Tag of the chunk, like `CPU `
+        tag: String,
+        /// Size of the chunk
+        size: usize,
+    },
+    /// Chunk which every save state has is missing
+    MissingChunk(String),
+}
+
+impl Display for StateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateError::Io(error) => write!(f, "{}", error),
+            StateError::NotASaveState => write!(f, "Not a save state"),
+            StateError::UnsupportedVersion { found, supported } => write!(
+                f,
+                "Save state version {} is newer than the supported version {}",
+                found, supported
+            ),
+            StateError::Truncated => write!(f, "Save state is truncated"),
+            StateError::InvalidChunk { tag, size } => {
+                write!(f, "Chunk '{}' has a invalid size of {} bytes", tag, size)
+            }
+            StateError::MissingChunk(tag) => write!(f, "Save state has no '{}' chunk", tag),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl From<io::Error> for StateError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => StateError::Truncated,
+            _ => StateError::Io(error),
+        }
+    }
+}
+
+/// Snapshot of the CPU and the whole memory
+///
+/// The binary format starts with a header, the 8 bytes of [`MAGIC`], the format
+/// [`VERSION`] and the number of chunks as little endian `u16`s. Every chunk is a 4 byte
+/// tag, a little endian `u32` size and its data:
+///
+/// | Tag    | Size  | Data                                                    |
+/// |--------|-------|---------------------------------------------------------|
+/// | `CPU ` | 8     | PC as little endian `u16`, SR, SP, A, X, Y, `NV1BDIZC`  |
+/// | `CYC ` | 8     | Executed cycles as little endian `u64`                  |
+/// | `MEM ` | 65536 | Memory from `$0000` to `$FFFF`                          |
+///
+/// There is no chunk for pending interrupts or attached devices. The CPU takes a
+/// interrupt as soon as it is raised, so none is pending between instructions, and
+/// devices are writes to the memory, which the `MEM ` chunk already holds.
+///
+/// Readers skip chunks they do not know, so later versions can add chunks which older
+/// readers ignore. A save state with a newer version is refused with
+/// [`StateError::UnsupportedVersion`].
+/// ## Example
+/// ```
+/// use rusty_6502::{cpu::CPU, debugger::MessageType, mem::MEM, state::SaveState};
+/// let mut mem = MEM::new();
+/// let mut cpu = CPU::new(|_: MessageType| {});
+/// cpu.reset(0x600, &mut mem);
+/// mem[0x200] = 0x42;
+/// let bytes = SaveState::capture(&cpu, &mem, 120).to_bytes();
+///
+/// mem[0x200] = 0;
+/// cpu.A = 0xFF;
+/// let state = SaveState::from_bytes(&bytes).unwrap();
+/// state.restore(&mut cpu, &mut mem);
+/// assert_eq!((cpu.A, mem[0x200], state.cycles), (0, 0x42, 120));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveState {
+    /// Registers and flags [`CpuState`]
+    pub cpu: CpuState,
+    /// Cycles executed when the state was captured
+    pub cycles: u64,
+    /// The whole memory
+    pub memory: Box<[u8; MEMORY_SIZE]>,
+}
+
+impl SaveState {
+    /// Capture the state of a CPU and its memory
+    /// ## Arguments
+    /// * `cpu` - The CPU [`CPU`]
+    /// * `mem` - The memory space [`MEM`]
+    /// * `cycles` - Cycles executed so far, kept by the caller [`u64`]
+    pub fn capture<E>(cpu: &CPU<E>, mem: &MEM, cycles: u64) -> Self
+    where
+        E: FnOnce(MessageType) + Copy + Sized,
+    {
+        SaveState {
+            cpu: cpu.state(),
+            cycles,
+            memory: Box::new(mem.data),
+        }
+    }
+
+    /// Restore the CPU and the memory
+    /// ## Arguments
+    /// * `cpu` - The CPU [`CPU`]
+    /// * `mem` - The memory space [`MEM`]
+    pub fn restore<E>(&self, cpu: &mut CPU<E>, mem: &mut MEM)
+    where
+        E: FnOnce(MessageType) + Copy + Sized,
+    {
+        cpu.set_state(&self.cpu);
+        mem.data = *self.memory;
+    }
+
+    /// Write the save state in the binary format
+    /// ## Arguments
+    /// * `out` - Where the state is written [`Write`]
+    pub fn write(&self, mut out: impl Write) -> io::Result<()> {
+        let cpu = &self.cpu;
+        let registers = [
+            cpu.PC as u8,
+            (cpu.PC >> 8) as u8,
+            cpu.SR,
+            cpu.SP,
+            cpu.A,
+            cpu.X,
+            cpu.Y,
+            cpu.status_flags.bits(),
+        ];
+        let chunks: [(&[u8; 4], &[u8]); 3] = [
+            (&CPU_CHUNK, &registers),
+            (&CYCLES_CHUNK, &self.cycles.to_le_bytes()),
+            (&MEMORY_CHUNK, self.memory.as_slice()),
+        ];
+        out.write_all(&MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&(chunks.len() as u16).to_le_bytes())?;
+        for (tag, data) in chunks {
+            out.write_all(tag)?;
+            out.write_all(&(data.len() as u32).to_le_bytes())?;
+            out.write_all(data)?;
+        }
+        out.flush()
+    }
+
+    /// Save state in the binary format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(MEMORY_SIZE + 64);
+        self.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// Read a save state written with [`SaveState::write`]
+    /// ## Arguments
+    /// * `input` - Where the state is read from [`Read`]
+    pub fn read(mut input: impl Read) -> Result<SaveState, StateError> {
+        let mut magic = [0; 8];
+        input
+            .read_exact(&mut magic)
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::UnexpectedEof => StateError::NotASaveState,
+                _ => StateError::Io(error),
+            })?;
+        if magic != MAGIC {
+            return Err(StateError::NotASaveState);
+        }
+        let mut word = [0; 2];
+        input.read_exact(&mut word)?;
+        let version = u16::from_le_bytes(word);
+        if version > VERSION {
+            return Err(StateError::UnsupportedVersion {
+                found: version,
+                supported: VERSION,
+            });
+        }
+        input.read_exact(&mut word)?;
+        let count = u16::from_le_bytes(word);
+
+        let mut cpu = None;
+        let mut cycles = None;
+        let mut memory = None;
+        for _ in 0..count {
+            let mut tag = [0; 4];
+            input.read_exact(&mut tag)?;
+            let mut size = [0; 4];
+            input.read_exact(&mut size)?;
+            let size = u32::from_le_bytes(size) as usize;
+            let mut data = Vec::new();
+            (&mut input).take(size as u64).read_to_end(&mut data)?;
+            if data.len() != size {
+                return Err(StateError::Truncated);
+            }
+            let expected = match tag {
+                CPU_CHUNK => 8,
+                CYCLES_CHUNK => 8,
+                MEMORY_CHUNK => MEMORY_SIZE,
+                //Chunks of later versions
+                _ => continue,
+            };
+            if size != expected {
+                return Err(StateError::InvalidChunk {
+                    tag: String::from_utf8_lossy(&tag).into(),
+                    size,
+                });
+            }
+            match tag {
+                CPU_CHUNK => {
+                    cpu = Some(CpuState {
+                        PC: u16::from_le_bytes([data[0], data[1]]),
+                        SR: data[2],
+                        SP: data[3],
+                        A: data[4],
+                        X: data[5],
+                        Y: data[6],
+                        status_flags: StatusFlags::from_bits(data[7]),
+                    })
+                }
+                CYCLES_CHUNK => cycles = Some(u64::from_le_bytes(data.try_into().unwrap())),
+                //The size was checked above
+                _ => memory = Some(data.into_boxed_slice().try_into().unwrap()),
+            }
+        }
+        let missing = |tag: [u8; 4]| StateError::MissingChunk(String::from_utf8_lossy(&tag).into());
+        Ok(SaveState {
+            cpu: cpu.ok_or_else(|| missing(CPU_CHUNK))?,
+            cycles: cycles.unwrap_or(0),
+            memory: memory.ok_or_else(|| missing(MEMORY_CHUNK))?,
+        })
+    }
+
+    /// Read a save state from bytes
+    /// ## Arguments
+    /// * `bytes` - Save state in the binary format [`u8`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<SaveState, StateError> {
+        Self::read(bytes)
+    }
+}
+
+/// Write the state of a CPU and its memory
+/// ## Arguments
+/// * `out` - Where the state is written [`Write`]
+/// * `cpu` - The CPU [`CPU`]
+/// * `mem` - The memory space [`MEM`]
+/// * `cycles` - Cycles executed so far [`u64`]
+pub fn save_state<E>(out: impl Write, cpu: &CPU<E>, mem: &MEM, cycles: u64) -> io::Result<()>
+where
+    E: FnOnce(MessageType) + Copy + Sized,
+{
+    SaveState::capture(cpu, mem, cycles).write(out)
+}
+
+/// Read a save state into a CPU and its memory, which are unchanged on errors
+/// ## Arguments
+/// * `input` - Where the state is read from [`Read`]
+/// * `cpu` - The CPU [`CPU`]
+/// * `mem` - The memory space [`MEM`]
+/// ## Returns
+/// Cycles executed when the state was saved
+pub fn load_state<E>(input: impl Read, cpu: &mut CPU<E>, mem: &mut MEM) -> Result<u64, StateError>
+where
+    E: FnOnce(MessageType) + Copy + Sized,
+{
+    let state = SaveState::read(input)?;
+    state.restore(cpu, mem);
+    Ok(state.cycles)
+}
@@ -0,0 +1,563 @@
+use std::{
+    any::Any,
+    fmt::Display,
+    panic::{self, AssertUnwindSafe},
+};
+
+use crate::{
+    cpu::{CpuState, CPU},
+    debugger::MessageType,
+    mem::MEM,
+};
+
+mod vectors;
+
+pub use vectors::{
+    run_vector_directory, run_vector_file, run_vectors, BusCycle, Vector, VectorError,
+    VectorFailure, VectorReport, VectorState,
+};
+
+/// Register or flag of the CPU which a test can expect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    /// Program counter
+    PC,
+    /// Status register
+    SR,
+    /// Stack pointer
+    SP,
+    /// Accumulator
+    A,
+    /// X register
+    X,
+    /// Y register
+    Y,
+    /// Negative flag
+    N,
+    /// Overflow flag
+    V,
+    /// Unused flag
+    U,
+    /// Interrupt flag
+    I,
+    /// Break flag
+    B,
+    /// Decimal mode flag
+    D,
+    /// Zero flag
+    Z,
+    /// Carry flag
+    C,
+}
+
+impl Register {
+    /// Every register, in the order of the state diff
+    pub const ALL: [Register; 14] = [
+        Register::PC,
+        Register::SR,
+        Register::SP,
+        Register::A,
+        Register::X,
+        Register::Y,
+        Register::N,
+        Register::V,
+        Register::U,
+        Register::I,
+        Register::B,
+        Register::D,
+        Register::Z,
+        Register::C,
+    ];
+
+    /// Value of the register in a state
+    /// ## Arguments
+    /// * `state` - State of the CPU [`CpuState`]
+    pub fn get(self, state: &CpuState) -> u16 {
+        let flags = &state.status_flags;
+        (match self {
+            Register::PC => return state.PC,
+            Register::SR => state.SR,
+            Register::SP => state.SP,
+            Register::A => state.A,
+            Register::X => state.X,
+            Register::Y => state.Y,
+            Register::N => flags.N,
+            Register::V => flags.V,
+            Register::U => flags.U,
+            Register::I => flags.I,
+            Register::B => flags.B,
+            Register::D => flags.D,
+            Register::Z => flags.Z,
+            Register::C => flags.C,
+        }) as u16
+    }
+
+    /// Format a value of the register, `$0600` for the program counter, `$01` for
+    /// registers and `1` for flags
+    fn format(self, value: u16) -> String {
+        match self {
+            Register::PC => format!("${:04X}", value),
+            Register::SR | Register::SP | Register::A | Register::X | Register::Y => {
+                format!("${:02X}", value)
+            }
+            _ => value.to_string(),
+        }
+    }
+}
+
+impl Display for Register {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(&format!("{:?}", self))
+    }
+}
+
+/// Declarative test of a program: the initial state, memory patches and the program, then
+/// the expected registers, flags, memory and cycles
+///
+/// The program is loaded at the program counter of the initial state and runs until the
+/// program counter reaches the end of the program or a `BRK`, which is not executed.
+/// Registers, flags and memory which are not expected are checked to be unchanged, except
+/// the program counter.
+/// ## Example
+/// ```
+/// use rusty_6502::{asm6502, cpu::CpuState, testing::{Register, TestCase}};
+/// TestCase::new(CpuState { X: 0x01, ..CpuState::reset(0x600) })
+///     .patch(0x0011, &[0x80])
+///     .program(&asm6502! { LDA $10,X; STA $0200 })
+///     .expect(Register::A, 0x80)
+///     .expect(Register::N, 1)
+///     .expect_memory(0x0200, &[0x80])
+///     .expect_cycles(8)
+///     .assert();
+/// ```
+#[derive(Debug, Clone)]
+pub struct TestCase {
+    initial: CpuState,
+    patches: Vec<(u16, Vec<u8>)>,
+    program: Vec<u8>,
+    expected: Vec<(Register, u16)>,
+    memory: Vec<(u16, Vec<u8>)>,
+    cycles: Option<usize>,
+    max_instructions: usize,
+}
+
+/// State after a test case ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    /// Registers and flags [`CpuState`]
+    pub state: CpuState,
+    /// Cycles of the executed instructions
+    pub cycles: usize,
+    /// Number of executed instructions
+    pub instructions: usize,
+}
+
+/// Failed test case, displayed as a diff of the whole state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestFailure {
+    /// The diff, lines which differ start with `!`
+    pub report: String,
+}
+
+impl Display for TestFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.report)
+    }
+}
+
+impl std::error::Error for TestFailure {}
+
+/// Memory differences which are listed before the rest is counted
+const MAX_MEMORY_LINES: usize = 16;
+
+impl TestCase {
+    /// Create a test case starting from the given state
+    /// ## Arguments
+    /// * `initial` - Registers and flags before the program runs [`CpuState`]
+    pub fn new(initial: CpuState) -> Self {
+        TestCase {
+            initial,
+            patches: Vec::new(),
+            program: Vec::new(),
+            expected: Vec::new(),
+            memory: Vec::new(),
+            cycles: None,
+            max_instructions: 100_000,
+        }
+    }
+
+    /// Write bytes to memory before the program runs
+    /// ## Arguments
+    /// * `address` - Address of the first byte [`u16`]
+    /// * `bytes` - Bytes to write [`u8`]
+    pub fn patch(&mut self, address: u16, bytes: &[u8]) -> &mut Self {
+        self.patches.push((address, bytes.to_vec()));
+        self
+    }
+
+    /// Set the program, loaded at the program counter after the patches
+    /// ## Arguments
+    /// * `bytes` - Bytes of the program [`u8`]
+    pub fn program(&mut self, bytes: &[u8]) -> &mut Self {
+        self.program = bytes.to_vec();
+        self
+    }
+
+    /// Expect a value of a register or flag
+    /// ## Arguments
+    /// * `register` - Register or flag [`Register`]
+    /// * `value` - Expected value [`u16`]
+    pub fn expect(&mut self, register: Register, value: u16) -> &mut Self {
+        self.expected.retain(|(other, _)| *other != register);
+        self.expected.push((register, value));
+        self
+    }
+
+    /// Expect bytes in memory
+    /// ## Arguments
+    /// * `address` - Address of the first byte [`u16`]
+    /// * `bytes` - Expected bytes [`u8`]
+    pub fn expect_memory(&mut self, address: u16, bytes: &[u8]) -> &mut Self {
+        self.memory.push((address, bytes.to_vec()));
+        self
+    }
+
+    /// Expect the cycles of the executed instructions
+    /// ## Arguments
+    /// * `cycles` - Expected cycles [`usize`]
+    pub fn expect_cycles(&mut self, cycles: usize) -> &mut Self {
+        self.cycles = Some(cycles);
+        self
+    }
+
+    /// Limit the executed instructions, 100000 by default
+    /// ## Arguments
+    /// * `instructions` - Maximum number of instructions [`usize`]
+    pub fn max_instructions(&mut self, instructions: usize) -> &mut Self {
+        self.max_instructions = instructions;
+        self
+    }
+
+    /// Run the program and compare the state with the expected one
+    /// ## Returns
+    /// The final state, or the diff of the whole state if anything differs
+    pub fn run(&self) -> Result<Outcome, TestFailure> {
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(|_: MessageType| {});
+        cpu.reset(self.initial.PC, &mut mem);
+        cpu.set_state(&self.initial);
+        for (address, bytes) in &self.patches {
+            mem.load(*address as usize, bytes);
+        }
+        mem.load(self.initial.PC as usize, &self.program);
+        let before = mem.data;
+
+        let end = self.initial.PC as usize + self.program.len();
+        let mut outcome = Outcome {
+            state: self.initial,
+            cycles: 0,
+            instructions: 0,
+        };
+        while cpu.PC as usize != end && mem[cpu.PC as usize] != 0x00 {
+            if outcome.instructions == self.max_instructions {
+                return Err(TestFailure {
+                    report: format!(
+                        "Program did not finish in {} instructions, PC is ${:04X}",
+                        self.max_instructions, cpu.PC
+                    ),
+                });
+            }
+            let (_, cycles, _) = cpu.step(&mut mem);
+            outcome.cycles += cycles as usize;
+            outcome.instructions += 1;
+        }
+        outcome.state = cpu.state();
+
+        //Everything which is not expected must be unchanged
+        let mut expected_memory = before;
+        for (address, bytes) in &self.memory {
+            let address = *address as usize;
+            expected_memory[address..address + bytes.len()].copy_from_slice(bytes);
+        }
+        let mut failed = false;
+        let mut report = format!(
+            "State differs from the expected state\n{:<8} {:<9} {:<9} {}\n",
+            "", "initial", "expected", "actual"
+        );
+        for register in Register::ALL {
+            let initial = register.get(&self.initial);
+            let expected = self
+                .expected
+                .iter()
+                .find(|(other, _)| *other == register)
+                .map(|(_, value)| *value)
+                .or((register != Register::PC).then_some(initial));
+            let actual = register.get(&outcome.state);
+            let differs = expected.is_some_and(|expected| expected != actual);
+            failed |= differs;
+            report += &format!(
+                "{} {:<6} {:<9} {:<9} {}\n",
+                if differs { '!' } else { ' ' },
+                register,
+                register.format(initial),
+                expected.map_or("*".to_string(), |expected| register.format(expected)),
+                register.format(actual)
+            );
+        }
+        if let Some(cycles) = self.cycles {
+            let differs = cycles != outcome.cycles;
+            failed |= differs;
+            report += &format!(
+                "{} {:<6} {:<9} {:<9} {}\n",
+                if differs { '!' } else { ' ' },
+                "cycles",
+                "",
+                cycles,
+                outcome.cycles
+            );
+        }
+        let differences: Vec<usize> = (0..mem.data.len())
+            .filter(|address| mem.data[*address] != expected_memory[*address])
+            .collect();
+        if !differences.is_empty() {
+            failed = true;
+            report += "memory\n";
+            for address in differences.iter().take(MAX_MEMORY_LINES) {
+                report += &format!(
+                    "! ${:04X}  ${:02X}       ${:02X}       ${:02X}\n",
+                    address, before[*address], expected_memory[*address], mem.data[*address]
+                );
+            }
+            if differences.len() > MAX_MEMORY_LINES {
+                report += &format!(
+                    "  ... {} more bytes differ\n",
+                    differences.len() - MAX_MEMORY_LINES
+                );
+            }
+        }
+        if failed {
+            return Err(TestFailure { report });
+        }
+        Ok(outcome)
+    }
+
+    /// Run the program like [`TestCase::run`]
+    /// ## Panics
+    /// With the diff of the whole state if anything differs
+    pub fn assert(&self) -> Outcome {
+        match self.run() {
+            Ok(outcome) => outcome,
+            Err(failure) => panic!("{}", failure),
+        }
+    }
+}
+
+/// Conformance test binary which reports its result by trapping the program counter in a
+/// loop, like the Klaus Dormann functional and decimal tests
+///
+/// The binary must already be in memory, the CPU starts from [`CpuState::reset`] at the
+/// start address. An instruction which does not change the program counter, like `JMP *`
+/// or a branch to itself, is a trap: at the success address the test passed, anywhere
+/// else it failed. Tests without a success address pass when their error byte is zero.
+/// ## Example
+/// ```
+/// use rusty_6502::{image, mem::MEM, testing::TrapTest};
+/// let mut mem = MEM::new();
+/// image::load_raw(&mut mem, &[0xE8, 0xE8, 0xE8], 0x400).unwrap();
+/// let outcome = TrapTest::new(0x400).success(0x403).run(&mut mem).unwrap();
+/// assert_eq!(outcome.state.X, 3);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TrapTest {
+    start: u16,
+    success: Option<u16>,
+    test_number: Option<u16>,
+    error: Option<u16>,
+    max_instructions: usize,
+}
+
+/// Failed conformance test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrapFailure {
+    /// The program counter was trapped outside of the success address
+    Trapped {
+        /// Address of the trap
+        pc: u16,
+        /// Number of the failing test, read from the test number address
+        test: Option<u8>,
+        /// Number of executed instructions
+        instructions: usize,
+    },
+    /// The CPU panicked, usually on a instruction which is not implemented yet
+    Panicked {
+        /// Address of the instruction which panicked
+        pc: u16,
+        /// Number of the failing test, read from the test number address
+        test: Option<u8>,
+        /// Number of instructions executed before it
+        instructions: usize,
+        /// Message of the panic
+        message: String,
+    },
+    /// The test did not trap in the maximum number of instructions
+    Timeout {
+        /// Program counter after the last instruction
+        pc: u16,
+        /// Number of executed instructions
+        instructions: usize,
+    },
+}
+
+impl Display for TrapFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrapFailure::Trapped {
+                pc,
+                test: Some(test),
+                instructions,
+            } => write!(
+                f,
+                "Test ${:02X} failed, trapped at ${:04X} after {} instructions",
+                test, pc, instructions
+            ),
+            TrapFailure::Trapped {
+                pc,
+                test: None,
+                instructions,
+            } => write!(
+                f,
+                "Test failed, trapped at ${:04X} after {} instructions",
+                pc, instructions
+            ),
+            TrapFailure::Panicked {
+                pc,
+                test,
+                instructions,
+                message,
+            } => {
+                if let Some(test) = test {
+                    write!(f, "Test ${:02X} failed, ", test)?;
+                }
+                write!(
+                    f,
+                    "CPU panicked at ${:04X} after {} instructions: {}",
+                    pc, instructions, message
+                )
+            }
+            TrapFailure::Timeout { pc, instructions } => write!(
+                f,
+                "Test did not finish in {} instructions, PC is ${:04X}",
+                instructions, pc
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TrapFailure {}
+
+/// Message of a caught panic, empty if it is not a string
+pub(crate) fn panic_message(error: &(dyn Any + Send)) -> &str {
+    error
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| error.downcast_ref::<&str>().copied())
+        .unwrap_or("")
+}
+
+impl TrapTest {
+    /// Create a test starting at the given address
+    /// ## Arguments
+    /// * `start` - Address of the first instruction [`u16`]
+    pub fn new(start: u16) -> Self {
+        TrapTest {
+            start,
+            success: None,
+            test_number: None,
+            error: None,
+            max_instructions: 100_000_000,
+        }
+    }
+
+    /// Set the address of the trap which means the test passed
+    /// ## Arguments
+    /// * `address` - Address of the success trap [`u16`]
+    pub fn success(&mut self, address: u16) -> &mut Self {
+        self.success = Some(address);
+        self
+    }
+
+    /// Set the address which holds the number of the running test, reported on failure
+    /// ## Arguments
+    /// * `address` - Address of the test number [`u16`]
+    pub fn test_number(&mut self, address: u16) -> &mut Self {
+        self.test_number = Some(address);
+        self
+    }
+
+    /// Set the address of a byte which is zero when the test passed, checked on any trap
+    /// ## Arguments
+    /// * `address` - Address of the error byte [`u16`]
+    pub fn error(&mut self, address: u16) -> &mut Self {
+        self.error = Some(address);
+        self
+    }
+
+    /// Limit the executed instructions, 100000000 by default
+    /// ## Arguments
+    /// * `instructions` - Maximum number of instructions [`usize`]
+    pub fn max_instructions(&mut self, instructions: usize) -> &mut Self {
+        self.max_instructions = instructions;
+        self
+    }
+
+    /// Run the test until it traps or reaches the success address
+    /// ## Arguments
+    /// * `mem` - Memory with the loaded test [`MEM`]
+    /// ## Returns
+    /// The state at the success trap, or where and in which test it failed
+    pub fn run(&self, mem: &mut MEM) -> Result<Outcome, TrapFailure> {
+        let mut cpu = CPU::new(|_: MessageType| {});
+        cpu.set_state(&CpuState::reset(self.start));
+        let mut outcome = Outcome {
+            state: cpu.state(),
+            cycles: 0,
+            instructions: 0,
+        };
+        loop {
+            if Some(cpu.PC) == self.success {
+                outcome.state = cpu.state();
+                return Ok(outcome);
+            }
+            if outcome.instructions == self.max_instructions {
+                return Err(TrapFailure::Timeout {
+                    pc: cpu.PC,
+                    instructions: outcome.instructions,
+                });
+            }
+            let pc = cpu.PC;
+            let step = panic::catch_unwind(AssertUnwindSafe(|| cpu.step(mem)));
+            let (_, cycles, _) = step.map_err(|error| TrapFailure::Panicked {
+                pc,
+                test: self.test_number.map(|address| mem[address as usize]),
+                instructions: outcome.instructions,
+                message: panic_message(error.as_ref()).to_string(),
+            })?;
+            outcome.cycles += cycles as usize;
+            outcome.instructions += 1;
+            if cpu.PC != pc {
+                continue;
+            }
+            outcome.state = cpu.state();
+            let passed =
+                self.success.is_none() && self.error.is_some_and(|error| mem[error as usize] == 0);
+            if passed {
+                return Ok(outcome);
+            }
+            return Err(TrapFailure::Trapped {
+                pc,
+                test: self.test_number.map(|address| mem[address as usize]),
+                instructions: outcome.instructions,
+            });
+        }
+    }
+}
@@ -0,0 +1,401 @@
+use std::{
+    fmt::Display,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+};
+
+use crate::{
+    cpu::{CpuState, StatusFlags, CPU},
+    debugger::MessageType,
+    json::{self, Json},
+    mem::MEM,
+};
+
+/// Registers and memory of a single step test vector
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VectorState {
+    /// Registers and flags, the status register is also split into the flags [`CpuState`]
+    pub state: CpuState,
+    /// Address and value of every byte the vector uses
+    pub ram: Vec<(u16, u8)>,
+}
+
+/// Bus access during one cycle of a test vector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusCycle {
+    /// Address on the bus
+    pub address: u16,
+    /// Value read or written
+    pub value: u8,
+    /// Whether the cycle writes
+    pub write: bool,
+}
+
+/// Single step test vector: the state before and after one instruction and its bus cycles
+///
+/// Vectors are read from JSON arrays in the layout of the widely used single step tests,
+/// `{"name": "a9 01 02", "initial": {"pc": 1536, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36,
+/// "ram": [[1536, 169], [1537, 1]]}, "final": {...}, "cycles": [[1536, 169, "read"], ...]}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vector {
+    /// Name of the vector, the bytes of the instruction
+    pub name: String,
+    /// State before the instruction [`VectorState`]
+    pub initial: VectorState,
+    /// Expected state after the instruction [`VectorState`]
+    pub expected: VectorState,
+    /// Expected bus accesses, one per cycle [`BusCycle`]
+    pub cycles: Vec<BusCycle>,
+}
+
+/// Vector which did not match
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VectorFailure {
+    /// Name of the vector
+    pub name: String,
+    /// Every mismatch, like `a: expected $01, found $00`
+    pub message: String,
+}
+
+/// Results of the vectors of one opcode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VectorReport {
+    /// The tested opcode
+    pub opcode: u8,
+    /// Number of vectors which matched
+    pub passed: usize,
+    /// Vectors which did not match [`VectorFailure`]
+    pub failures: Vec<VectorFailure>,
+}
+
+/// Error while reading test vectors
+#[derive(Debug)]
+pub enum VectorError {
+    /// File could not be read
+    Io(std::io::Error),
+    /// File is not a array of vectors
+    Invalid(String),
+}
+
+impl Display for VectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VectorError::Io(error) => write!(f, "{}", error),
+            VectorError::Invalid(message) => write!(f, "Invalid test vectors: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for VectorError {}
+
+impl From<std::io::Error> for VectorError {
+    fn from(error: std::io::Error) -> Self {
+        VectorError::Io(error)
+    }
+}
+
+/// Failures listed by the display of a report before the rest is counted
+const MAX_FAILURE_LINES: usize = 8;
+
+impl Display for VectorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "${:02X}: {} passed, {} failed",
+            self.opcode,
+            self.passed,
+            self.failures.len()
+        )?;
+        for failure in self.failures.iter().take(MAX_FAILURE_LINES) {
+            writeln!(f, "  {}: {}", failure.name, failure.message)?;
+        }
+        if self.failures.len() > MAX_FAILURE_LINES {
+            writeln!(
+                f,
+                "  ... {} more vectors failed",
+                self.failures.len() - MAX_FAILURE_LINES
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl VectorReport {
+    /// Whether every vector matched
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl VectorState {
+    fn parse(json: &Json) -> Result<Self, String> {
+        let number = |field: &str, max: i64| {
+            json.get(field)
+                .and_then(Json::as_i64)
+                .filter(|value| (0..=max).contains(value))
+                .ok_or_else(|| format!("Invalid '{}'", field))
+        };
+        let byte = |field: &str| number(field, 0xFF).map(|value| value as u8);
+        let p = byte("p")?;
+        let ram = json
+            .get("ram")
+            .and_then(Json::as_array)
+            .ok_or("Invalid 'ram'")?
+            .iter()
+            .map(
+                |pair| match pair.as_array().map(|pair| pair.iter().map(Json::as_i64)) {
+                    Some(mut pair) => match (pair.next().flatten(), pair.next().flatten()) {
+                        (Some(address @ 0..=0xFFFF), Some(value @ 0..=0xFF)) => {
+                            Ok((address as u16, value as u8))
+                        }
+                        _ => Err("Invalid 'ram'".to_string()),
+                    },
+                    None => Err("Invalid 'ram'".to_string()),
+                },
+            )
+            .collect::<Result<_, _>>()?;
+        Ok(VectorState {
+            state: CpuState {
+                PC: number("pc", 0xFFFF)? as u16,
+                SR: p,
+                SP: byte("s")?,
+                A: byte("a")?,
+                X: byte("x")?,
+                Y: byte("y")?,
+                status_flags: StatusFlags::from_bits(p),
+            },
+            ram,
+        })
+    }
+}
+
+impl Vector {
+    /// Parse a JSON array of vectors
+    /// ## Arguments
+    /// * `text` - Contents of a vector file [`str`]
+    pub fn parse_all(text: &str) -> Result<Vec<Vector>, VectorError> {
+        let json = json::parse(text).map_err(VectorError::Invalid)?;
+        let vectors = json
+            .as_array()
+            .ok_or_else(|| VectorError::Invalid("Expected a array of vectors".to_string()))?;
+        vectors
+            .iter()
+            .enumerate()
+            .map(|(index, vector)| {
+                Self::parse(vector).map_err(|message| {
+                    VectorError::Invalid(format!("vector {}: {}", index, message))
+                })
+            })
+            .collect()
+    }
+
+    fn parse(json: &Json) -> Result<Vector, String> {
+        let state = |field: &str| {
+            VectorState::parse(json.get(field).ok_or(format!("Missing '{}'", field))?)
+        };
+        let cycles = json
+            .get("cycles")
+            .and_then(Json::as_array)
+            .ok_or("Invalid 'cycles'")?
+            .iter()
+            .map(|cycle| {
+                let cycle = cycle.as_array().ok_or("Invalid cycle")?;
+                match cycle {
+                    [address, value, kind] => Ok(BusCycle {
+                        address: address
+                            .as_i64()
+                            .and_then(|address| u16::try_from(address).ok())
+                            .ok_or("Invalid cycle address")?,
+                        value: value
+                            .as_i64()
+                            .and_then(|value| u8::try_from(value).ok())
+                            .ok_or("Invalid cycle value")?,
+                        write: match kind.as_str() {
+                            Some("read") => false,
+                            Some("write") => true,
+                            _ => return Err("Invalid cycle kind".to_string()),
+                        },
+                    }),
+                    _ => Err("Invalid cycle".to_string()),
+                }
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Vector {
+            name: json
+                .get("name")
+                .and_then(Json::as_str)
+                .unwrap_or("")
+                .to_string(),
+            initial: state("initial")?,
+            expected: state("final")?,
+            cycles,
+        })
+    }
+
+    /// Execute the instruction of the vector and compare the state after it
+    ///
+    /// The registers, the listed bytes and the number of cycles are compared, and the
+    /// reads and writes of the CPU are compared with the bus cycles of the vector one by
+    /// one. A instruction which panics, like a unimplemented one, fails.
+    /// ## Returns
+    /// Every mismatch, like `a: expected $01, found $00`
+    pub fn run(&self) -> Result<(), String> {
+        let mut mem = MEM::new();
+        for (address, value) in &self.initial.ram {
+            mem[*address as usize] = *value;
+        }
+        let mut cpu = CPU::new(|_: MessageType| {});
+        cpu.set_state(&self.initial.state);
+        mem.record_accesses();
+        let cycles =
+            panic::catch_unwind(AssertUnwindSafe(|| cpu.step(&mut mem).1)).map_err(|error| {
+                format!("panicked: {}", super::panic_message(error.as_ref()))
+            })?;
+
+        let actual = cpu.state();
+        let expected = &self.expected.state;
+        let mut mismatches = Vec::new();
+        let mut compare = |name: String, expected: u16, actual: u16, width: usize| {
+            if expected != actual {
+                mismatches.push(format!(
+                    "{}: expected ${:0width$X}, found ${:0width$X}",
+                    name,
+                    expected,
+                    actual,
+                    width = width
+                ));
+            }
+        };
+        compare("pc".to_string(), expected.PC, actual.PC, 4);
+        compare("s".to_string(), expected.SP as u16, actual.SP as u16, 2);
+        compare("a".to_string(), expected.A as u16, actual.A as u16, 2);
+        compare("x".to_string(), expected.X as u16, actual.X as u16, 2);
+        compare("y".to_string(), expected.Y as u16, actual.Y as u16, 2);
+        compare(
+            "p".to_string(),
+            expected.status_flags.bits() as u16,
+            actual.status_flags.bits() as u16,
+            2,
+        );
+        for (address, value) in &self.expected.ram {
+            compare(
+                format!("ram[${:04X}]", address),
+                *value as u16,
+                mem[*address as usize] as u16,
+                2,
+            );
+        }
+        if cycles as usize != self.cycles.len() {
+            mismatches.push(format!(
+                "cycles: expected {}, found {}",
+                self.cycles.len(),
+                cycles
+            ));
+        }
+        //Only the first differing bus cycle is reported, the ones after it are shifted
+        let describe = |cycle: Option<BusCycle>| match cycle {
+            Some(BusCycle {
+                address,
+                value,
+                write,
+            }) => format!(
+                "{} ${:04X} = ${:02X}",
+                if write { "write" } else { "read" },
+                address,
+                value
+            ),
+            None => "none".to_string(),
+        };
+        let accesses: Vec<BusCycle> = mem
+            .take_accesses()
+            .into_iter()
+            .map(|access| BusCycle {
+                address: access.address,
+                value: access.value,
+                write: access.write,
+            })
+            .collect();
+        let bus_cycles = accesses.len().max(self.cycles.len());
+        if let Some(index) =
+            (0..bus_cycles).find(|index| accesses.get(*index) != self.cycles.get(*index))
+        {
+            mismatches.push(format!(
+                "bus cycle {}: expected {}, found {}",
+                index + 1,
+                describe(self.cycles.get(index).copied()),
+                describe(accesses.get(index).copied())
+            ));
+        }
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatches.join(", "))
+        }
+    }
+}
+
+/// Run every vector of one opcode
+///
+/// A vector which panics fails and the rest still run, the panic hook is silenced while they
+/// run so each unimplemented instruction does not print a message.
+/// ## Arguments
+/// * `opcode` - The tested opcode [`u8`]
+/// * `vectors` - Vectors of the opcode [`Vector`]
+pub fn run_vectors(opcode: u8, vectors: &[Vector]) -> VectorReport {
+    let mut report = VectorReport {
+        opcode,
+        passed: 0,
+        failures: Vec::new(),
+    };
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    for vector in vectors {
+        match vector.run() {
+            Ok(()) => report.passed += 1,
+            Err(message) => report.failures.push(VectorFailure {
+                name: vector.name.clone(),
+                message,
+            }),
+        }
+    }
+    panic::set_hook(hook);
+    report
+}
+
+/// Run a vector file named after its opcode in hex, like `a9.json`
+/// ## Arguments
+/// * `path` - Path of the file [`Path`]
+pub fn run_vector_file(path: impl AsRef<Path>) -> Result<VectorReport, VectorError> {
+    let path = path.as_ref();
+    let opcode = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| u8::from_str_radix(stem, 16).ok())
+        .ok_or_else(|| {
+            VectorError::Invalid(format!("{} is not named after a opcode", path.display()))
+        })?;
+    let vectors = Vector::parse_all(&std::fs::read_to_string(path)?)?;
+    Ok(run_vectors(opcode, &vectors))
+}
+
+/// Run every vector file of a directory, sorted by opcode
+/// ## Arguments
+/// * `directory` - Directory with files like `00.json` to `ff.json` [`Path`]
+pub fn run_vector_directory(directory: impl AsRef<Path>) -> Result<Vec<VectorReport>, VectorError> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            paths.push(path);
+        }
+    }
+    let mut reports = paths
+        .iter()
+        .map(run_vector_file)
+        .collect::<Result<Vec<_>, _>>()?;
+    reports.sort_by_key(|report| report.opcode);
+    Ok(reports)
+}
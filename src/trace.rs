@@ -0,0 +1,408 @@
+use std::{
+    io::{self, Write},
+    ops::RangeInclusive,
+};
+
+use crate::{
+    asm,
+    cpu::{CpuState, CPU},
+    debugger::MessageType,
+    mem::MEM,
+};
+
+/// Width of the disassembly column, the registers start after it
+const DISASSEMBLY_WIDTH: usize = 32;
+
+/// Format the trace line of the instruction at the program counter, before it is executed
+///
+/// The columns follow the widely used nestest log, without its PPU column: the program
+/// counter, the raw bytes, the disassembly, the registers and the cycles executed before
+/// the instruction.
+/// ## Arguments
+/// * `state` - State before the instruction [`CpuState`]
+/// * `mem` - Memory with the instruction [`MEM`]
+/// * `cycles` - Cycles executed before the instruction [`usize`]
+/// ## Example
+/// ```
+/// use rusty_6502::{cpu::CpuState, mem::MEM, trace};
+/// let mut mem = MEM::new();
+/// mem.load(0x600, &[0xA9, 0x01]);
+/// assert_eq!(
+///     trace::trace_line(&CpuState::reset(0x600), &mem, 7),
+///     "0600  A9 01     LDA #$01                        A:00 X:00 Y:00 P:20 SP:FF CYC:7"
+/// );
+/// ```
+pub fn trace_line(state: &CpuState, mem: &MEM, cycles: usize) -> String {
+    let pc = state.PC as usize;
+    let bytes = &mem.data[pc..(pc + 3).min(mem.data.len())];
+    let line = &asm::disassemble(bytes, state.PC, &[])[0];
+    let raw = line
+        .bytes
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "{:04X}  {:<8}  {:<width$}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+        state.PC,
+        raw,
+        line.text(),
+        state.A,
+        state.X,
+        state.Y,
+        state.status_flags.bits(),
+        state.SP,
+        cycles,
+        width = DISASSEMBLY_WIDTH
+    )
+}
+
+/// Execution tracer, writes a line for every executed instruction
+///
+/// Lines are written with [`trace_line`] to any [`Write`]r. Only instructions in the
+/// address range are written, after skipping the first matching ones and up to the
+/// limit. Cycles are counted for every instruction, written or not.
+/// ## Example
+/// ```
+/// use rusty_6502::{asm6502, cpu::CPU, debugger::MessageType, mem::MEM, trace::Tracer};
+/// let mut mem = MEM::new();
+/// let mut cpu = CPU::new(|_: MessageType| {});
+/// cpu.reset(0x600, &mut mem);
+/// mem.load(0x600, &asm6502! { LDX #$01; INX });
+/// let mut tracer = Tracer::new(Vec::new());
+/// tracer.step(&mut cpu, &mut mem).unwrap();
+/// tracer.step(&mut cpu, &mut mem).unwrap();
+/// let log = String::from_utf8(tracer.into_inner()).unwrap();
+/// assert!(log.ends_with("0602  E8        INX                             A:00 X:01 Y:00 P:20 SP:FF CYC:2\n"));
+/// ```
+#[derive(Debug)]
+pub struct Tracer<W: Write> {
+    out: W,
+    range: RangeInclusive<u16>,
+    skip: usize,
+    limit: Option<usize>,
+    matched: usize,
+    written: usize,
+    cycles: usize,
+}
+
+impl<W: Write> Tracer<W> {
+    /// Create a tracer which writes every instruction
+    /// ## Arguments
+    /// * `out` - Where lines are written [`Write`]
+    pub fn new(out: W) -> Self {
+        Tracer {
+            out,
+            range: 0..=0xFFFF,
+            skip: 0,
+            limit: None,
+            matched: 0,
+            written: 0,
+            cycles: 0,
+        }
+    }
+
+    /// Only write instructions whose address is in the range
+    /// ## Arguments
+    /// * `range` - Addresses of the traced instructions [`RangeInclusive`]
+    pub fn range(&mut self, range: RangeInclusive<u16>) -> &mut Self {
+        self.range = range;
+        self
+    }
+
+    /// Do not write the first instructions in the range
+    /// ## Arguments
+    /// * `instructions` - Number of instructions to skip [`usize`]
+    pub fn skip(&mut self, instructions: usize) -> &mut Self {
+        self.skip = instructions;
+        self
+    }
+
+    /// Stop writing after a number of lines
+    /// ## Arguments
+    /// * `lines` - Maximum number of lines [`usize`]
+    pub fn limit(&mut self, lines: usize) -> &mut Self {
+        self.limit = Some(lines);
+        self
+    }
+
+    /// Set the cycles executed before tracing, like the 7 cycles of a reset
+    /// ## Arguments
+    /// * `cycles` - Cycles of the next line [`usize`]
+    pub fn cycles(&mut self, cycles: usize) -> &mut Self {
+        self.cycles = cycles;
+        self
+    }
+
+    /// Cycles executed so far
+    pub fn total_cycles(&self) -> usize {
+        self.cycles
+    }
+
+    /// Number of written lines
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Whether the limit of lines is reached
+    pub fn is_done(&self) -> bool {
+        self.limit.is_some_and(|limit| self.written >= limit)
+    }
+
+    /// Get back the writer
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Write the line of the instruction at the program counter if it passes the filters,
+    /// then execute it with [`CPU::step`]
+    /// ## Arguments
+    /// * `cpu` - The CPU [`CPU`]
+    /// * `mem` - The memory space [`MEM`]
+    /// ## Returns
+    /// (opcode: u8, cycles: u32, halted: bool) of the executed instruction
+    pub fn step<E>(&mut self, cpu: &mut CPU<E>, mem: &mut MEM) -> io::Result<(u8, u32, bool)>
+    where
+        E: FnOnce(MessageType) + Copy + Sized,
+    {
+        if self.range.contains(&cpu.PC) && !self.is_done() {
+            self.matched += 1;
+            if self.matched > self.skip {
+                writeln!(self.out, "{}", trace_line(&cpu.state(), mem, self.cycles))?;
+                self.written += 1;
+            }
+        }
+        let result = cpu.step(mem);
+        self.cycles += result.1 as usize;
+        Ok(result)
+    }
+
+    /// Trace until the CPU halts, the limit of lines is reached or a number of instructions
+    /// is executed
+    /// ## Arguments
+    /// * `cpu` - The CPU [`CPU`]
+    /// * `mem` - The memory space [`MEM`]
+    /// * `max_instructions` - Maximum number of executed instructions [`usize`]
+    /// ## Returns
+    /// Number of executed instructions
+    pub fn run<E>(
+        &mut self,
+        cpu: &mut CPU<E>,
+        mem: &mut MEM,
+        max_instructions: usize,
+    ) -> io::Result<usize>
+    where
+        E: FnOnce(MessageType) + Copy + Sized,
+    {
+        let mut executed = 0;
+        while executed < max_instructions && !self.is_done() {
+            let (_, _, halted) = self.step(cpu, mem)?;
+            executed += 1;
+            if halted {
+                break;
+            }
+        }
+        self.out.flush()?;
+        Ok(executed)
+    }
+}
+
+/// Instruction of a trace log, parsed from a line written by [`trace_line`] or a
+/// reference log in the same columns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// Line of the entry in its log, starting from 1
+    pub line: usize,
+    /// The whole line
+    pub text: String,
+    /// Program counter
+    pub pc: u16,
+    /// Accumulator
+    pub a: u8,
+    /// X register
+    pub x: u8,
+    /// Y register
+    pub y: u8,
+    /// Status register
+    pub p: u8,
+    /// Stack pointer
+    pub sp: u8,
+    /// Cycles before the instruction, `None` when the log has no `CYC:` column
+    pub cycles: Option<usize>,
+}
+
+impl TraceEntry {
+    /// Parse a trace line, the disassembly and columns like the PPU of nestest are ignored
+    /// ## Arguments
+    /// * `line` - Line number of the entry [`usize`]
+    /// * `text` - The line [`str`]
+    /// ## Returns
+    /// The entry, or `None` for lines which are not a instruction
+    /// ## Example
+    /// ```
+    /// use rusty_6502::trace::TraceEntry;
+    /// let entry = TraceEntry::parse(1, "C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7").unwrap();
+    /// assert_eq!((entry.pc, entry.p, entry.sp, entry.cycles), (0xC000, 0x24, 0xFD, Some(7)));
+    /// ```
+    pub fn parse(line: usize, text: &str) -> Option<TraceEntry> {
+        let pc = u16::from_str_radix(text.get(..4)?, 16).ok()?;
+        let field = |name: &str| {
+            text.split_whitespace()
+                .find_map(|word| word.strip_prefix(name))
+        };
+        let register = |name: &str| u8::from_str_radix(field(name)?, 16).ok();
+        Some(TraceEntry {
+            line,
+            text: text.to_string(),
+            pc,
+            a: register("A:")?,
+            x: register("X:")?,
+            y: register("Y:")?,
+            p: register("P:")?,
+            sp: register("SP:")?,
+            cycles: field("CYC:").and_then(|cycles| cycles.parse().ok()),
+        })
+    }
+}
+
+/// Parse every instruction of a trace log, other lines are skipped
+/// ## Arguments
+/// * `text` - Contents of the log [`str`]
+pub fn parse_trace(text: &str) -> Vec<TraceEntry> {
+    text.lines()
+        .enumerate()
+        .filter_map(|(index, line)| TraceEntry::parse(index + 1, line))
+        .collect()
+}
+
+/// How traces are compared by [`compare_traces`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompareOptions {
+    /// Entries shown before the divergence, 3 by default
+    pub context: usize,
+    /// Whether cycles are compared when both logs have them, true by default
+    pub cycles: bool,
+    /// Bits of the status register which are compared, all by default
+    pub status_mask: u8,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        CompareOptions {
+            context: 3,
+            cycles: true,
+            status_mask: 0xFF,
+        }
+    }
+}
+
+/// First entry where two traces differ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the entry in both traces, starting from 0
+    pub index: usize,
+    /// Entry of the emulator, `None` when its trace ended first [`TraceEntry`]
+    pub actual: Option<TraceEntry>,
+    /// Entry of the reference, `None` when its trace ended first [`TraceEntry`]
+    pub expected: Option<TraceEntry>,
+    /// Names of the differing columns, like `PC`, `A`, `P` or `CYC`
+    pub fields: Vec<&'static str>,
+    /// Matching entries before the divergence, oldest first [`TraceEntry`]
+    pub context: Vec<TraceEntry>,
+}
+
+impl std::fmt::Display for Divergence {
+    /// Render the context, then the reference and emulator lines of the divergence
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.fields.is_empty() {
+            true => writeln!(f, "Traces diverge at instruction {}", self.index + 1)?,
+            false => writeln!(
+                f,
+                "Traces diverge at instruction {} in {}",
+                self.index + 1,
+                self.fields.join(", ")
+            )?,
+        }
+        for entry in &self.context {
+            writeln!(f, "  {}", entry.text)?;
+        }
+        let line = |entry: &Option<TraceEntry>| {
+            entry
+                .as_ref()
+                .map_or("<end of trace>".to_string(), |entry| {
+                    format!("{} (line {})", entry.text, entry.line)
+                })
+        };
+        writeln!(f, "- {}", line(&self.expected))?;
+        writeln!(f, "+ {}", line(&self.actual))
+    }
+}
+
+/// Differing columns of two entries
+fn differences(
+    actual: &TraceEntry,
+    expected: &TraceEntry,
+    options: &CompareOptions,
+) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+    let mut check = |name, differs: bool| {
+        if differs {
+            fields.push(name);
+        }
+    };
+    check("PC", actual.pc != expected.pc);
+    check("A", actual.a != expected.a);
+    check("X", actual.x != expected.x);
+    check("Y", actual.y != expected.y);
+    check("P", (actual.p ^ expected.p) & options.status_mask != 0);
+    check("SP", actual.sp != expected.sp);
+    if let (true, Some(actual), Some(expected)) = (options.cycles, actual.cycles, expected.cycles) {
+        check("CYC", actual != expected);
+    }
+    fields
+}
+
+/// Find the first instruction where a trace of the emulator differs from a reference
+///
+/// The program counter, registers, status register and cycles are compared, the
+/// disassembly is not. A trace which ends before the other diverges where it ends.
+/// ## Arguments
+/// * `actual` - Trace of the emulator [`str`]
+/// * `expected` - Reference trace [`str`]
+/// * `options` - What is compared [`CompareOptions`]
+/// ## Returns
+/// The first divergence, or `None` when the traces match
+/// ## Example
+/// ```
+/// use rusty_6502::trace::{self, CompareOptions};
+/// let expected = "0600  A9 01     LDA #$01  A:00 X:00 Y:00 P:20 SP:FF CYC:0\n\
+///                 0602  AA        TAX       A:01 X:00 Y:00 P:20 SP:FF CYC:2\n";
+/// let actual = expected.replace("P:20 SP:FF CYC:2", "P:22 SP:FF CYC:2");
+/// let divergence = trace::compare_traces(&actual, expected, &CompareOptions::default()).unwrap();
+/// assert_eq!((divergence.index, divergence.fields.clone()), (1, vec!["P"]));
+/// ```
+pub fn compare_traces(
+    actual: &str,
+    expected: &str,
+    options: &CompareOptions,
+) -> Option<Divergence> {
+    let actual = parse_trace(actual);
+    let expected = parse_trace(expected);
+    let index = (0..actual.len().max(expected.len())).find(|index| {
+        match (actual.get(*index), expected.get(*index)) {
+            (Some(actual), Some(expected)) => !differences(actual, expected, options).is_empty(),
+            _ => true,
+        }
+    })?;
+    let fields = match (actual.get(index), expected.get(index)) {
+        (Some(actual), Some(expected)) => differences(actual, expected, options),
+        _ => Vec::new(),
+    };
+    Some(Divergence {
+        index,
+        actual: actual.get(index).cloned(),
+        expected: expected.get(index).cloned(),
+        fields,
+        context: actual[index.saturating_sub(options.context)..index].to_vec(),
+    })
+}
@@ -0,0 +1,129 @@
+mod assembler_tests {
+    use rusty_6502::{asm::Program, cpu::CPU, mem::MEM};
+
+    #[test]
+    fn assemble_addressing_modes() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                NOP
+                ASL
+                ROL A
+                LDA #$01
+                LDA $10
+                LDA $10,X
+                LDX $10,Y
+                LDA $1234
+                LDA $1234,X
+                LDA $1234,Y
+                JMP ($1234)
+                LDA ($10,X)
+                LDA ($10),Y
+                ",
+            )
+            .unwrap();
+        assert_eq!(
+            program.lines,
+            vec![
+                0xEA, 0x0A, 0x2A, 0xA9, 0x01, 0xA5, 0x10, 0xB5, 0x10, 0xB6, 0x10, 0xAD, 0x34, 0x12,
+                0xBD, 0x34, 0x12, 0xB9, 0x34, 0x12, 0x6C, 0x34, 0x12, 0xA1, 0x10, 0xB1, 0x10,
+            ]
+        );
+    }
+
+    #[test]
+    fn assemble_literals() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble("lda #%00001111\nldx #255\nldy #$7f\nsta 512")
+            .unwrap();
+        assert_eq!(
+            program.lines,
+            vec![0xA9, 0x0F, 0xA2, 0xFF, 0xA0, 0x7F, 0x8D, 0x00, 0x02]
+        );
+    }
+
+    #[test]
+    fn assemble_labels() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                start:  LDX #$03    ; Count down from three
+                loop:   DEX
+                        BNE loop
+                        JMP end     ; Forward reference
+                end:    JSR start
+                ",
+            )
+            .unwrap();
+        assert_eq!(
+            program.lines,
+            vec![0xA2, 0x03, 0xCA, 0xD0, 0xFD, 0x4C, 0x08, 0x06, 0x20, 0x00, 0x06]
+        );
+        assert_eq!(program.labels["start"], 0x600);
+        assert_eq!(program.labels["loop"], 0x602);
+        assert_eq!(program.labels["end"], 0x608);
+    }
+
+    #[test]
+    fn assemble_forward_reference_uses_absolute() {
+        let mut program = Program::new(0x600);
+        program.assemble("LDA data\ndata: BRK").unwrap();
+        assert_eq!(program.lines, vec![0xAD, 0x03, 0x06, 0x00]);
+    }
+
+    #[test]
+    fn assemble_trailing_newline() {
+        let mut program = Program::new(600);
+        program.get_from_str("A2 01\n").unwrap();
+        assert_eq!(program.lines, vec![0xA2, 0x01]);
+        program.assemble("INX\n").unwrap();
+        assert_eq!(program.lines, vec![0xA2, 0x01, 0xE8]);
+    }
+
+    #[test]
+    fn assemble_errors() {
+        let errors = Program::new(0x600).assemble("NOP\nFOO #$01").unwrap_err();
+        assert_eq!(errors[0].span.line, 2);
+        assert_eq!(errors[0].message, "Unknown mnemonic 'FOO'");
+
+        let errors = Program::new(0x600).assemble("STX $1234,X").unwrap_err();
+        assert_eq!(errors[0].message, "Illegal addressing mode for STX");
+
+        let errors = Program::new(0x600).assemble("JMP nowhere").unwrap_err();
+        assert_eq!(errors[0].message, "Undefined symbol 'nowhere'");
+
+        let errors = Program::new(0x600).assemble("LDA #$100").unwrap_err();
+        assert_eq!(errors[0].message, "Value 256 does not fit in a byte");
+
+        let errors = Program::new(0x600)
+            .assemble("loop: NOP\nloop: NOP")
+            .unwrap_err();
+        assert_eq!(errors[0].span.line, 2);
+    }
+
+    #[test]
+    fn assemble_and_execute() {
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(|_| {});
+        cpu.reset(600, &mut mem);
+        Program::new(600)
+            .assemble(
+                "
+                LDX #$01
+                LDY #$03
+                STY $0202
+                DEC $0201,X
+                ",
+            )
+            .unwrap()
+            .fill_ram(&mut mem);
+
+        cpu.execute_continuous(&mut mem);
+        assert_eq!(cpu.X, 0x01);
+        assert_eq!(cpu.Y, 0x03);
+        assert_eq!(mem[0x0202], 0x02);
+    }
+}
@@ -0,0 +1,79 @@
+mod diagnostics_tests {
+    use rusty_6502::asm::{ErrorKind, Program, Span};
+
+    #[test]
+    fn error_kinds() {
+        let kind = |source: &str| Program::new(0x600).assemble(source).unwrap_err()[0].kind;
+        assert_eq!(kind("JMP nowhere"), ErrorKind::UndefinedSymbol);
+        assert_eq!(kind("STX $1234,X"), ErrorKind::IllegalAddressingMode);
+        assert_eq!(kind("LDA ($10)"), ErrorKind::IllegalAddressingMode);
+        assert_eq!(kind("LDA #$100"), ErrorKind::ValueTooLarge);
+        assert_eq!(kind("start: .fill 200\nBNE start"), ErrorKind::BranchOutOfRange);
+        assert_eq!(kind("FOO"), ErrorKind::UnknownMnemonic);
+        assert_eq!(kind("LDA #(1"), ErrorKind::Syntax);
+        assert_eq!(kind("A = B\nB = A"), ErrorKind::CircularDefinition);
+        assert_eq!(kind("x:\nx:"), ErrorKind::DuplicateSymbol);
+    }
+
+    #[test]
+    fn spans_point_at_the_offending_text() {
+        let errors = Program::new(0x600)
+            .assemble("loop: NOP\n  loop2: LDA  missing+1 ; comment\n\tBNE far")
+            .unwrap_err();
+        assert_eq!(
+            errors[0].span,
+            Span {
+                file: None,
+                line: 2,
+                column: 15,
+                length: 7,
+            }
+        );
+        assert_eq!(errors[0].source_line, "  loop2: LDA  missing+1 ; comment");
+        assert_eq!(errors[1].span.line, 3);
+        assert_eq!(errors[1].span.column, 6);
+        assert_eq!(errors[1].span.length, 3);
+    }
+
+    #[test]
+    fn every_error_is_reported() {
+        let errors = Program::new(0x600)
+            .assemble("LDA #$100\nFOO\nNOP\nJMP nowhere\n.if MISSING\nNOP\n.endif")
+            .unwrap_err();
+        let lines: Vec<usize> = errors.iter().map(|error| error.span.line).collect();
+        assert_eq!(lines, vec![1, 2, 4, 5]);
+    }
+
+    #[test]
+    fn hex_bytes() {
+        let mut program = Program::new(0x600);
+        let errors = program.get_from_str("A9 01\nLDA 100").unwrap_err();
+        let spans: Vec<(usize, usize, usize)> = errors
+            .iter()
+            .map(|error| (error.span.line, error.span.column, error.span.length))
+            .collect();
+        assert_eq!(spans, vec![(2, 1, 3), (2, 5, 3)]);
+        assert_eq!(errors[0].kind, ErrorKind::Syntax);
+        assert_eq!(errors[0].message, "Invalid hex byte 'LDA'");
+        assert!(program.lines.is_empty());
+    }
+
+    #[test]
+    fn rendering() {
+        let errors = Program::new(0x600)
+            .assemble("NOP\n  LDA #value")
+            .unwrap_err();
+        assert_eq!(
+            errors.to_string(),
+            "error: Undefined symbol 'value'\n --> <source>:2:8\n  |\n2 |   LDA #value\n  |        ^^^^^"
+        );
+
+        let errors = Program::new(0x600)
+            .assemble_file("tests/asm/fixtures/missing.s")
+            .unwrap_err();
+        assert_eq!(errors[0].kind, ErrorKind::File);
+        assert!(errors
+            .to_string()
+            .ends_with(" --> tests/asm/fixtures/missing.s"));
+    }
+}
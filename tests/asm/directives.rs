@@ -0,0 +1,151 @@
+mod directives_tests {
+    use rusty_6502::{
+        asm::{Program, Segment},
+        mem::MEM,
+    };
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/asm/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    #[test]
+    fn byte_word_text() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                .byte 1, $02, %11, 'A'
+                .word $1234, table
+                table: .text \"Hi; there\\n\"
+                .byte \"ok\", 0
+                ",
+            )
+            .unwrap();
+        assert_eq!(
+            program.lines,
+            vec![
+                0x01, 0x02, 0x03, 0x41, 0x34, 0x12, 0x08, 0x06, b'H', b'i', b';', b' ', b't', b'h',
+                b'e', b'r', b'e', b'\n', b'o', b'k', 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn fill_and_align() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                NOP
+                .fill 3, $FF
+                .align 8
+                aligned: .fill 2
+                .align 4, $EA
+                ",
+            )
+            .unwrap();
+        assert_eq!(
+            program.lines,
+            vec![0xEA, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xEA, 0xEA]
+        );
+        assert_eq!(program.labels["aligned"], 0x608);
+    }
+
+    #[test]
+    fn org_segments() {
+        let mut mem = MEM::new();
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                        JMP main
+                .org $0800
+                main:   LDA #$01
+                        BRK
+                .org $FFFC
+                        .word main
+                ",
+            )
+            .unwrap()
+            .fill_ram(&mut mem);
+        assert_eq!(program.lines, vec![0x4C, 0x00, 0x08]);
+        assert_eq!(
+            program.segments,
+            vec![
+                Segment {
+                    origin: 0x0800,
+                    data: vec![0xA9, 0x01, 0x00]
+                },
+                Segment {
+                    origin: 0xFFFC,
+                    data: vec![0x00, 0x08]
+                }
+            ]
+        );
+        assert_eq!(mem.hex_dump(0x600, 0x603), "4C 00 08 ");
+        assert_eq!(mem.hex_dump(0x800, 0x803), "A9 01 00 ");
+        assert_eq!(mem.hex_dump(0xFFFC, 0xFFFE), "00 08 ");
+    }
+
+    #[test]
+    fn org_overlap() {
+        let errors = Program::new(0x600)
+            .assemble(".org $0700\n.fill 4\n.org $0702\nNOP")
+            .unwrap_err();
+        assert_eq!(errors[0].message, "Segment at $0702 overlaps segment at $0700");
+    }
+
+    #[test]
+    fn org_at_origin() {
+        let mut mem = MEM::new();
+        let mut program = Program::new(0x600);
+        program
+            .assemble(".org $0600\nNOP")
+            .unwrap()
+            .fill_ram(&mut mem);
+        assert_eq!(mem.hex_dump(0x600, 0x601), "EA ");
+    }
+
+    #[test]
+    fn include_and_incbin() {
+        let mut program = Program::new(0);
+        program.assemble_file(fixture("main.s")).unwrap();
+        assert_eq!(
+            program.segments,
+            vec![Segment {
+                origin: 0x0600,
+                data: vec![0x20, 0x04, 0x06, 0x00, 0xA2, 0x00, 0x60, 0x20, 0x30]
+            }]
+        );
+        assert_eq!(program.labels["table"], 0x0607);
+    }
+
+    #[test]
+    fn include_errors() {
+        let errors = Program::new(0)
+            .assemble_file(fixture("recursive.s"))
+            .unwrap_err();
+        assert_eq!(errors[0].span.line, 2);
+        assert!(errors[0].message.starts_with("Recursive include"));
+
+        let errors = Program::new(0)
+            .assemble(".include \"missing.s\"")
+            .unwrap_err();
+        assert!(errors[0].message.starts_with("Can not read 'missing.s'"));
+    }
+
+    #[test]
+    fn directive_errors() {
+        let errors = Program::new(0).assemble(".org later\nlater:").unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "Symbol 'later' must be defined before it is used here"
+        );
+
+        let errors = Program::new(0).assemble(".text 1").unwrap_err();
+        assert_eq!(errors[0].message, "Expected a string, found '1'");
+
+        let errors = Program::new(0).assemble(".foo").unwrap_err();
+        assert_eq!(errors[0].message, "Unknown directive '.foo'");
+    }
+}
@@ -0,0 +1,192 @@
+mod disassembler_tests {
+    use rusty_6502::{
+        asm::{self, Program, Symbol},
+        mem::MEM,
+    };
+
+    #[test]
+    fn addressing_modes() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                ASL A
+                LDA #$01
+                LDA $10
+                LDA $10,X
+                LDX $10,Y
+                STA $0200,X
+                LDA $1234,Y
+                JMP ($1234)
+                LDA ($10,X)
+                LDA ($10),Y
+                loop: BNE loop
+                RTS
+                ",
+            )
+            .unwrap();
+        let lines = asm::disassemble(&program.lines, 0x600, &[]);
+        let texts: Vec<String> = lines.iter().map(|line| line.text()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "ASL A",
+                "LDA #$01",
+                "LDA $10",
+                "LDA $10,X",
+                "LDX $10,Y",
+                "STA $0200,X",
+                "LDA $1234,Y",
+                "JMP ($1234)",
+                "LDA ($10,X)",
+                "LDA ($10),Y",
+                "BNE $0616",
+                "RTS",
+            ]
+        );
+        assert_eq!(lines[10].target, Some(0x616));
+        assert_eq!(lines[10].bytes, vec![0xD0, 0xFE]);
+    }
+
+    #[test]
+    fn undecodable_bytes() {
+        let lines = asm::disassemble(&[0x02, 0xEA, 0xAD, 0x00], 0x600, &[]);
+        assert_eq!(
+            lines.to_string(),
+            "0600  02        .byte $02\n\
+             0601  EA        NOP\n\
+             0602  AD        .byte $AD\n\
+             0603  00        BRK\n"
+        );
+    }
+
+    #[test]
+    fn symbol_substitution() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble("PTR = $10\nstart: LDA (PTR),Y\nloop: JSR start\nBNE loop")
+            .unwrap();
+        let symbols = [
+            Symbol::label("start", 0x600),
+            Symbol::label("loop", 0x602),
+            Symbol::label("ptr", 0x10),
+        ];
+        assert_eq!(
+            asm::disassemble(&program.lines, 0x600, &symbols).to_string(),
+            "start:\n\
+             0600  B1 10     LDA (ptr),Y\n\
+             loop:\n\
+             0602  20 00 06  JSR start\n\
+             0605  D0 FB     BNE loop\n"
+        );
+    }
+
+    #[test]
+    fn memory_range() {
+        let mut mem = MEM::new();
+        Program::new(0x600)
+            .assemble("LDX #$01\nSTX $00\nLDA $00")
+            .unwrap()
+            .fill_ram(&mut mem);
+        let lines = asm::disassemble_mem(&mem, 0x602, 0x606, &[Symbol::label("zero", 0x00)]);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].address, 0x602);
+        assert_eq!(lines[0].text(), "STX zero");
+        assert_eq!(lines[1].text(), "LDA zero");
+    }
+
+    const ROM: &str = "
+        SCREEN = $D020
+        .org $F000
+        reset:  LDX #$00
+        copy:   LDA table,X
+                STA SCREEN,X
+                INX
+                CPX #4
+                BNE copy
+                JSR wait
+                .byte $AD, $10, $00 ; LDA $0010 in absolute form
+                LDA ($10),Y
+                JMP (vector)
+        table:  .byte $EA, $EA, $60, $00
+        vector: .word reset
+        wait:   LDY #$02
+        skip:   .byte $2C       ; BIT $xxxx hiding the next instruction
+        inner:  LDY #$01
+                DEY
+                BNE skip
+                BEQ inner
+        nmi:    RTI
+        .org $FFFA
+        .word nmi, reset, nmi
+        ";
+
+    fn rom() -> MEM {
+        let mut mem = MEM::new();
+        Program::new(0).assemble(ROM).unwrap().fill_ram(&mut mem);
+        mem
+    }
+
+    #[test]
+    fn flow_separates_code_from_data() {
+        let mem = rom();
+        let flow = asm::disassemble_flow(&mem, 0xF000, 0x10000, &[], &[]);
+        let line = |address: u16| {
+            flow.lines
+                .iter()
+                .find(|line| line.address == address)
+                .unwrap()
+        };
+        assert_eq!(line(0xF000).label.as_deref(), Some("LF000"));
+        assert_eq!(line(0xF002).text(), "LDA LF018,X");
+        //The table is made of valid opcodes but it is never reached
+        assert_eq!(line(0xF018).text(), ".byte $EA, $EA, $60, $00");
+        assert_eq!(line(0xF018).mnemonic, None);
+        assert_eq!(line(0xF020).text(), "BIT $01A0");
+        assert_eq!(line(0xF026).text(), "BEQ LF020+1");
+        assert_eq!(line(0xFFFA).text(), ".byte $28, $F0, $00, $F0, $28, $F0");
+    }
+
+    #[test]
+    fn flow_output_assembles_to_the_same_bytes() {
+        let mem = rom();
+        let symbols = [
+            Symbol::label("SCREEN", 0xD020),
+            Symbol::label("reset", 0xF000),
+        ];
+        let flow = asm::disassemble_flow(&mem, 0xF000, 0x10000, &[], &symbols);
+        let source = flow.to_string();
+        assert!(source.starts_with("SCREEN = $D020\n\n.org $F000\nreset:\n"));
+        assert!(source.contains("    STA SCREEN,X\n"));
+        assert!(source.contains("    .byte $AD, $10, $00 ; LDA $0010\n"));
+        assert!(source.contains("    .fill 4049, $00\n"));
+
+        let mut copy = MEM::new();
+        Program::new(0)
+            .assemble(&source)
+            .unwrap()
+            .fill_ram(&mut copy);
+        assert_eq!(copy.data[0xF000..], mem.data[0xF000..]);
+    }
+
+    #[test]
+    fn flow_wrapping_branches_assemble_again() {
+        let mut mem = MEM::new();
+        mem.data[0x0000..0x0003].copy_from_slice(&[0xF0, 0xFC, 0x60]);
+        mem.data[0xFFFC..].copy_from_slice(&[0xD0, 0x02, 0x60, 0x00]);
+        for (start, end, entry, branch) in [
+            (0x0000, 0x0003, 0x0000, "BEQ *-2"),
+            (0xFFFC, 0x10000, 0xFFFC, "BNE *+4"),
+        ] {
+            let flow = asm::disassemble_flow(&mem, start, end, &[entry], &[]);
+            assert_eq!(flow.lines[0].text(), branch);
+
+            let mut copy = MEM::new();
+            Program::new(start)
+                .assemble(&flow.to_string())
+                .unwrap()
+                .fill_ram(&mut copy);
+            assert_eq!(copy.data[start..end], mem.data[start..end]);
+        }
+    }
+}
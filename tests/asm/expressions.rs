@@ -0,0 +1,132 @@
+mod expressions_tests {
+    use rusty_6502::asm::Program;
+
+    #[test]
+    fn arithmetic_and_precedence() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                .byte 1 + 2 * 3, (1 + 2) * 3, 10 / 3, 10 % 3, 20 - 4 - 6
+                .byte 1 << 4 | 1, $F0 & $3C ^ $FF, ~0 & $FF, -1, 256 >> 4
+                ",
+            )
+            .unwrap();
+        assert_eq!(
+            program.lines,
+            vec![7, 9, 3, 1, 10, 0x11, 0xCF, 0xFF, 0xFF, 0x10]
+        );
+    }
+
+    #[test]
+    fn low_high_bytes_and_pc() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                LDA #<table
+                LDX #>table
+                JMP *+3
+                table: .word *, table+2
+                .byte <(table+1), >$1234
+                ",
+            )
+            .unwrap();
+        assert_eq!(
+            program.lines,
+            vec![0xA9, 0x07, 0xA2, 0x06, 0x4C, 0x07, 0x06, 0x07, 0x06, 0x09, 0x06, 0x08, 0x12]
+        );
+    }
+
+    #[test]
+    fn constants() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                SCREEN = $0200
+                WIDTH  = 32
+                LAST   = SCREEN + WIDTH * ROWS - 1 ; Forward reference to ROWS
+                        LDA #WIDTH
+                        STA SCREEN+1
+                        STA LAST
+                        STA ZP,X
+                ROWS   = 4
+                ZP     = $10
+                END    = *
+                ",
+            )
+            .unwrap();
+        assert_eq!(
+            program.lines,
+            vec![0xA9, 0x20, 0x8D, 0x01, 0x02, 0x8D, 0x7F, 0x02, 0x9D, 0x10, 0x00]
+        );
+        assert_eq!(program.constants["LAST"], 0x027F);
+        assert_eq!(program.constants["END"], 0x060B);
+    }
+
+    #[test]
+    fn constants_before_use_select_zero_page() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble("PTR = $FB\nLDA (PTR),Y\nSTA PTR+1")
+            .unwrap();
+        assert_eq!(program.lines, vec![0xB1, 0xFB, 0x85, 0xFC]);
+    }
+
+    #[test]
+    fn constants_across_assemble_calls() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble("COLOR = 7")
+            .unwrap()
+            .assemble("LDA #COLOR")
+            .unwrap();
+        assert_eq!(program.lines, vec![0xA9, 0x07]);
+    }
+
+    #[test]
+    fn parenthesized_operands() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble("ZP = $20\nJMP (vector)\nLDA (2+3)*2\nLDA (ZP+1),Y\nvector: .word 0")
+            .unwrap();
+        assert_eq!(
+            program.lines,
+            vec![0x6C, 0x07, 0x06, 0xA5, 0x0A, 0xB1, 0x21, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn symbol_errors() {
+        let errors = Program::new(0x600)
+            .assemble("A1 = B1 + 1\nB1 = A1\nLDA #A1")
+            .unwrap_err();
+        assert_eq!(errors[0].span.line, 1);
+        assert_eq!(errors[0].message, "Circular definition of symbol 'A1'");
+        assert_eq!(errors[2].span.line, 3);
+        assert_eq!(errors[2].message, "Circular definition of symbol 'A1'");
+
+        let errors = Program::new(0x600)
+            .assemble("UNUSED = MISSING * 2")
+            .unwrap_err();
+        assert_eq!(errors[0].message, "Undefined symbol 'MISSING'");
+
+        let errors = Program::new(0x600).assemble("LDA #1/0").unwrap_err();
+        assert_eq!(errors[0].message, "Division by zero");
+
+        let errors = Program::new(0x600).assemble("LDA #1 << 64").unwrap_err();
+        assert_eq!(errors[0].message, "Shift count 64 is out of range");
+
+        let errors = Program::new(0x600).assemble("LDA #2 >> -1").unwrap_err();
+        assert_eq!(errors[0].message, "Shift count -1 is out of range");
+
+        let errors = Program::new(0x600)
+            .assemble("VALUE = 1\nVALUE = 2")
+            .unwrap_err();
+        assert_eq!(errors[0].message, "Symbol 'VALUE' is already defined");
+
+        let errors = Program::new(0x600).assemble("LDA #(1+2").unwrap_err();
+        assert_eq!(errors[0].message, "Missing ')' in expression '(1+2'");
+    }
+}
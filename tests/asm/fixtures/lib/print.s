@@ -0,0 +1,4 @@
+; Included relative to main.s, data is relative to this file
+print:  LDX #$00
+        RTS
+table:  .incbin "table.bin", 1, 2
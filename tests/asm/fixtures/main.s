@@ -0,0 +1,5 @@
+; Entry point of the include test
+        .org $0600
+start:  JSR print
+        BRK
+        .include "lib/print.s"
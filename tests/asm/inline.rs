@@ -0,0 +1,73 @@
+mod inline_tests {
+    use rusty_6502::{asm::inline_source, asm6502, cpu::CPU, mem::MEM};
+
+    #[test]
+    fn inline_program() {
+        let bytes = asm6502! { LDX #$01; STX $00; LDA $00 };
+        assert_eq!(bytes, [0xA2, 0x01, 0x86, 0x00, 0xA5, 0x00]);
+
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(|_| {});
+        cpu.reset(600, &mut mem);
+        mem.load(600, &bytes);
+        cpu.execute_continuous(&mut mem);
+        assert_eq!(cpu.A, 0x01);
+        assert_eq!(cpu.X, 0x01);
+        assert_eq!(mem[0], 0x01);
+    }
+
+    #[test]
+    fn load_out_of_memory() {
+        let mut mem = MEM::new();
+        assert!(mem.try_load(0xFFFE, &[0x01, 0x02]));
+        assert_eq!((mem[0xFFFE], mem[0xFFFF]), (0x01, 0x02));
+        assert!(!mem.try_load(0xFFFF, &[0x03, 0x04]));
+        assert!(!mem.try_load(usize::MAX, &[0x03]));
+        assert_eq!(mem[0xFFFF], 0x02);
+    }
+
+    #[test]
+    #[should_panic]
+    fn load_panics_out_of_memory() {
+        MEM::new().load(0xFFFF, &[0x03, 0x04]);
+    }
+
+    #[test]
+    fn inline_syntax() {
+        let bytes = asm6502! {
+            origin = 0x1000;
+            PTR = $10;
+            start:  LDA ($10),Y;
+                    LDA (PTR,X);
+                    ASL A;
+            @loop:  DEX;
+                    BNE @loop;
+                    LDA #<start;
+                    LDY #>start;
+                    JMP start;
+            .byte "a;b", 'c'
+        };
+        assert_eq!(
+            bytes,
+            [
+                0xB1, 0x10, 0xA1, 0x10, 0x0A, 0xCA, 0xD0, 0xFD, 0xA9, 0x00, 0xA0, 0x10, 0x4C, 0x00,
+                0x10, b'a', b';', b'b', b'c'
+            ]
+        );
+        assert_eq!(asm6502!("LDA #$0E ; Not a Rust token"), [0xA9, 0x0E]);
+    }
+
+    #[test]
+    fn inline_source_lines() {
+        assert_eq!(
+            inline_source("loop: LDA($10),Y; BNE\nloop; .byte \"a;  b\""),
+            "loop: LDA ($10),Y\nBNE loop\n.byte \"a;  b\"\n"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown mnemonic 'FOO'")]
+    fn inline_errors() {
+        asm6502! { LDA #1; FOO $10 };
+    }
+}
@@ -0,0 +1,70 @@
+mod listing_tests {
+    use rusty_6502::asm::Program;
+
+    #[test]
+    fn listing_lines() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble("start: LDX #$03 ; Count\nloop: DEX\n  BNE loop\n.org $0700\n.word start")
+            .unwrap();
+        let listing = &program.listing;
+        assert_eq!(listing.len(), 5);
+        assert_eq!(listing[0].address, 0x600);
+        assert_eq!(listing[0].bytes, vec![0xA2, 0x03]);
+        assert_eq!(listing[0].cycles, Some(2));
+        assert_eq!(listing[0].line, 1);
+        assert_eq!(listing[2].branch, Some(0x602));
+        assert!(!listing[2].crosses_page());
+        assert_eq!(listing[3].address, 0x700);
+        assert_eq!(listing[4].bytes, vec![0x00, 0x06]);
+        assert_eq!(listing[4].cycles, None);
+        assert_eq!(
+            program.listing.to_string(),
+            "0600  A2 03     2         start: LDX #$03 ; Count\n\
+             0602  CA        2         loop: DEX\n\
+             0603  D0 FD     2/3         BNE loop\n\
+             0700                      .org $0700\n\
+             0700  00 06               .word start\n"
+        );
+    }
+
+    #[test]
+    fn page_crossing_branch() {
+        let mut program = Program::new(0x6FC);
+        program.assemble("loop: NOP\nNOP\nBNE loop").unwrap();
+        assert!(program.listing[2].crosses_page());
+        assert!(program.listing[2].to_string().contains("2/4 page"));
+    }
+
+    #[test]
+    fn long_data_and_expansions() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(".fill 14, $EA\n.repeat 2, i\n.byte i\n.endrepeat")
+            .unwrap();
+        assert_eq!(
+            program.listing[0].to_string(),
+            "0600  EA EA EA            .fill 14, $EA\n\
+             0603  EA EA EA\n\
+             0606  EA EA EA\n\
+             0609  EA EA EA\n\
+             060C  ... 2 more bytes"
+        );
+        let sources: Vec<&str> = program
+            .listing
+            .iter()
+            .map(|line| line.source.as_str())
+            .collect();
+        assert_eq!(
+            sources,
+            vec![
+                ".fill 14, $EA",
+                ".repeat 2, i",
+                ".byte i",
+                ".endrepeat",
+                ".byte 0",
+                ".byte 1"
+            ]
+        );
+    }
+}
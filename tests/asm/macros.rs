@@ -0,0 +1,170 @@
+mod macros_tests {
+    use rusty_6502::asm::Program;
+
+    #[test]
+    fn macro_with_parameters() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                .macro store value, address
+                    LDA #value
+                    STA address
+                .endmacro
+                store $01, $10
+                store <label, $0200
+                label: RTS
+                ",
+            )
+            .unwrap();
+        assert_eq!(
+            program.lines,
+            vec![0xA9, 0x01, 0x85, 0x10, 0xA9, 0x09, 0x8D, 0x00, 0x02, 0x60]
+        );
+    }
+
+    #[test]
+    fn local_labels_are_unique_per_expansion() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                .macro wait count
+                    LDX #count
+                @loop: DEX
+                    BNE @loop
+                .endmacro
+                wait 2
+                wait 3
+                ",
+            )
+            .unwrap();
+        assert_eq!(
+            program.lines,
+            vec![0xA2, 0x02, 0xCA, 0xD0, 0xFD, 0xA2, 0x03, 0xCA, 0xD0, 0xFD]
+        );
+    }
+
+    #[test]
+    fn macros_can_invoke_macros() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                .macro inc16 address
+                    INC address
+                    BNE @done
+                    INC address+1
+                @done:
+                .endmacro
+                .macro inc16_twice address
+                    inc16 address
+                    inc16 address
+                .endmacro
+                inc16_twice $10
+                ",
+            )
+            .unwrap();
+        assert_eq!(
+            program.lines,
+            vec![0xE6, 0x10, 0xD0, 0x02, 0xE6, 0x11, 0xE6, 0x10, 0xD0, 0x02, 0xE6, 0x11]
+        );
+    }
+
+    #[test]
+    fn conditional_assembly() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                DEBUG = 1
+                .if DEBUG
+                    .byte 1
+                    .if DEBUG == 2
+                        .byte 2
+                    .else
+                        .byte 3
+                    .endif
+                .else
+                    .byte 4
+                    .if 1
+                        .unknown directive is not parsed
+                    .else
+                        .byte 5
+                    .endif
+                .endif
+                .if DEBUG > 1 || !DEBUG
+                    .byte 6
+                .endif
+                ",
+            )
+            .unwrap();
+        assert_eq!(program.lines, vec![1, 3]);
+    }
+
+    #[test]
+    fn repeat_blocks() {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                .repeat 4, i
+                    .byte i * 2
+                .endrepeat
+                .repeat 2
+                @here: .word @here
+                .endrepeat
+                ",
+            )
+            .unwrap();
+        assert_eq!(program.lines, vec![0, 2, 4, 6, 0x04, 0x06, 0x06, 0x06]);
+    }
+
+    #[test]
+    fn wrong_argument_count() {
+        let errors = Program::new(0x600)
+            .assemble(".macro pair a, b\n.byte a, b\n.endmacro\npair 1")
+            .unwrap_err();
+        assert_eq!(errors[0].span.line, 4);
+        assert_eq!(errors[0].message, "Macro 'PAIR' expects 2 arguments, found 1");
+    }
+
+    #[test]
+    fn unterminated_blocks() {
+        let errors = Program::new(0x600)
+            .assemble("NOP\n.macro broken\nNOP")
+            .unwrap_err();
+        assert_eq!(errors[0].span.line, 2);
+        assert_eq!(errors[0].message, "Missing .endmacro for .macro");
+
+        let errors = Program::new(0x600).assemble(".if 1\nNOP").unwrap_err();
+        assert_eq!(errors[0].span.line, 1);
+        assert_eq!(errors[0].message, "Missing .endif for .if");
+
+        let errors = Program::new(0x600).assemble(".endif").unwrap_err();
+        assert_eq!(errors[0].message, ".endif without .if");
+    }
+
+    #[test]
+    fn conditions_need_known_symbols() {
+        let errors = Program::new(0x600)
+            .assemble(".if LATER\nNOP\n.endif\nLATER = 1")
+            .unwrap_err();
+        assert_eq!(errors[0].span.line, 1);
+        assert_eq!(
+            errors[0].message,
+            "Symbol 'LATER' must be defined before it is used here"
+        );
+    }
+
+    #[test]
+    fn recursive_macro() {
+        let errors = Program::new(0x600)
+            .assemble(".macro forever\nforever\n.endmacro\nforever")
+            .unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "Includes or macro expansions are nested too deeply"
+        );
+    }
+}
@@ -0,0 +1,291 @@
+mod objects_tests {
+    use rusty_6502::{
+        asm::{
+            self, ErrorKind, Export, Import, LinkError, MemoryConfig, Object, Program, Relocation,
+            RelocationKind, RelocationTarget, SymbolKind,
+        },
+        mem::MEM,
+    };
+
+    const CONFIG: &str = "
+        # Zero page, program RAM and a ROM with the vectors
+        MEMORY {
+            ZP:  start = $0000, size = $0100;
+            RAM: start = $0600, size = $0100;
+            ROM: start = $FFF0, size = $0010, fill = $EA;
+        }
+        SEGMENTS {
+            ZEROPAGE: load = ZP, type = zp, start = $10;
+            CODE:     load = RAM, type = ro;
+            DATA:     load = RAM, type = rw, align = $10;
+            BSS:      load = RAM, type = bss;
+            VECTORS:  load = ROM, start = $FFFC;
+        }
+    ";
+
+    fn main() -> Object {
+        Object::assemble(
+            "main",
+            "
+            .import print
+            .importzp ptr
+            .export start
+            start:  LDA #<message
+                    STA ptr
+                    LDA #>message
+                    STA ptr+1
+                    JSR print
+            @done:  JMP @done
+            .data
+            message: .byte \"HI\", 0
+            .segment \"VECTORS\"
+                    .word start, start
+            ",
+        )
+        .unwrap()
+    }
+
+    fn lib() -> Object {
+        Object::assemble(
+            "lib",
+            "
+            .export print, ptr, WIDTH
+            WIDTH = 40
+            .zeropage
+            ptr:    .word 0
+            .code
+            print:  LDY #0
+            @next:  LDA (ptr),Y
+                    BEQ @end
+                    STA buffer
+                    INY
+                    BNE @next
+            @end:   RTS
+            .bss
+            buffer: .fill 16
+            ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn relocatable_object() {
+        let object = main();
+        assert_eq!(object.name, "main");
+        let names: Vec<&str> = object.segments.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["CODE", "DATA", "VECTORS"]);
+
+        //Imported zero page symbols keep the zero page addressing mode
+        let code = &object.segments[0];
+        assert_eq!(
+            code.data,
+            [0xA9, 0x00, 0x85, 0x00, 0xA9, 0x00, 0x85, 0x01, 0x20, 0x00, 0x00, 0x4C, 0x0B, 0x00]
+        );
+        assert_eq!(
+            code.relocations[0],
+            Relocation {
+                offset: 1,
+                kind: RelocationKind::Low,
+                target: RelocationTarget::Segment("DATA".to_string()),
+                addend: 0,
+            }
+        );
+        assert_eq!(code.relocations[1].kind, RelocationKind::Byte);
+        assert_eq!(code.relocations[3].addend, 1);
+        assert_eq!(
+            code.relocations[4].target,
+            RelocationTarget::Import("print".to_string())
+        );
+        assert_eq!(
+            code.relocations[5].target,
+            RelocationTarget::Segment("CODE".to_string())
+        );
+        assert_eq!(
+            object.exports,
+            [Export {
+                name: "start".to_string(),
+                segment: Some("CODE".to_string()),
+                value: 0,
+            }]
+        );
+        assert_eq!(
+            object.imports,
+            [
+                Import {
+                    name: "print".to_string(),
+                    zero_page: false,
+                },
+                Import {
+                    name: "ptr".to_string(),
+                    zero_page: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn object_errors() {
+        let errors = Object::assemble(
+            "bad",
+            "
+            .org $0600
+            .data
+            value: .byte 1
+            .code
+            LDA #value
+            BNE value
+            .export missing
+            ",
+        )
+        .unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|e| e.message.clone()).collect();
+        assert_eq!(
+            messages,
+            [
+                ".org can not be used in a relocatable object, use .segment",
+                "Address 'value' does not fit in a byte, use < or > for one of its bytes",
+                "Branch target must be in the same segment",
+                "Undefined symbol 'missing'",
+            ]
+        );
+
+        let errors = Program::new(0x600)
+            .assemble(".data\n.export x")
+            .unwrap_err();
+        assert_eq!(errors[0].kind, ErrorKind::Syntax);
+        assert_eq!(
+            errors[0].message,
+            "Segments can only be used in a relocatable object"
+        );
+    }
+
+    #[test]
+    fn link_objects() {
+        let config = MemoryConfig::parse(CONFIG).unwrap();
+        let linked = asm::link(&[main(), lib()], &config).unwrap();
+        let mut mem = MEM::new();
+        linked.fill_ram(&mut mem);
+
+        //CODE of main, CODE of lib, then DATA aligned to $10
+        assert_eq!(
+            mem.hex_dump(0x600, 0x60E),
+            "A9 20 85 10 A9 06 85 11 20 0E 06 4C 0B 06 "
+        );
+        assert_eq!(
+            mem.hex_dump(0x60E, 0x61C),
+            "A0 00 B1 10 F0 06 8D 23 06 C8 D0 F6 60 00 "
+        );
+        assert_eq!(mem.hex_dump(0x620, 0x623), "48 49 00 ");
+        assert_eq!(mem.hex_dump(0xFFF0, 0x10000).len(), 48);
+        assert_eq!(mem.hex_dump(0xFFFA, 0x10000), "EA EA 00 06 00 06 ");
+
+        //BSS is placed but not written
+        let bss = linked
+            .placements
+            .iter()
+            .find(|placement| placement.segment == "BSS")
+            .unwrap();
+        assert_eq!((bss.start, bss.size), (0x623, 16));
+        assert!(linked
+            .segments
+            .iter()
+            .all(
+                |segment| segment.origin as usize + segment.data.len() <= 0x623
+                    || segment.origin >= 0xFFF0
+            ));
+
+        let symbol = |name: &str| linked.symbols.iter().find(|s| s.name == name).unwrap();
+        assert_eq!(symbol("start").value, 0x600);
+        assert_eq!(symbol("print").value, 0x60E);
+        assert_eq!(symbol("ptr").value, 0x10);
+        assert_eq!(symbol("WIDTH").kind, SymbolKind::Constant);
+        assert_eq!(symbol("WIDTH").value, 40);
+
+        let map = linked.map();
+        assert!(map.contains("RAM              $0600  $06FF  $0100  $002E\n"));
+        assert!(map.contains("CODE             lib              $060E  $061A  $000D\n"));
+        assert!(map.contains("print            $060E\n"));
+        assert!(map.contains("WIDTH            40\n"));
+    }
+
+    #[test]
+    fn link_errors() {
+        let config = MemoryConfig::parse(CONFIG).unwrap();
+        assert_eq!(
+            asm::link(&[main()], &config).unwrap_err(),
+            LinkError::Unresolved {
+                object: "main".to_string(),
+                name: "print".to_string(),
+            }
+        );
+        assert_eq!(
+            asm::link(&[main(), lib(), lib()], &config)
+                .unwrap_err()
+                .to_string(),
+            "Symbol 'print' is exported by 'lib' and 'lib'"
+        );
+
+        let big = Object::assemble("big", ".fill $101").unwrap();
+        assert_eq!(
+            asm::link(&[big], &config).unwrap_err().to_string(),
+            "Segment 'CODE' does not fit in memory area 'RAM' by 1 bytes"
+        );
+        let other = Object::assemble("other", ".segment \"OTHER\"\nNOP").unwrap();
+        assert_eq!(
+            asm::link(&[other], &config).unwrap_err(),
+            LinkError::UnknownSegment {
+                object: "other".to_string(),
+                segment: "OTHER".to_string(),
+            }
+        );
+
+        //Constants which are out of the zero page are found when linking
+        let far = Object::assemble("far", ".export ptr\nptr = $1234").unwrap();
+        let user = Object::assemble("user", ".importzp ptr\nLDA ptr").unwrap();
+        assert_eq!(
+            asm::link(&[user, far], &config).unwrap_err(),
+            LinkError::Range {
+                object: "user".to_string(),
+                segment: "CODE".to_string(),
+                offset: 1,
+                value: 0x1234,
+            }
+        );
+    }
+
+    #[test]
+    fn memory_config_errors() {
+        let error = |text: &str| MemoryConfig::parse(text).unwrap_err().to_string();
+        assert_eq!(
+            error("MEMORY {\n RAM: start = $0600;\n}"),
+            "line 2: Memory area 'RAM' has no size"
+        );
+        assert_eq!(
+            error("MEMORY {\n RAM: start = $0600, size = $100\n}"),
+            "line 3: Expected ',' or ';', found '}'"
+        );
+        assert_eq!(
+            error("SEGMENTS {\n CODE: load = RAM, kind = ro;\n}"),
+            "line 2: Unknown attribute 'kind'"
+        );
+        assert_eq!(
+            error("SEGMENTS { CODE: load = RAM; }"),
+            "Segment 'CODE' is loaded to unknown memory area 'RAM'"
+        );
+        assert_eq!(
+            error("MEMORY { RAM: start = $FF00, size = $200; }"),
+            "line 1: Memory area 'RAM' ends outside of the address space"
+        );
+    }
+
+    #[test]
+    fn object_files() {
+        let object = lib();
+        let text = object.write();
+        assert_eq!(Object::read(&text).unwrap(), object);
+        assert!(matches!(
+            Object::read("{\"name\": \"x\"}"),
+            Err(LinkError::InvalidObject(_))
+        ));
+    }
+}
@@ -0,0 +1,108 @@
+mod symbols_tests {
+    use rusty_6502::{
+        asm::{
+            read_symbols, write_symbols, Program, Symbol, SymbolFormat, SymbolKind, SymbolScope,
+        },
+        debugger::{Debugger, MessageType},
+    };
+
+    fn program() -> Program {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                COUNT = 3
+                start:  LDX #COUNT
+                .macro wait
+                @loop:  DEX
+                        BNE @loop
+                .endmacro
+                        wait
+                end:    RTS
+                ",
+            )
+            .unwrap();
+        program
+    }
+
+    #[test]
+    fn symbols_of_a_program() {
+        let program = program();
+        let start = program.symbols.iter().find(|s| s.name == "start").unwrap();
+        assert_eq!(start.value, 0x600);
+        assert_eq!(start.kind, SymbolKind::Label);
+        assert_eq!(start.scope, SymbolScope::Global);
+        assert_eq!(start.size, 2);
+        assert_eq!(start.line, 3);
+
+        let count = program.symbols.iter().find(|s| s.name == "COUNT").unwrap();
+        assert_eq!(count.kind, SymbolKind::Constant);
+        assert_eq!(count.value, 3);
+        assert_eq!(count.size, 0);
+
+        let local = program
+            .symbols
+            .iter()
+            .find(|s| s.name.starts_with("@loop"))
+            .unwrap();
+        assert_eq!(local.scope, SymbolScope::Local);
+        assert_eq!(local.value, 0x602);
+
+        let end = program.symbols.iter().find(|s| s.name == "end").unwrap();
+        assert_eq!(end.size, 1);
+    }
+
+    #[test]
+    fn vice_labels() {
+        let output = write_symbols(&program().symbols, SymbolFormat::Vice);
+        assert!(output.contains("al C:0600 .start\n"));
+        assert!(output.contains("al C:0605 .end\n"));
+        assert!(!output.contains("@loop"));
+        assert!(!output.contains("COUNT"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let symbols = program().symbols;
+        let json = write_symbols(&symbols, SymbolFormat::Json);
+        assert_eq!(read_symbols(&json).unwrap(), symbols);
+
+        let simple = read_symbols(&write_symbols(&symbols, SymbolFormat::Simple)).unwrap();
+        assert_eq!(simple.len(), symbols.len());
+        assert!(simple.contains(&Symbol::label("start", 0x600)));
+
+        let vice = read_symbols(&write_symbols(&symbols, SymbolFormat::Vice)).unwrap();
+        assert_eq!(
+            vice,
+            vec![Symbol::label("start", 0x600), Symbol::label("end", 0x605)]
+        );
+    }
+
+    #[test]
+    fn invalid_symbol_files() {
+        let error = read_symbols("start = $0600\nbroken").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(
+            error.to_string(),
+            "line 2: Expected 'name = value', found 'broken'"
+        );
+
+        let error = read_symbols("al C:XYZ .start").unwrap_err();
+        assert_eq!(error.message, "Invalid address 'XYZ'");
+
+        assert!(read_symbols("[{\"name\": \"start\"}]").is_err());
+    }
+
+    #[test]
+    fn debugger_imports_symbols() {
+        let symbols = program().symbols;
+        let mut debugger = Debugger::new(|_: MessageType| {});
+        let count = debugger
+            .import_symbols(&write_symbols(&symbols, SymbolFormat::Json))
+            .unwrap();
+        assert_eq!(count, symbols.len());
+        assert_eq!(debugger.symbol_at(0x605), Some("end"));
+        assert_eq!(debugger.symbol_at(0x700), None);
+        assert_eq!(debugger.lookup("COUNT"), Some(3));
+    }
+}
@@ -0,0 +1,120 @@
+mod klaus_tests {
+    use std::path::{Path, PathBuf};
+
+    use rusty_6502::{
+        image,
+        mem::MEM,
+        testing::{TrapFailure, TrapTest},
+    };
+
+    /// Binaries are not vendored, see `tests/conformance/README.md`. A missing binary skips
+    /// the test unless `RUSTY6502_KLAUS` is set, then it fails.
+    fn binary(name: &str) -> Option<PathBuf> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/conformance")
+            .join(name);
+        if path.exists() {
+            return Some(path);
+        }
+        assert!(
+            std::env::var_os("RUSTY6502_KLAUS").is_none(),
+            "{} is not present",
+            path.display()
+        );
+        eprintln!("{} is not present, skipped", path.display());
+        None
+    }
+
+    fn run(name: &str, base: u16, test: &TrapTest) {
+        let Some(path) = binary(name) else {
+            return;
+        };
+        let mut mem = MEM::new();
+        image::load_file(&mut mem, &path, base).unwrap();
+        if let Err(failure) = test.run(&mut mem) {
+            panic!("{}: {}", path.display(), failure);
+        }
+    }
+
+    #[test]
+    fn functional_test() {
+        //Default build of 6502_functional_test.a65, loaded at $0000
+        run(
+            "6502_functional_test.bin",
+            0x0000,
+            TrapTest::new(0x0400).success(0x3469).test_number(0x0200),
+        );
+    }
+
+    #[test]
+    fn decimal_test() {
+        //6502_decimal_test.a65 assembled at $0200, ERROR is zero when it passed
+        run(
+            "6502_decimal_test.bin",
+            0x0200,
+            TrapTest::new(0x0200).error(0x000B),
+        );
+    }
+
+    #[test]
+    fn trap_runner() {
+        let mut mem = MEM::new();
+        image::load_raw(&mut mem, &[0xA2, 0x05, 0xE8, 0xCA, 0xE8], 0x0400).unwrap();
+        let outcome = TrapTest::new(0x0400).success(0x0405).run(&mut mem).unwrap();
+        assert_eq!(outcome.state.X, 0x06);
+        assert_eq!(outcome.instructions, 4);
+        assert_eq!(outcome.cycles, 8);
+
+        let failure = TrapTest::new(0x0400)
+            .success(0x3469)
+            .max_instructions(3)
+            .run(&mut mem)
+            .unwrap_err();
+        assert_eq!(
+            failure,
+            TrapFailure::Timeout {
+                pc: 0x0404,
+                instructions: 3,
+            }
+        );
+        assert_eq!(
+            failure.to_string(),
+            "Test did not finish in 3 instructions, PC is $0404"
+        );
+        assert_eq!(
+            TrapFailure::Trapped {
+                pc: 0x09D0,
+                test: Some(0x2A),
+                instructions: 1200,
+            }
+            .to_string(),
+            "Test $2A failed, trapped at $09D0 after 1200 instructions"
+        );
+    }
+
+    #[test]
+    fn trap_runner_catches_panics() {
+        let mut mem = MEM::new();
+        //JMP is not implemented yet, so the CPU panics on it
+        image::load_raw(&mut mem, &[0xE8, 0x4C, 0x00, 0x04], 0x0400).unwrap();
+        mem[0x0200] = 0x07;
+        let failure = TrapTest::new(0x0400)
+            .success(0x3469)
+            .test_number(0x0200)
+            .run(&mut mem)
+            .unwrap_err();
+        assert_eq!(
+            failure,
+            TrapFailure::Panicked {
+                pc: 0x0401,
+                test: Some(0x07),
+                instructions: 1,
+                message: "Wrong addressing mode".to_string(),
+            }
+        );
+        assert_eq!(
+            failure.to_string(),
+            "Test $07 failed, CPU panicked at $0401 after 1 instructions: Wrong addressing mode"
+        );
+    }
+}
@@ -0,0 +1,105 @@
+mod vectors_tests {
+    use std::path::Path;
+
+    use rusty_6502::testing::{self, Vector, VectorError};
+
+    //LDA #$80 and a vector which expects the wrong accumulator and cycles
+    const VECTORS: &str = r#"[
+        {
+            "name": "a9 80",
+            "initial": {"pc": 1536, "s": 253, "a": 0, "x": 0, "y": 0, "p": 38,
+                        "ram": [[1536, 169], [1537, 128]]},
+            "final": {"pc": 1538, "s": 253, "a": 128, "x": 0, "y": 0, "p": 164,
+                      "ram": [[1536, 169], [1537, 128]]},
+            "cycles": [[1536, 169, "read"], [1537, 128, "read"]]
+        },
+        {
+            "name": "a9 01",
+            "initial": {"pc": 1536, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36,
+                        "ram": [[1536, 169], [1537, 1]]},
+            "final": {"pc": 1538, "s": 253, "a": 2, "x": 0, "y": 0, "p": 36,
+                      "ram": [[1536, 169], [1537, 1]]},
+            "cycles": [[1536, 169, "read"], [1537, 1, "read"], [1538, 0, "read"]]
+        }
+    ]"#;
+
+    #[test]
+    fn vector_report() {
+        let vectors = Vector::parse_all(VECTORS).unwrap();
+        assert_eq!(vectors.len(), 2);
+        assert_eq!(vectors[0].initial.state.status_flags.Z, 1);
+        assert!(vectors[1].cycles.iter().all(|cycle| !cycle.write));
+
+        let report = testing::run_vectors(0xA9, &vectors);
+        assert_eq!(report.passed, 1);
+        assert!(!report.passed());
+        assert_eq!(
+            report.to_string(),
+            "$A9: 1 passed, 1 failed\n  a9 01: a: expected $02, found $01, cycles: expected 3, found 2, bus cycle 3: expected read $0602 = $00, found none\n"
+        );
+    }
+
+    #[test]
+    fn vectors_after_a_panic() {
+        //CMP #$80 is not implemented, the vectors after it still run
+        let cmp = VECTORS.replacen("[1536, 169], [1537, 128]", "[1536, 201], [1537, 128]", 1);
+        let cmp = Vector::parse_all(&cmp).unwrap().remove(0);
+        let lda = Vector::parse_all(VECTORS).unwrap().remove(0);
+
+        let report = testing::run_vectors(0xA9, &[cmp.clone(), cmp, lda]);
+        assert_eq!(report.passed, 1);
+        assert_eq!(report.failures.len(), 2);
+        assert!(report
+            .failures
+            .iter()
+            .all(|failure| failure.message == "panicked: Wrong addressing mode"));
+    }
+
+    #[test]
+    fn invalid_vectors() {
+        assert!(matches!(
+            Vector::parse_all("{}"),
+            Err(VectorError::Invalid(_))
+        ));
+        let error = Vector::parse_all(&VECTORS.replace("\"s\": 253", "\"s\": 256")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid test vectors: vector 0: Invalid 's'"
+        );
+    }
+
+    #[test]
+    fn bus_activity() {
+        //STA $10, the vector expects the write at the wrong address
+        let vectors = Vector::parse_all(
+            r#"[{
+                "name": "85 10",
+                "initial": {"pc": 1536, "s": 253, "a": 7, "x": 0, "y": 0, "p": 36,
+                            "ram": [[1536, 133], [1537, 16], [16, 0]]},
+                "final": {"pc": 1538, "s": 253, "a": 7, "x": 0, "y": 0, "p": 36,
+                          "ram": [[1536, 133], [1537, 16], [16, 7]]},
+                "cycles": [[1536, 133, "read"], [1537, 16, "read"], [17, 7, "write"]]
+            }]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            vectors[0].run(),
+            Err("bus cycle 3: expected write $0011 = $07, found write $0010 = $07".to_string())
+        );
+    }
+
+    #[test]
+    #[ignore = "needs the single step vectors in tests/conformance/vectors, see the README there"]
+    fn vector_files() {
+        //Vector files are not vendored, see `tests/conformance/README.md`
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/vectors");
+        assert!(directory.exists(), "{} is not present", directory.display());
+        let reports = testing::run_vector_directory(&directory).unwrap();
+        let failed: String = reports
+            .iter()
+            .filter(|report| !report.passed())
+            .map(|report| report.to_string())
+            .collect();
+        assert!(failed.is_empty(), "{}", failed);
+    }
+}
@@ -0,0 +1,385 @@
+mod dap_tests {
+    use rusty_6502::{
+        asm::{self, SymbolFormat},
+        dap::{CallFrame, DapServer},
+        image::{self, ImageFormat},
+        mem::MEM,
+    };
+    use serde_json::{json, Value};
+    use std::path::PathBuf;
+
+    const SOURCE: &str = "\
+start:  LDX #$00
+        JSR inc
+        JSR inc
+        BRK
+; Increment X and Y
+inc:    INX
+        JSR twice
+        RTS
+twice:  INY
+        RTS
+";
+
+    /// Write the source to a directory of its own
+    fn source(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rusty_6502_dap_{}", name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("program.s");
+        std::fs::write(&path, SOURCE).unwrap();
+        path
+    }
+
+    fn request(server: &mut DapServer, command: &str, arguments: Value) -> Vec<Value> {
+        let request =
+            json!({"seq": 1, "type": "request", "command": command, "arguments": arguments});
+        server
+            .handle(&request.to_string())
+            .iter()
+            .map(|message| serde_json::from_str(message).unwrap())
+            .collect()
+    }
+
+    fn launch(name: &str, stop_on_entry: bool) -> (DapServer, PathBuf) {
+        let path = source(name);
+        let mut server = DapServer::new();
+        request(
+            &mut server,
+            "initialize",
+            json!({"adapterID": "rusty_6502"}),
+        );
+        let messages = request(
+            &mut server,
+            "launch",
+            json!({"program": path, "stopOnEntry": stop_on_entry}),
+        );
+        assert_eq!(messages[0]["success"], true);
+        assert_eq!(messages[1]["event"], "initialized");
+        (server, path)
+    }
+
+    /// Body of the stopped event
+    fn stopped(messages: &[Value]) -> &Value {
+        &messages
+            .iter()
+            .find(|message| message["event"] == "stopped")
+            .expect("stopped event")["body"]
+    }
+
+    /// Names and lines of the stack frames
+    fn stack(server: &mut DapServer) -> Vec<(String, i64)> {
+        let messages = request(server, "stackTrace", json!({"threadId": 1}));
+        messages[0]["body"]["stackFrames"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|frame| {
+                (
+                    frame["name"].as_str().unwrap().to_string(),
+                    frame["line"].as_i64().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn breakpoints_map_to_source_lines() {
+        let (mut server, path) = launch("breakpoints", false);
+        let messages = request(
+            &mut server,
+            "setBreakpoints",
+            json!({"source": {"path": path}, "breakpoints": [{"line": 5}, {"line": 9}, {"line": 40}]}),
+        );
+        let breakpoints = &messages[0]["body"]["breakpoints"];
+        //The comment line moves to the next instruction
+        assert_eq!(breakpoints[0]["verified"], true);
+        assert_eq!(breakpoints[0]["line"], 6);
+        assert_eq!(breakpoints[1]["verified"], true);
+        assert_eq!(breakpoints[1]["line"], 9);
+        assert_eq!(breakpoints[2]["verified"], false);
+        assert_eq!(server.cpu.messenger.breakpoints.len(), 2);
+
+        let messages = request(&mut server, "configurationDone", json!({}));
+        assert_eq!(stopped(&messages)["reason"], "breakpoint");
+        assert_eq!(
+            stack(&mut server),
+            vec![("inc".to_string(), 6), ("start".to_string(), 2)]
+        );
+
+        let messages = request(&mut server, "continue", json!({"threadId": 1}));
+        assert_eq!(stopped(&messages)["reason"], "breakpoint");
+        assert_eq!(
+            stack(&mut server),
+            vec![
+                ("twice".to_string(), 9),
+                ("inc".to_string(), 7),
+                ("start".to_string(), 2)
+            ]
+        );
+
+        //Replacing the breakpoints of the file removes the old ones
+        request(
+            &mut server,
+            "setBreakpoints",
+            json!({"source": {"path": path}, "breakpoints": [{"line": 3}]}),
+        );
+        assert_eq!(server.cpu.messenger.breakpoints.len(), 1);
+        let messages = request(&mut server, "continue", json!({"threadId": 1}));
+        assert_eq!(stopped(&messages)["reason"], "breakpoint");
+        assert_eq!(stack(&mut server), vec![("start".to_string(), 3)]);
+    }
+
+    #[test]
+    fn stepping_follows_calls() {
+        let (mut server, _) = launch("stepping", true);
+        let messages = request(&mut server, "configurationDone", json!({}));
+        assert_eq!(stopped(&messages)["reason"], "entry");
+        assert_eq!(stack(&mut server), vec![("start".to_string(), 1)]);
+
+        request(&mut server, "next", json!({"threadId": 1}));
+        //Stepping over a call runs the whole subroutine
+        let messages = request(&mut server, "next", json!({"threadId": 1}));
+        assert_eq!(stopped(&messages)["reason"], "step");
+        assert_eq!(stack(&mut server), vec![("start".to_string(), 3)]);
+        assert_eq!((server.cpu.X, server.cpu.Y), (1, 1));
+
+        request(&mut server, "stepIn", json!({"threadId": 1}));
+        assert_eq!(
+            stack(&mut server),
+            vec![("inc".to_string(), 6), ("start".to_string(), 3)]
+        );
+        assert_eq!(
+            server.call_stack(),
+            &[CallFrame {
+                call: 0x605,
+                routine: 0x609
+            }]
+        );
+        request(&mut server, "stepIn", json!({"threadId": 1}));
+        request(&mut server, "stepIn", json!({"threadId": 1}));
+        assert_eq!(stack(&mut server)[0], ("twice".to_string(), 9));
+
+        let messages = request(&mut server, "stepOut", json!({"threadId": 1}));
+        assert_eq!(stopped(&messages)["reason"], "step");
+        assert_eq!(
+            stack(&mut server),
+            vec![("inc".to_string(), 8), ("start".to_string(), 3)]
+        );
+        request(&mut server, "stepOut", json!({"threadId": 1}));
+        assert_eq!(stack(&mut server), vec![("start".to_string(), 4)]);
+        assert!(server.call_stack().is_empty());
+        assert_eq!((server.cpu.X, server.cpu.Y), (2, 2));
+    }
+
+    #[test]
+    fn registers_and_memory_scopes() {
+        let (mut server, _) = launch("scopes", true);
+        request(&mut server, "configurationDone", json!({}));
+        request(&mut server, "stepIn", json!({"threadId": 1}));
+        let messages = request(&mut server, "scopes", json!({"frameId": 0}));
+        let scopes = messages[0]["body"]["scopes"].as_array().unwrap();
+        assert_eq!(scopes[0]["name"], "Registers");
+        assert_eq!(scopes[1]["name"], "Memory");
+
+        let reference = scopes[0]["variablesReference"].clone();
+        let messages = request(
+            &mut server,
+            "variables",
+            json!({"variablesReference": reference}),
+        );
+        let registers: Vec<(String, String)> = messages[0]["body"]["variables"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variable| {
+                (
+                    variable["name"].as_str().unwrap().to_string(),
+                    variable["value"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(registers[1], ("X".to_string(), "$00".to_string()));
+        assert_eq!(registers[4], ("PC".to_string(), "$0602".to_string()));
+        //LDX #$00 sets Z
+        assert_eq!(registers[5], ("P".to_string(), "$22 nv-bdiZc".to_string()));
+        assert_eq!(registers[6], ("Cycles".to_string(), "2".to_string()));
+
+        let reference = scopes[1]["variablesReference"].clone();
+        let messages = request(
+            &mut server,
+            "variables",
+            json!({"variablesReference": reference}),
+        );
+        let pages = messages[0]["body"]["variables"].as_array().unwrap();
+        assert_eq!(pages.len(), 256);
+        assert_eq!(pages[6]["name"], "$0600");
+        let reference = pages[6]["variablesReference"].clone();
+        let messages = request(
+            &mut server,
+            "variables",
+            json!({"variablesReference": reference}),
+        );
+        let rows = messages[0]["body"]["variables"].as_array().unwrap();
+        assert_eq!(rows.len(), 16);
+        assert_eq!(rows[0]["name"], "$0600");
+        assert!(rows[0]["value"]
+            .as_str()
+            .unwrap()
+            .starts_with("A2 00 20 09 06 20 09 06 00 E8"));
+    }
+
+    #[test]
+    fn brk_ends_the_program() {
+        let (mut server, _) = launch("brk", false);
+        let messages = request(&mut server, "configurationDone", json!({}));
+        assert_eq!(messages[1]["event"], "exited");
+        assert_eq!(messages[1]["body"]["exitCode"], 0);
+        assert_eq!(messages[2]["event"], "terminated");
+        let messages = request(&mut server, "continue", json!({"threadId": 1}));
+        assert_eq!(messages[0]["success"], false);
+        assert_eq!(messages[0]["message"], "The program has ended");
+    }
+
+    #[test]
+    fn unimplemented_instructions_stop_with_a_exception() {
+        let dir = std::env::temp_dir().join("rusty_6502_dap_exception");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("program.bin");
+        //INX, JMP $0600 which the CPU does not implement
+        std::fs::write(&path, [0xE8, 0x4C, 0x00, 0x06]).unwrap();
+        let mut server = DapServer::new();
+        request(
+            &mut server,
+            "launch",
+            json!({"program": path, "origin": "$0600"}),
+        );
+        let messages = request(&mut server, "configurationDone", json!({}));
+        let stopped = stopped(&messages);
+        assert_eq!(stopped["reason"], "exception");
+        assert!(stopped["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("CPU stopped at $0601"));
+        assert_eq!(server.cpu.PC, 0x601);
+        assert_eq!(stack(&mut server), vec![("$0600".to_string(), 0)]);
+    }
+
+    #[test]
+    fn images_use_the_lines_of_a_symbol_file() {
+        let path = source("image");
+        let dir = path.parent().unwrap();
+        let mut program = asm::Program::new(0x600);
+        program.assemble_file(&path).unwrap();
+        let mut mem = MEM::new();
+        program.fill_ram(&mut mem);
+        let image = dir.join("program.prg");
+        let bytes = image::write_mem(&mem, 0x600, 0x612, ImageFormat::Prg, None);
+        std::fs::write(&image, bytes).unwrap();
+        let symbols = dir.join("program.json");
+        std::fs::write(
+            &symbols,
+            asm::write_symbols(&program.symbols, SymbolFormat::Json),
+        )
+        .unwrap();
+
+        let mut server = DapServer::new();
+        let messages = request(
+            &mut server,
+            "launch",
+            json!({"program": image, "symbols": symbols, "start": "start"}),
+        );
+        assert_eq!(messages[0]["success"], true);
+        assert_eq!(server.cpu.PC, 0x600);
+        let messages = request(
+            &mut server,
+            "setBreakpoints",
+            json!({"source": {"path": path}, "breakpoints": [{"line": 9}]}),
+        );
+        assert_eq!(messages[0]["body"]["breakpoints"][0]["verified"], true);
+        request(&mut server, "configurationDone", json!({}));
+        //Only the lines of labels are known, not the ones of the calls
+        assert_eq!(
+            stack(&mut server),
+            vec![
+                ("twice".to_string(), 9),
+                ("inc".to_string(), 0),
+                ("start".to_string(), 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn launch_errors_are_reported() {
+        let mut server = DapServer::new();
+        let messages = request(
+            &mut server,
+            "launch",
+            json!({"program": "/nonexistent/program.s"}),
+        );
+        assert_eq!(messages[0]["success"], false);
+        assert_eq!(messages.len(), 1);
+        let messages = request(&mut server, "evaluate", json!({"expression": "A"}));
+        assert_eq!(messages[0]["message"], "Unsupported request 'evaluate'");
+    }
+
+    #[test]
+    fn messages_are_framed() {
+        let path = source("framed");
+        let mut input = String::new();
+        for (seq, (command, arguments)) in [
+            ("initialize", json!({})),
+            ("launch", json!({"program": path, "stopOnEntry": true})),
+            ("configurationDone", json!({})),
+            ("threads", json!({})),
+            ("disconnect", json!({})),
+            ("threads", json!({})),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let request = json!({"seq": seq + 1, "type": "request", "command": command, "arguments": arguments})
+                .to_string();
+            input += &format!("Content-Length: {}\r\n\r\n{}", request.len(), request);
+        }
+        let mut server = DapServer::new();
+        let mut output = Vec::new();
+        server.serve(input.as_bytes(), &mut output).unwrap();
+        assert!(server.is_done());
+
+        let mut output = String::from_utf8(output).unwrap();
+        let mut messages = Vec::new();
+        while let Some(rest) = output.strip_prefix("Content-Length: ") {
+            let (length, rest) = rest.split_once("\r\n\r\n").unwrap();
+            let length: usize = length.parse().unwrap();
+            messages.push(serde_json::from_str::<Value>(&rest[..length]).unwrap());
+            output = rest[length..].to_string();
+        }
+        assert!(output.is_empty());
+        let kinds: Vec<String> = messages
+            .iter()
+            .map(|message| match message["type"].as_str().unwrap() {
+                "event" => message["event"].as_str().unwrap().to_string(),
+                _ => message["command"].as_str().unwrap().to_string(),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "initialize",
+                "launch",
+                "initialized",
+                "configurationDone",
+                "stopped",
+                "threads",
+                "disconnect"
+            ]
+        );
+        let sequence: Vec<i64> = messages
+            .iter()
+            .map(|message| message["seq"].as_i64().unwrap())
+            .collect();
+        assert_eq!(sequence, (1..=7).collect::<Vec<i64>>());
+        assert_eq!(messages[5]["request_seq"], 4);
+        assert_eq!(messages[5]["body"]["threads"][0]["id"], 1);
+    }
+}
@@ -0,0 +1,233 @@
+mod gdb_tests {
+    use rusty_6502::gdb::{GdbServer, WatchKind, Watchpoint, TARGET_XML};
+
+    fn server(program: &[u8]) -> GdbServer {
+        let mut server = GdbServer::new();
+        server.mem.load(0x600, program);
+        server
+    }
+
+    fn reply(server: &mut GdbServer, packet: &str) -> String {
+        server.handle(packet).unwrap()
+    }
+
+    fn frame(packet: &str) -> String {
+        let checksum = packet.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        format!("${}#{:02x}", packet, checksum)
+    }
+
+    #[test]
+    fn registers_are_read_and_written() {
+        let mut server = server(&[]);
+        server.cpu.A = 0x12;
+        server.cpu.X = 0x34;
+        server.cpu.Y = 0x56;
+        server.cpu.SP = 0xFD;
+        //a x y p sp pc, the program counter is little endian
+        assert_eq!(reply(&mut server, "g"), "12345620fd0006");
+        assert_eq!(reply(&mut server, "G0102038001ff10"), "OK");
+        assert_eq!(
+            (server.cpu.A, server.cpu.X, server.cpu.Y, server.cpu.SP),
+            (1, 2, 3, 1)
+        );
+        assert_eq!(server.cpu.status_flags.N, 1);
+        assert_eq!(server.cpu.PC, 0x10FF);
+        assert_eq!(reply(&mut server, "P5=0007"), "OK");
+        assert_eq!(reply(&mut server, "p5"), "0007");
+        assert_eq!(reply(&mut server, "P0=aa"), "OK");
+        assert_eq!(reply(&mut server, "p0"), "aa");
+        assert_eq!(reply(&mut server, "p6"), "E01");
+        assert_eq!(reply(&mut server, "P5=00"), "E01");
+        assert_eq!(reply(&mut server, "G00"), "E01");
+    }
+
+    #[test]
+    fn memory_is_read_and_written() {
+        let mut server = server(&[0xA9, 0x01]);
+        assert_eq!(reply(&mut server, "m600,3"), "a90100");
+        assert_eq!(reply(&mut server, "M200,2:beef"), "OK");
+        assert_eq!(&server.mem.data[0x200..0x202], &[0xBE, 0xEF]);
+        //Reads stop at the end of memory
+        assert_eq!(reply(&mut server, "mfffe,4"), "0000");
+        assert_eq!(reply(&mut server, "m10000,1"), "E01");
+        assert_eq!(reply(&mut server, "M200,2:be"), "E01");
+        assert_eq!(reply(&mut server, "Mffff,2:0000"), "E01");
+        assert_eq!(reply(&mut server, "mffff,ffffffffffffffff"), "00");
+    }
+
+    #[test]
+    fn target_description_is_served_in_chunks() {
+        let mut server = server(&[]);
+        assert!(reply(&mut server, "qSupported:multiprocess+;swbreak+")
+            .contains("qXfer:features:read+"));
+        let first = reply(&mut server, "qXfer:features:read:target.xml:0,10");
+        assert_eq!(first, format!("m{}", &TARGET_XML[..0x10]));
+        let rest = reply(
+            &mut server,
+            &format!("qXfer:features:read:target.xml:10,{:x}", TARGET_XML.len()),
+        );
+        assert_eq!(rest, format!("l{}", &TARGET_XML[0x10..]));
+        assert!(TARGET_XML.contains(r#"<reg name="pc" bitsize="16" regnum="5""#));
+    }
+
+    #[test]
+    fn unsupported_packets_get_empty_replies() {
+        let mut server = server(&[]);
+        assert_eq!(reply(&mut server, "vMustReplyEmpty"), "");
+        assert_eq!(reply(&mut server, "vCont?"), "");
+        assert_eq!(reply(&mut server, "Z5,600,1"), "");
+        assert_eq!(reply(&mut server, "qAttached"), "1");
+        assert_eq!(reply(&mut server, "?"), "S05");
+    }
+
+    #[test]
+    fn single_steps() {
+        let mut server = server(&[0xA2, 0x05, 0xE8, 0x00]);
+        assert_eq!(reply(&mut server, "s"), "S05");
+        assert_eq!(server.cpu.PC, 0x602);
+        assert_eq!(server.cpu.X, 5);
+        assert_eq!(reply(&mut server, "s"), "S05");
+        assert_eq!(server.cpu.X, 6);
+        assert_eq!(server.cycles, 4);
+        //Stepping from a address
+        assert_eq!(reply(&mut server, "s602"), "S05");
+        assert_eq!(server.cpu.X, 7);
+    }
+
+    #[test]
+    fn continue_stops_at_breakpoints() {
+        let mut server = server(&[0xE8, 0xE8, 0xE8, 0x00]);
+        assert_eq!(reply(&mut server, "Z0,602,1"), "OK");
+        assert_eq!(server.cpu.messenger.breakpoints, vec![0x602]);
+        assert_eq!(reply(&mut server, "c"), "T05swbreak:;");
+        assert_eq!(server.cpu.PC, 0x602);
+        assert_eq!(reply(&mut server, "?"), "T05swbreak:;");
+        //Continuing from a breakpoint executes it
+        assert_eq!(reply(&mut server, "z0,602,1"), "OK");
+        assert!(server.cpu.messenger.breakpoints.is_empty());
+        assert_eq!(reply(&mut server, "c"), "S05");
+        assert_eq!(server.cpu.X, 3);
+    }
+
+    #[test]
+    fn continue_is_limited() {
+        let mut server = GdbServer::new();
+        server.max_instructions = 100;
+        server.mem.data[0x600..0x700].fill(0xE8);
+        assert_eq!(reply(&mut server, "c"), "S02");
+        assert_eq!(server.cpu.X, 100);
+    }
+
+    #[test]
+    fn unimplemented_instructions_stop_before_them() {
+        //JMP is not implemented by the CPU
+        let mut server = server(&[0xE8, 0x4C, 0x00, 0x06]);
+        assert_eq!(reply(&mut server, "c"), "S04");
+        assert_eq!(server.cpu.PC, 0x601);
+    }
+
+    #[test]
+    fn write_watchpoints() {
+        //LDA #$01, STA $0300, STA $0301, BRK
+        let mut server = server(&[0xA9, 0x01, 0x8D, 0x00, 0x03, 0x8D, 0x01, 0x03, 0x00]);
+        assert_eq!(reply(&mut server, "Z2,301,1"), "OK");
+        assert_eq!(
+            server.watchpoints,
+            vec![Watchpoint {
+                kind: WatchKind::Write,
+                address: 0x301,
+                len: 1
+            }]
+        );
+        assert_eq!(reply(&mut server, "c"), "T05watch:0301;");
+        assert_eq!(server.cpu.PC, 0x608);
+        assert_eq!(reply(&mut server, "z2,301,1"), "OK");
+        assert!(server.watchpoints.is_empty());
+    }
+
+    #[test]
+    fn writes_of_the_same_value_are_seen() {
+        //STA $0300 with A = 0
+        let mut server = server(&[0x8D, 0x00, 0x03, 0x00]);
+        reply(&mut server, "Z2,300,1");
+        assert_eq!(reply(&mut server, "c"), "T05watch:0300;");
+    }
+
+    #[test]
+    fn read_and_access_watchpoints() {
+        //LDY #$02, LDA $0400,Y, STA $10, BRK
+        let program = [0xA0, 0x02, 0xB9, 0x00, 0x04, 0x85, 0x10, 0x00];
+        let mut server = server(&program);
+        reply(&mut server, "Z3,400,4");
+        //A write to a read watchpoint does not stop
+        reply(&mut server, "Z3,10,1");
+        assert_eq!(reply(&mut server, "c"), "T05rwatch:0402;");
+        assert_eq!(server.cpu.PC, 0x605);
+        assert_eq!(reply(&mut server, "c"), "S05");
+
+        let mut server = self::server(&program);
+        reply(&mut server, "Z4,10,1");
+        assert_eq!(reply(&mut server, "c"), "T05awatch:0010;");
+    }
+
+    #[test]
+    fn stack_and_fetch_watchpoints() {
+        //JSR $0700 pushes the return address on the stack
+        let mut server = server(&[0x20, 0x00, 0x07]);
+        reply(&mut server, "Z2,100,100");
+        assert_eq!(reply(&mut server, "c"), "T05watch:01ff;");
+        assert_eq!(server.cpu.PC, 0x700);
+
+        //Fetching the operand of the JSR reads it
+        let mut server = self::server(&[0x20, 0x00, 0x07]);
+        reply(&mut server, "Z3,602,1");
+        assert_eq!(reply(&mut server, "s"), "T05rwatch:0602;");
+    }
+
+    #[test]
+    fn packets_are_framed_and_acknowledged() {
+        let mut server = server(&[0xE8, 0x00]);
+        let input = format!(
+            "+{}{}$m600,1#00{}",
+            frame("qAttached"),
+            frame("s"),
+            frame("k")
+        );
+        let mut output = Vec::new();
+        server.serve(input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        //The packet with a bad checksum is rejected
+        assert_eq!(output, format!("+{}+{}-+", frame("1"), frame("S05")));
+        assert!(!server.is_attached());
+    }
+
+    #[test]
+    fn replies_are_resent_and_acks_can_be_disabled() {
+        let mut server = server(&[]);
+        let input = format!(
+            "{}-{}+{}",
+            frame("qAttached"),
+            frame("QStartNoAckMode"),
+            frame("p1")
+        );
+        let mut output = Vec::new();
+        server.serve(input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            format!("+{0}{0}+{1}{2}", frame("1"), frame("OK"), frame("00"))
+        );
+    }
+
+    #[test]
+    fn detach_ends_the_session() {
+        let mut server = server(&[]);
+        let input = format!("{}{}", frame("D"), frame("g"));
+        let mut output = Vec::new();
+        server.serve(input.as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!("+{}", frame("OK"))
+        );
+    }
+}
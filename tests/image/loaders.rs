@@ -0,0 +1,132 @@
+mod loaders_tests {
+    use rusty_6502::{
+        image::{self, ImageFormat, LoadError},
+        mem::MEM,
+    };
+
+    #[test]
+    fn intel_hex() {
+        let mut mem = MEM::new();
+        let loaded = image::load_ihex(
+            &mut mem,
+            "
+            :020000040000FA
+            :04060000A2018600CD
+            :02060400A5004F
+            :02FFFC000006FD
+            :0400000500000600F1
+            :00000001FF
+            ",
+        )
+        .unwrap();
+        assert_eq!(loaded.ranges, vec![0x600..=0x605, 0xFFFC..=0xFFFD]);
+        assert_eq!(loaded.entry, Some(0x600));
+        assert_eq!(mem.hex_dump(0x600, 0x606), "A2 01 86 00 A5 00 ");
+        assert_eq!(mem.hex_dump(0xFFFC, 0xFFFE), "00 06 ");
+    }
+
+    #[test]
+    fn intel_hex_errors() {
+        let mut mem = MEM::new();
+        match image::load_ihex(&mut mem, ":020000040000FA\n:04060000A2018600CC") {
+            Err(LoadError::Checksum {
+                line: 2,
+                expected: 0xCD,
+                found: 0xCC,
+            }) => {}
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(matches!(
+            image::load_ihex(&mut mem, ":02060400A5004F"),
+            Err(LoadError::MissingEnd)
+        ));
+        assert!(matches!(
+            image::load_ihex(&mut mem, ":020000040001F9\n:01000000EA15\n:00000001FF"),
+            Err(LoadError::OutOfMemory { address: 0x10000 })
+        ));
+        let error = image::load_ihex(&mut mem, "02060400A5004F").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: Record does not start with ':'");
+        assert!(matches!(
+            image::load_ihex(&mut mem, ":02060400A500"),
+            Err(LoadError::InvalidRecord { line: 1, .. })
+        ));
+        let error = image::load_ihex(&mut mem, ":0é0").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 1: Record contains a invalid hex digit"
+        );
+    }
+
+    #[test]
+    fn s_records() {
+        let mut mem = MEM::new();
+        let loaded = image::load_srec(
+            &mut mem,
+            "S00600004844521B\nS206000600A90149\nS20600FFFE0006F6\nS5030002FA\nS804000600F5\n",
+        )
+        .unwrap();
+        assert_eq!(loaded.ranges, vec![0x600..=0x601, 0xFFFE..=0xFFFF]);
+        assert_eq!(loaded.entry, Some(0x600));
+        assert_eq!(mem.hex_dump(0xFFFE, 0x10000), "00 06 ");
+
+        assert!(matches!(
+            image::load_srec(&mut mem, "S206000600A90148"),
+            Err(LoadError::Checksum { line: 1, .. })
+        ));
+        assert!(matches!(
+            image::load_srec(&mut mem, "S105FFFF0102F9"),
+            Err(LoadError::OutOfMemory { address: 0x10000 })
+        ));
+        assert!(matches!(
+            image::load_srec(&mut mem, "S4030000FC"),
+            Err(LoadError::InvalidRecord { line: 1, .. })
+        ));
+        assert!(matches!(
+            image::load_srec(&mut mem, "S1é00"),
+            Err(LoadError::InvalidRecord { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn prg_with_basic_stub() {
+        //10 SYS 2061
+        let prg = [
+            0x01, 0x08, 0x0B, 0x08, 0x0A, 0x00, 0x9E, b'2', b'0', b'6', b'1', 0x00, 0x00, 0x00,
+            0xEA,
+        ];
+        let mut mem = MEM::new();
+        let loaded = image::load_prg(&mut mem, &prg).unwrap();
+        assert_eq!(loaded.ranges, vec![0x801..=0x80D]);
+        assert_eq!(loaded.entry, Some(2061));
+        assert_eq!(mem[0x80D], 0xEA);
+
+        let loaded = image::load_prg(&mut mem, &[0x00, 0xC0, 0x60]).unwrap();
+        assert_eq!(loaded.ranges, vec![0xC000..=0xC000]);
+        assert_eq!(loaded.entry, None);
+        assert!(matches!(
+            image::load_prg(&mut mem, &[0x00]),
+            Err(LoadError::MissingLoadAddress)
+        ));
+    }
+
+    #[test]
+    fn raw_and_files() {
+        let mut mem = MEM::new();
+        assert!(matches!(
+            image::load_raw(&mut mem, &[1, 2, 3], 0xFFFE),
+            Err(LoadError::OutOfMemory { address: 0x10000 })
+        ));
+
+        let path = std::env::temp_dir().join("rusty_6502_loader_test.hex");
+        std::fs::write(&path, ":02060400A5004F\n:00000001FF\n").unwrap();
+        let loaded = image::load_file(&mut mem, &path, 0).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.ranges, vec![0x604..=0x605]);
+        assert_eq!(ImageFormat::from_path(&path), ImageFormat::IntelHex);
+
+        assert!(matches!(
+            image::load_file(&mut mem, "tests/image/missing.bin", 0),
+            Err(LoadError::Io(_))
+        ));
+    }
+}
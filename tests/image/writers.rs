@@ -0,0 +1,96 @@
+mod writers_tests {
+    use rusty_6502::{
+        asm::Program,
+        image::{self, ImageFormat},
+        mem::MEM,
+    };
+
+    fn program() -> Program {
+        let mut program = Program::new(0x600);
+        program
+            .assemble(
+                "
+                start:  LDX #$10
+                loop:   DEX
+                        BNE loop
+                        .fill 20, $EA
+                .org $0700
+                        .byte 1, 2, 3
+                .org $FFFC
+                        .word start
+                ",
+            )
+            .unwrap();
+        program
+    }
+
+    #[test]
+    fn text_formats_keep_gaps_and_entry() {
+        let program = program();
+        let mut expected = MEM::new();
+        program.fill_ram(&mut expected);
+
+        for format in [ImageFormat::IntelHex, ImageFormat::SRecord] {
+            let data = image::write_program(&program, format, Some(0x600));
+            let mut mem = MEM::new();
+            let loaded = image::load(&mut mem, &data, format, 0).unwrap();
+            assert_eq!(
+                loaded.ranges,
+                vec![0x600..=0x618, 0x700..=0x702, 0xFFFC..=0xFFFD]
+            );
+            assert_eq!(loaded.entry, Some(0x600));
+            assert_eq!(mem.data, expected.data);
+        }
+    }
+
+    #[test]
+    fn intel_hex_records() {
+        let text = image::write_program(&program(), ImageFormat::IntelHex, None);
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            ":10060000A210CAD0FDEAEAEAEAEAEAEAEAEAEAEA93\n\
+             :09061000EAEAEAEAEAEAEAEAEAA7\n\
+             :03070000010203F0\n\
+             :02FFFC000006FD\n\
+             :00000001FF\n"
+        );
+    }
+
+    #[test]
+    fn binary_formats_fill_gaps() {
+        let mut program = Program::new(0x600);
+        program.assemble("NOP\n.org $0604\nRTS").unwrap();
+        assert_eq!(
+            image::write_program(&program, ImageFormat::Raw, None),
+            vec![0xEA, 0, 0, 0, 0x60]
+        );
+        assert_eq!(
+            image::write_program(&program, ImageFormat::Prg, None),
+            vec![0x00, 0x06, 0xEA, 0, 0, 0, 0x60]
+        );
+
+        let mut mem = MEM::new();
+        image::load(
+            &mut mem,
+            &[0x00, 0x06, 0xEA, 0, 0, 0, 0x60],
+            ImageFormat::Prg,
+            0,
+        )
+        .unwrap();
+        assert_eq!(mem[0x604], 0x60);
+    }
+
+    #[test]
+    fn memory_ranges() {
+        let mut mem = MEM::new();
+        program().fill_ram(&mut mem);
+        assert_eq!(
+            image::write_mem(&mem, 0x700, 0x703, ImageFormat::Raw, None),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            image::write_mem(&mem, 0xFFFC, 0x10000, ImageFormat::SRecord, Some(0xFFFC)),
+            b"S0030000FC\nS107FFFC00060000F7\nS5030001FB\nS903FFFC01\n"
+        );
+    }
+}
@@ -18,4 +18,20 @@ mod inx_tests {
         assert_eq!(mem[601], 0x01);
         assert_eq!(mem[602], 0xE8);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn inx_execute_sized() {
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(|_| {});
+        cpu.reset(600, &mut mem);
+        for offset in 0..4 {
+            mem[600 + offset] = 0xE8;
+        }
+
+        let mut cycles = 5;
+        let pc = cpu.execute_sized(&mut cycles, &mut mem);
+        assert_eq!(cycles, 0);
+        assert_eq!(cpu.X, 0x03);
+        assert_eq!(pc, 603);
+    }
+}
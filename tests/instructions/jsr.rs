@@ -0,0 +1,37 @@
+mod jsr_tests {
+    use rusty_6502::{cpu::CPU, mem::MEM};
+
+    #[test]
+    fn jsr_absolute() {
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(|_| {});
+        cpu.reset(0x600, &mut mem);
+        mem[0x600] = 0x20;
+        mem[0x601] = 0x10;
+        mem[0x602] = 0x06;
+
+        let (_, cycles, _) = cpu.step(&mut mem);
+        assert_eq!(cycles, 6);
+        assert_eq!(cpu.PC, 0x610);
+        assert_eq!(cpu.SP, 0xFD);
+        assert_eq!(mem[0x1FF], 0x06);
+        assert_eq!(mem[0x1FE], 0x02);
+    }
+
+    #[test]
+    fn jsr_wraps_the_stack_pointer() {
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(|_| {});
+        cpu.reset(0x600, &mut mem);
+        cpu.SP = 0x00;
+        mem[0x600] = 0x20;
+        mem[0x601] = 0x00;
+        mem[0x602] = 0x07;
+
+        cpu.step(&mut mem);
+        assert_eq!(cpu.PC, 0x700);
+        assert_eq!(cpu.SP, 0xFE);
+        assert_eq!(mem[0x100], 0x06);
+        assert_eq!(mem[0x1FF], 0x02);
+    }
+}
@@ -0,0 +1,37 @@
+mod rts_tests {
+    use rusty_6502::{cpu::CPU, mem::MEM};
+
+    #[test]
+    fn rts_implied() {
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(|_| {});
+        cpu.reset(0x600, &mut mem);
+        mem[0x600] = 0x20;
+        mem[0x601] = 0x10;
+        mem[0x602] = 0x06;
+        mem[0x603] = 0xE8;
+        mem[0x610] = 0xA0;
+        mem[0x611] = 0x03;
+        mem[0x612] = 0x60;
+
+        cpu.execute_continuous(&mut mem);
+        assert_eq!(cpu.X, 0x01);
+        assert_eq!(cpu.Y, 0x03);
+    }
+
+    #[test]
+    fn rts_cycles() {
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(|_| {});
+        cpu.reset(0x600, &mut mem);
+        cpu.SP = 0xFD;
+        mem[0x1FE] = 0x02;
+        mem[0x1FF] = 0x07;
+        mem[0x600] = 0x60;
+
+        let (_, cycles, _) = cpu.step(&mut mem);
+        assert_eq!(cycles, 6);
+        assert_eq!(cpu.PC, 0x703);
+        assert_eq!(cpu.SP, 0xFF);
+    }
+}
@@ -5,9 +5,11 @@ mod instructions {
     mod inc;
     mod inx;
     mod iny;
+    mod jsr;
     mod lda;
     mod ldx;
     mod ldy;
+    mod rts;
     mod sec;
     mod sed;
     mod sei;
@@ -21,3 +23,52 @@ mod instructions {
     mod txs;
     mod tya;
 }
+mod asm {
+    mod assembler;
+    mod diagnostics;
+    mod disassembler;
+    mod directives;
+    mod expressions;
+    mod inline;
+    mod listing;
+    mod macros;
+    mod objects;
+    mod symbols;
+}
+mod conformance {
+    mod klaus;
+    mod vectors;
+}
+mod dap {
+    mod session;
+}
+mod gdb {
+    mod remote;
+}
+mod image {
+    mod loaders;
+    mod writers;
+}
+mod testing {
+    mod harness;
+}
+mod monitor {
+    mod commands;
+}
+mod replay {
+    mod recording;
+}
+mod rewind {
+    mod history;
+}
+#[cfg(feature = "serde")]
+mod serde {
+    mod serialize;
+}
+mod state {
+    mod save_states;
+}
+mod trace {
+    mod compare;
+    mod tracer;
+}
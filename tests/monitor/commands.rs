@@ -0,0 +1,199 @@
+mod monitor_tests {
+    use rusty_6502::monitor::Monitor;
+
+    fn run(monitor: &mut Monitor, commands: &[&str]) -> String {
+        let mut out = Vec::new();
+        for command in commands {
+            assert!(monitor.command(command, &mut out).unwrap());
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn registers_can_be_shown_and_edited() {
+        let mut monitor = Monitor::new();
+        let out = run(&mut monitor, &["r a=10 x=ff y=1 sp=fd pc=0700 c=1 d=1"]);
+        assert_eq!(
+            out,
+            "PC:0700 A:10 X:FF Y:01 P:29 SP:FD NV-BDIZC:00101001 CYC:0\n"
+        );
+        assert_eq!(monitor.cpu.PC, 0x700);
+        assert_eq!(monitor.cpu.status_flags.D, 1);
+        let out = run(&mut monitor, &["r p=80"]);
+        assert!(out.contains("P:80"));
+    }
+
+    #[test]
+    fn invalid_register_edits_are_reported() {
+        let mut monitor = Monitor::new();
+        let out = run(&mut monitor, &["r q=1", "r a=100", "r c=2", "r a"]);
+        assert_eq!(
+            out,
+            "? Unknown register 'q'\n\
+             ? Invalid byte '100'\n\
+             ? Flag c must be 0 or 1\n\
+             ? Expected register=value, found 'a'\n"
+        );
+    }
+
+    #[test]
+    fn memory_can_be_examined_and_modified() {
+        let mut monitor = Monitor::new();
+        let out = run(&mut monitor, &["m 0200 = 41 42 $43", "m 0200 0211"]);
+        assert_eq!(monitor.mem[0x202], 0x43);
+        assert_eq!(
+            out,
+            "0200  41 42 43 00 00 00 00 00 00 00 00 00 00 00 00 00  ABC.............\n\
+             0210  00 00                                            ..\n"
+        );
+        //Without arguments the dump continues where it stopped
+        let out = run(&mut monitor, &["m"]);
+        assert!(out.starts_with("0212  "));
+        assert_eq!(out.lines().count(), 8);
+    }
+
+    #[test]
+    fn instructions_are_assembled_in_place() {
+        let mut monitor = Monitor::new();
+        let out = run(&mut monitor, &["a 0600 LDA #$01"]);
+        assert_eq!(&monitor.mem.data[0x600..0x602], &[0xA9, 0x01]);
+        assert!(out.contains("LDA #$01"));
+        assert_eq!(monitor.prompt(), ". ");
+
+        run(&mut monitor, &["a 0602"]);
+        assert_eq!(monitor.prompt(), "0602  ");
+        run(&mut monitor, &["STA $0300"]);
+        assert_eq!(monitor.prompt(), "0605  ");
+        let out = run(&mut monitor, &["LDA #"]);
+        assert!(out.starts_with("? "));
+        assert_eq!(monitor.prompt(), "0605  ");
+        run(&mut monitor, &["BRK", ""]);
+        assert_eq!(monitor.prompt(), ". ");
+        assert_eq!(&monitor.mem.data[0x602..0x606], &[0x8D, 0x00, 0x03, 0x00]);
+    }
+
+    #[test]
+    fn disassembly_marks_breakpoints() {
+        let mut monitor = Monitor::new();
+        run(&mut monitor, &["m 0600 = a9 01 e8 00"]);
+        let out = run(&mut monitor, &["b 0602", "d 0600 0603"]);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "Breakpoint at 0602 set");
+        assert!(lines[1].starts_with(" 0600") && lines[1].ends_with("LDA #$01"));
+        assert!(lines[2].starts_with("*0602") && lines[2].ends_with("INX"));
+        assert!(lines[3].ends_with("BRK"));
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn breakpoints_toggle() {
+        let mut monitor = Monitor::new();
+        let out = run(&mut monitor, &["b", "b 0700", "b 0600", "b", "b 0700", "b"]);
+        assert_eq!(
+            out,
+            "No breakpoints\n\
+             Breakpoint at 0700 set\n\
+             Breakpoint at 0600 set\n\
+             0600 0700\n\
+             Breakpoint at 0700 removed\n\
+             0600\n"
+        );
+    }
+
+    #[test]
+    fn go_stops_at_breakpoints_and_brk() {
+        let mut monitor = Monitor::new();
+        run(&mut monitor, &["m 0600 = a9 05 e8 e8 00"]);
+        let out = run(&mut monitor, &["b 0603", "g"]);
+        assert!(out.contains("Breakpoint at 0603\n"));
+        assert_eq!(monitor.cpu.PC, 0x603);
+        assert_eq!(monitor.cpu.X, 1);
+        let out = run(&mut monitor, &["g"]);
+        assert!(out.contains("BRK\n"));
+        assert_eq!(monitor.cpu.X, 2);
+        assert_eq!(monitor.cpu.A, 5);
+    }
+
+    #[test]
+    fn go_is_limited() {
+        let mut monitor = Monitor::new();
+        monitor.max_instructions = 10;
+        //A run of INX never reaches a BRK in 10 instructions
+        monitor.mem.data[0x600..0x700].fill(0xE8);
+        let out = run(&mut monitor, &["g 0600"]);
+        assert!(out.starts_with("Stopped after 10 instructions\n"));
+        assert_eq!(monitor.cpu.X, 10);
+    }
+
+    #[test]
+    fn trace_executes_steps() {
+        let mut monitor = Monitor::new();
+        run(&mut monitor, &["m 0600 = a2 03 e8 e8 00"]);
+        let out = run(&mut monitor, &["t 2", "t"]);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("0600  A2 03"));
+        assert!(lines[2].starts_with("0603  E8"));
+        assert_eq!(monitor.cpu.X, 5);
+        assert_eq!(monitor.cycles, 6);
+    }
+
+    #[test]
+    fn cpu_panics_are_reported() {
+        let mut monitor = Monitor::new();
+        //JMP is not implemented by the CPU
+        run(&mut monitor, &["m 0600 = 4c 00 06"]);
+        let out = run(&mut monitor, &["g"]);
+        assert!(out.starts_with("? CPU stopped"));
+        //The registers are left before the instruction
+        assert_eq!(monitor.cpu.PC, 0x600);
+        assert_eq!(monitor.cycles, 0);
+    }
+
+    #[test]
+    fn symbols_win_over_hex_numbers() {
+        let path = std::env::temp_dir().join("rusty_6502_monitor_test.lbl");
+        std::fs::write(&path, "al C:0300 .beef\n").unwrap();
+        let mut monitor = Monitor::new();
+        let out = run(&mut monitor, &[&format!("sym {}", path.to_str().unwrap())]);
+        assert_eq!(out, "Loaded 1 symbols\n");
+        std::fs::remove_file(path).unwrap();
+
+        run(&mut monitor, &["m beef = 01", "m $beef = 02"]);
+        assert_eq!(monitor.mem[0x300], 0x01);
+        assert_eq!(monitor.mem[0xBEEF], 0x02);
+    }
+
+    #[test]
+    fn memory_can_be_saved_and_loaded() {
+        let path = std::env::temp_dir().join("rusty_6502_monitor_test.bin");
+        let path = path.to_str().unwrap();
+        let mut monitor = Monitor::new();
+        run(&mut monitor, &["m 0300 = 01 02 03 04"]);
+        let out = run(&mut monitor, &[&format!("s {} 0300 0303", path)]);
+        assert_eq!(out, "Saved 0300-0303\n");
+
+        let mut other = Monitor::new();
+        let out = run(&mut other, &[&format!("l {} 1000", path)]);
+        assert_eq!(out, "Loaded 1000-1003\n");
+        assert_eq!(&other.mem.data[0x1000..0x1004], &[1, 2, 3, 4]);
+        std::fs::remove_file(path).unwrap();
+
+        let out = run(&mut other, &["l /nonexistent/file.bin"]);
+        assert!(out.starts_with("? /nonexistent/file.bin: "));
+    }
+
+    #[test]
+    fn run_reads_until_quit() {
+        let mut monitor = Monitor::new();
+        let input = "m 0200 = ff\nbogus\nx\nm 0200 = 00\n";
+        let mut out = Vec::new();
+        monitor.run(input.as_bytes(), &mut out).unwrap();
+        assert_eq!(monitor.mem[0x200], 0xFF);
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out,
+            ". . ? Unknown command 'bogus', ? lists the commands\n. "
+        );
+    }
+}
@@ -0,0 +1,148 @@
+mod recording_tests {
+    use rusty_6502::{
+        asm6502,
+        cpu::CPU,
+        debugger::MessageType,
+        mem::MEM,
+        replay::{Desync, Input, Player, Recorder, Recording, RecordingError},
+    };
+
+    fn machine(program: &[u8]) -> (CPU<impl FnOnce(MessageType) + Copy>, MEM) {
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(|_: MessageType| {});
+        cpu.reset(0x600, &mut mem);
+        mem.load(0x600, program);
+        //Interrupt handler at $0700
+        mem.load(0x700, &asm6502! { INY; STY $0201 });
+        mem.load(0xFFFE, &[0x00, 0x07]);
+        (cpu, mem)
+    }
+
+    fn program() -> Vec<u8> {
+        asm6502! { LDA $00FF; STA $0200; LDA $00FF; STA $0200 }
+    }
+
+    fn record() -> Recording {
+        let (mut cpu, mut mem) = machine(&program());
+        let mut recorder = Recorder::new();
+        recorder.input(
+            &mut cpu,
+            &mut mem,
+            Input::Write {
+                address: 0xFF,
+                value: 0x41,
+            },
+        );
+        recorder.step(&mut cpu, &mut mem);
+        recorder.step(&mut cpu, &mut mem);
+        recorder.input(
+            &mut cpu,
+            &mut mem,
+            Input::Write {
+                address: 0xFF,
+                value: 0x42,
+            },
+        );
+        recorder.step(&mut cpu, &mut mem);
+        recorder.input(&mut cpu, &mut mem, Input::Irq);
+        recorder.step(&mut cpu, &mut mem);
+        recorder.step(&mut cpu, &mut mem);
+        assert_eq!((cpu.PC, cpu.Y, mem[0x201]), (0x0704, 0x01, 0x01));
+        assert_eq!((mem[0x1FF], mem[0x1FE]), (0x06, 0x07));
+        recorder.finish(&cpu, &mem)
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let recording = record();
+        let cycles: Vec<u64> = recording.events.iter().map(|event| event.cycle).collect();
+        assert_eq!(cycles, [0, 7, 10]);
+        assert_eq!(recording.cycles, 23);
+
+        let text = recording.to_string();
+        assert!(text.starts_with("rusty_6502 recording 1\n0 write $00FF $41 hash "));
+        assert!(text.contains("\n10 irq hash "));
+        let read: Recording = text.parse().unwrap();
+        assert_eq!(read, recording);
+
+        let (mut cpu, mut mem) = machine(&program());
+        let mut player = Player::new(read);
+        assert_eq!(player.run(&mut cpu, &mut mem), Ok(23));
+        assert!(player.is_done());
+        assert_eq!((mem[0x200], mem[0x201]), (0x41, 0x01));
+    }
+
+    #[test]
+    fn desync() {
+        let recording = record();
+
+        //A different input is found at the next input after it
+        let mut changed = recording.clone();
+        changed.events[1].input = Input::Write {
+            address: 0xFF,
+            value: 0x43,
+        };
+        let (mut cpu, mut mem) = machine(&program());
+        let error = Player::new(changed).run(&mut cpu, &mut mem).unwrap_err();
+        assert_eq!(
+            error,
+            Desync {
+                cycle: 10,
+                message: "machine state differs before a input".to_string(),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "Desync at cycle 10: machine state differs before a input"
+        );
+
+        //A different program is found before the first input
+        let mut changed = program();
+        changed[1] = 0xFE;
+        let (mut cpu, mut mem) = machine(&changed);
+        let error = Player::new(recording.clone())
+            .run(&mut cpu, &mut mem)
+            .unwrap_err();
+        assert_eq!(error.cycle, 0);
+
+        //Differences after the last input are found by the final hash
+        let (mut cpu, mut mem) = machine(&program());
+        let mut player = Player::new(recording);
+        for _ in 0..4 {
+            player.step(&mut cpu, &mut mem).unwrap();
+        }
+        mem[0x300] = 0xEE;
+        let error = player.run(&mut cpu, &mut mem).unwrap_err();
+        assert_eq!(error.cycle, 23);
+        assert_eq!(error.message, "final machine state differs");
+    }
+
+    #[test]
+    fn invalid_recordings() {
+        let parse = |text: &str| text.parse::<Recording>().unwrap_err();
+        assert_eq!(parse("inputs"), RecordingError::NotARecording);
+        assert_eq!(
+            parse("rusty_6502 recording 2\nend 0 hash 0").to_string(),
+            "Recording version 2 is newer than the supported version 1"
+        );
+        assert_eq!(
+            parse("rusty_6502 recording 1\n10 key $41 hash 0").to_string(),
+            "line 2: Unknown input 'key'"
+        );
+        assert_eq!(
+            parse("rusty_6502 recording 1\n10 write $10000 $41 hash 0").to_string(),
+            "line 2: Invalid address"
+        );
+        assert_eq!(
+            parse("rusty_6502 recording 1\n10 nmi\n"),
+            RecordingError::InvalidLine {
+                line: 2,
+                message: "Expected 'hash' and the state hash".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("rusty_6502 recording 1\n10 nmi hash 0\n"),
+            RecordingError::MissingEnd
+        );
+    }
+}
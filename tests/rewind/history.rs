@@ -0,0 +1,112 @@
+mod history_tests {
+    use rusty_6502::{
+        asm6502,
+        cpu::CPU,
+        debugger::MessageType,
+        mem::MEM,
+        rewind::{History, Write, WriteRecord},
+    };
+
+    fn machine(program: &[u8]) -> (CPU<impl FnOnce(MessageType) + Copy>, MEM) {
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(|_: MessageType| {});
+        cpu.reset(0x600, &mut mem);
+        mem.load(0x600, program);
+        (cpu, mem)
+    }
+
+    #[test]
+    fn step_back() {
+        let (mut cpu, mut mem) = machine(&asm6502! { LDX #$05; STX $10; INC $10; DEX; STX $10 });
+        let mut history = History::new(1 << 20);
+        assert!(!history.step_back(&mut cpu, &mut mem));
+        for _ in 0..5 {
+            history.step(&mut cpu, &mut mem);
+        }
+        assert_eq!((history.index(), history.cycles()), (5, 15));
+        assert_eq!((cpu.X, mem[0x10]), (0x04, 0x04));
+
+        assert!(history.step_back(&mut cpu, &mut mem));
+        assert_eq!((cpu.PC, cpu.X, mem[0x10]), (0x0607, 0x04, 0x06));
+        assert!(history.step_back(&mut cpu, &mut mem));
+        assert_eq!((cpu.PC, cpu.X, history.cycles()), (0x0606, 0x05, 10));
+
+        //Execution continues from the earlier state
+        history.step(&mut cpu, &mut mem);
+        history.step(&mut cpu, &mut mem);
+        assert_eq!((cpu.PC, mem[0x10], history.index()), (0x0609, 0x04, 5));
+    }
+
+    #[test]
+    fn run_back_and_writes() {
+        let (mut cpu, mut mem) =
+            machine(&asm6502! { LDA #$01; STA $0200; INX; INC $0200; INY; INX });
+        let mut history = History::new(1 << 20);
+        for _ in 0..6 {
+            history.step(&mut cpu, &mut mem);
+        }
+        assert_eq!(
+            history.last_write(0x200),
+            Some(WriteRecord {
+                index: 3,
+                pc: 0x0606,
+                write: Write {
+                    address: 0x200,
+                    old: 0x01,
+                    new: 0x02,
+                },
+            })
+        );
+        assert_eq!(history.last_write(0x300), None);
+
+        let record = history
+            .back_to_last_write(&mut cpu, &mut mem, 0x200)
+            .unwrap();
+        assert_eq!((cpu.PC, mem[0x200], history.index()), (record.pc, 0x01, 3));
+        assert!(history.run_back_to(&mut cpu, &mut mem, 0x0602));
+        assert_eq!((cpu.PC, cpu.A, mem[0x200]), (0x0602, 0x01, 0x00));
+        assert!(!history.run_back_to(&mut cpu, &mut mem, 0x0700));
+        assert_eq!(cpu.PC, 0x0602);
+    }
+
+    #[test]
+    fn unchanged_writes_are_journaled() {
+        let (mut cpu, mut mem) = machine(&asm6502! { LDA #$00; STA $0200 });
+        let mut history = History::new(1 << 20);
+        history.step(&mut cpu, &mut mem);
+        history.step(&mut cpu, &mut mem);
+        assert_eq!(
+            history.last_write(0x200).map(|record| record.write),
+            Some(Write {
+                address: 0x200,
+                old: 0x00,
+                new: 0x00,
+            })
+        );
+    }
+
+    #[test]
+    fn budget_and_snapshots() {
+        let (mut cpu, mut mem) = machine(&[0xE8; 200]);
+        let budget = 66_000;
+        let mut history = History::new(budget);
+        history.interval(50);
+        for _ in 0..200 {
+            history.step(&mut cpu, &mut mem);
+            assert!(history.memory_used() <= budget);
+        }
+        assert_eq!(cpu.X, 200);
+
+        //Only the newest snapshot fits, older states are gone
+        assert_eq!(history.oldest(), 150);
+        assert!(!history.seek(&mut cpu, &mut mem, 149));
+        assert_eq!(cpu.X, 200);
+
+        //States before the journal are executed again from the snapshot
+        assert!(history.seek(&mut cpu, &mut mem, 160));
+        assert_eq!((cpu.X, cpu.PC, history.index()), (160, 0x06A0, 160));
+        assert!(history.step_back(&mut cpu, &mut mem));
+        assert_eq!(cpu.X, 159);
+        assert!(!history.seek(&mut cpu, &mut mem, 170));
+    }
+}
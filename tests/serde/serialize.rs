@@ -0,0 +1,69 @@
+mod serialize_tests {
+    use rusty_6502::{
+        asm::{AddrCode, AddrMode, Instructions},
+        cpu::{CpuState, StatusFlags},
+        debugger::MessageType,
+        mem::MEM,
+    };
+
+    #[test]
+    fn cpu_state() {
+        let state = CpuState {
+            A: 0x42,
+            status_flags: StatusFlags::from_bits(0xA1),
+            ..CpuState::reset(0x600)
+        };
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(
+            json,
+            "{\"PC\":1536,\"SR\":255,\"SP\":255,\"A\":66,\"X\":0,\"Y\":0,\"status_flags\":\
+             {\"N\":1,\"V\":0,\"U\":1,\"I\":0,\"B\":0,\"D\":0,\"Z\":0,\"C\":1}}"
+        );
+        assert_eq!(serde_json::from_str::<CpuState>(&json).unwrap(), state);
+    }
+
+    #[test]
+    fn memory() {
+        let mut mem = MEM::new();
+        mem.load(0x600, &[0xA9, 0x01, 0, 0, 0x8D, 0x00, 0x02]);
+        mem.load(0xFFFC, &[0x00, 0x06]);
+        let json = serde_json::to_string(&mem).unwrap();
+        assert_eq!(
+            json,
+            "[{\"address\":1536,\"data\":\"A90100008D0002\"},{\"address\":65533,\"data\":\"06\"}]"
+        );
+        let read: MEM = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.data, mem.data);
+
+        assert!(serde_json::from_str::<MEM>("[{\"address\":65535,\"data\":\"0102\"}]").is_err());
+        assert!(serde_json::from_str::<MEM>("[{\"address\":0,\"data\":\"0G\"}]").is_err());
+    }
+
+    #[test]
+    fn instructions() {
+        let instruction = Instructions::resolve(0xA9);
+        let json = serde_json::to_string(&instruction).unwrap();
+        assert_eq!(
+            json,
+            "{\"LDA\":{\"Immediate\":{\"cycles\":2,\"opcode\":169}}}"
+        );
+        assert_eq!(
+            serde_json::from_str::<Instructions>(&json).unwrap(),
+            instruction
+        );
+        assert_eq!(
+            serde_json::from_str::<AddrMode>("{\"Implied\":{\"cycles\":2,\"opcode\":234}}")
+                .unwrap(),
+            AddrMode::Implied(AddrCode {
+                cycles: 2,
+                opcode: 0xEA
+            })
+        );
+        let message = serde_json::to_string(&MessageType::LineExecuted(0xEA, 2)).unwrap();
+        assert_eq!(message, "{\"LineExecuted\":[234,2]}");
+        assert!(matches!(
+            serde_json::from_str(&message).unwrap(),
+            MessageType::LineExecuted(0xEA, 2)
+        ));
+    }
+}
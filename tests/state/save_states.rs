@@ -0,0 +1,103 @@
+mod save_states_tests {
+    use std::fs::File;
+
+    use rusty_6502::{
+        asm6502,
+        cpu::CPU,
+        debugger::MessageType,
+        mem::MEM,
+        state::{self, SaveState, StateError, MAGIC, VERSION},
+    };
+
+    fn saved() -> Vec<u8> {
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(|_: MessageType| {});
+        cpu.reset(0x600, &mut mem);
+        mem.load(0x600, &asm6502! { LDA #$80; STA $0200; SEC });
+        let (cycles, _) = cpu.execute_continuous(&mut mem);
+        SaveState::capture(&cpu, &mem, cycles as u64).to_bytes()
+    }
+
+    #[test]
+    fn save_and_load() {
+        let bytes = saved();
+        assert_eq!(&bytes[..8], b"R6502SAV");
+        assert_eq!(&bytes[8..12], [0x01, 0x00, 0x03, 0x00]);
+        assert_eq!(&bytes[12..20], b"CPU \x08\x00\x00\x00");
+
+        let path = std::env::temp_dir().join("rusty_6502_save_and_load.state");
+        let state = SaveState::from_bytes(&bytes).unwrap();
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(|_: MessageType| {});
+        state::save_state(File::create(&path).unwrap(), &cpu, &mem, 7).unwrap();
+        state.restore(&mut cpu, &mut mem);
+        assert_eq!(mem[0x200], 0x80);
+        assert_eq!(
+            (cpu.A, cpu.status_flags.N, cpu.status_flags.C),
+            (0x80, 1, 1)
+        );
+        assert_eq!(SaveState::capture(&cpu, &mem, state.cycles), state);
+
+        //Loading the empty state saved before restoring
+        let cycles = state::load_state(File::open(&path).unwrap(), &mut cpu, &mut mem).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(cycles, 7);
+        assert_eq!((cpu.A, mem[0x200]), (0, 0));
+    }
+
+    #[test]
+    fn versions() {
+        //Newer versions are refused
+        let mut bytes = saved();
+        bytes[8] = VERSION as u8 + 1;
+        let error = SaveState::from_bytes(&bytes).unwrap_err();
+        assert!(matches!(
+            error,
+            StateError::UnsupportedVersion {
+                found: 2,
+                supported: 1
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Save state version 2 is newer than the supported version 1"
+        );
+
+        //Unknown chunks are skipped
+        let mut bytes = saved();
+        bytes[10] = 4;
+        bytes.extend(b"DEV \x02\x00\x00\x00\xAA\xBB");
+        assert_eq!(
+            SaveState::from_bytes(&bytes).unwrap(),
+            SaveState::from_bytes(&saved()).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_states() {
+        let bytes = saved();
+        assert!(matches!(
+            SaveState::from_bytes(b"R6502"),
+            Err(StateError::NotASaveState)
+        ));
+        assert!(matches!(
+            SaveState::from_bytes(&bytes[..1000]),
+            Err(StateError::Truncated)
+        ));
+
+        let mut header = MAGIC.to_vec();
+        header.extend([0x01, 0x00, 0x01, 0x00]);
+        let mut short = header.clone();
+        short.extend(b"CYC \x02\x00\x00\x00\x00\x00");
+        assert_eq!(
+            SaveState::from_bytes(&short).unwrap_err().to_string(),
+            "Chunk 'CYC ' has a invalid size of 2 bytes"
+        );
+        let mut empty = header;
+        empty.extend(b"CYC \x08\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00");
+        assert_eq!(
+            SaveState::from_bytes(&empty).unwrap_err().to_string(),
+            "Save state has no 'CPU ' chunk"
+        );
+    }
+}
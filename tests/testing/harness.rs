@@ -0,0 +1,66 @@
+mod harness_tests {
+    use rusty_6502::{
+        asm6502,
+        cpu::CpuState,
+        testing::{Register, TestCase},
+    };
+
+    #[test]
+    fn passing_case() {
+        let outcome = TestCase::new(CpuState::reset(0x600))
+            .program(&asm6502! { LDX #$01; STX $00; LDA $00 })
+            .expect(Register::A, 0x01)
+            .expect(Register::X, 0x01)
+            .expect_memory(0x0000, &[0x01])
+            .expect_cycles(8)
+            .assert();
+        assert_eq!(outcome.state.PC, 0x606);
+        assert_eq!(outcome.instructions, 3);
+    }
+
+    #[test]
+    fn initial_state_and_patches() {
+        TestCase::new(CpuState {
+            Y: 0x02,
+            ..CpuState::reset(0x600)
+        })
+        .patch(0x0302, &[0x7F])
+        .patch(0x0600, &[0xEA])
+        .program(&asm6502! { LDA $0300,Y; INY })
+        .expect(Register::A, 0x7F)
+        .expect(Register::Y, 0x03)
+        .assert();
+    }
+
+    #[test]
+    fn failure_report() {
+        let failure = TestCase::new(CpuState::reset(0x600))
+            .program(&asm6502! { LDA #$80; STA $10 })
+            .expect(Register::A, 0x81)
+            .expect_cycles(4)
+            .run()
+            .unwrap_err();
+        let report = failure.to_string();
+        assert!(report.starts_with("State differs from the expected state\n"));
+        assert!(report.contains("\n  PC     $0600     *         $0604\n"));
+        assert!(report.contains("\n! A      $00       $81       $80\n"));
+        //Flags which are not expected must be unchanged
+        assert!(report.contains("\n! N      0         0         1\n"));
+        assert!(report.contains("\n  Z      0         0         0\n"));
+        assert!(report.contains("\n! cycles           4         5\n"));
+        assert!(report.ends_with("memory\n! $0010  $00       $00       $80\n"));
+    }
+
+    #[test]
+    fn instruction_limit() {
+        let failure = TestCase::new(CpuState::reset(0x600))
+            .program(&asm6502! { INX; INX; INX })
+            .max_instructions(2)
+            .run()
+            .unwrap_err();
+        assert_eq!(
+            failure.report,
+            "Program did not finish in 2 instructions, PC is $0602"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+mod compare_tests {
+    use rusty_6502::trace::{self, CompareOptions, TraceEntry};
+
+    const REFERENCE: &str = "\
+C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+C5F5  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 30 CYC:10
+C5F7  86 00     STX $00 = 00                    A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 36 CYC:12
+C5F9  86 10     STX $10 = 00                    A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 45 CYC:15
+C5FB  86 11     STX $11 = 00                    A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 54 CYC:18
+";
+
+    #[test]
+    fn matching_traces() {
+        //The disassembly and the PPU column are not compared
+        let actual = REFERENCE
+            .replace("STX $00 = 00", "STX $00")
+            .replace("PPU:  0, 21 ", "");
+        assert_eq!(
+            trace::compare_traces(&actual, REFERENCE, &CompareOptions::default()),
+            None
+        );
+        assert_eq!(trace::parse_trace(&format!("\n{}", REFERENCE))[1].line, 3);
+        assert_eq!(TraceEntry::parse(1, "not a trace line"), None);
+    }
+
+    #[test]
+    fn first_divergence() {
+        let actual = REFERENCE
+            .replace(
+                "P:26 SP:FD PPU:  0, 45 CYC:15",
+                "P:A6 SP:FC PPU:  0, 45 CYC:16",
+            )
+            .replace(
+                "A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 54",
+                "A:01 X:00 Y:00 P:26 SP:FD PPU:  0, 54",
+            );
+        let options = CompareOptions {
+            context: 2,
+            ..CompareOptions::default()
+        };
+        let divergence = trace::compare_traces(&actual, REFERENCE, &options).unwrap();
+        assert_eq!(divergence.index, 3);
+        assert_eq!(divergence.fields, ["P", "SP", "CYC"]);
+        assert_eq!(divergence.expected.as_ref().unwrap().line, 4);
+        assert_eq!(divergence.actual.as_ref().unwrap().sp, 0xFC);
+        assert_eq!(
+            divergence.to_string(),
+            format!(
+                "Traces diverge at instruction 4 in P, SP, CYC\n  {}\n  {}\n- {} (line 4)\n+ {} (line 4)\n",
+                REFERENCE.lines().nth(1).unwrap(),
+                REFERENCE.lines().nth(2).unwrap(),
+                REFERENCE.lines().nth(3).unwrap(),
+                actual.lines().nth(3).unwrap()
+            )
+        );
+
+        //Masked status bits and cycles can be ignored
+        let options = CompareOptions {
+            cycles: false,
+            status_mask: 0x7F,
+            ..CompareOptions::default()
+        };
+        let divergence = trace::compare_traces(&actual, REFERENCE, &options).unwrap();
+        assert_eq!(divergence.fields, ["SP"]);
+    }
+
+    #[test]
+    fn shorter_trace() {
+        let actual: String = REFERENCE
+            .lines()
+            .take(2)
+            .map(|line| line.to_string() + "\n")
+            .collect();
+        let divergence =
+            trace::compare_traces(&actual, REFERENCE, &CompareOptions::default()).unwrap();
+        assert_eq!(divergence.index, 2);
+        assert_eq!(divergence.actual, None);
+        assert!(divergence.fields.is_empty());
+        assert!(divergence
+            .to_string()
+            .ends_with("(line 3)\n+ <end of trace>\n"));
+    }
+}
@@ -0,0 +1,81 @@
+mod tracer_tests {
+    use rusty_6502::{
+        asm6502,
+        cpu::{CpuState, CPU},
+        debugger::MessageType,
+        mem::MEM,
+        trace::{self, Tracer},
+    };
+
+    fn trace(program: &[u8], configure: impl Fn(&mut Tracer<Vec<u8>>)) -> Vec<String> {
+        let mut mem = MEM::new();
+        let mut cpu = CPU::new(|_: MessageType| {});
+        cpu.reset(0x600, &mut mem);
+        mem.load(0x600, program);
+        let mut tracer = Tracer::new(Vec::new());
+        configure(&mut tracer);
+        tracer.run(&mut cpu, &mut mem, program.len()).unwrap();
+        String::from_utf8(tracer.into_inner())
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn trace_lines() {
+        let lines = trace(
+            &asm6502! { LDA #$80; STA $0200; LDX $0200; DEX; SEC },
+            |tracer| {
+                tracer.cycles(7);
+            },
+        );
+        assert_eq!(
+            lines,
+            [
+                "0600  A9 80     LDA #$80                        A:00 X:00 Y:00 P:20 SP:FF CYC:7",
+                "0602  8D 00 02  STA $0200                       A:80 X:00 Y:00 P:A0 SP:FF CYC:9",
+                "0605  AE 00 02  LDX $0200                       A:80 X:00 Y:00 P:A0 SP:FF CYC:13",
+                "0608  CA        DEX                             A:80 X:80 Y:00 P:A0 SP:FF CYC:17",
+                "0609  38        SEC                             A:80 X:7F Y:00 P:20 SP:FF CYC:19",
+                "060A  00        BRK                             A:80 X:7F Y:00 P:21 SP:FF CYC:21",
+            ]
+        );
+    }
+
+    #[test]
+    fn filters() {
+        let program = asm6502! { INX; INX; INX; INX; INX; INX };
+        let addresses = |lines: Vec<String>| -> Vec<String> {
+            lines.iter().map(|line| line[..4].to_string()).collect()
+        };
+        assert_eq!(
+            addresses(trace(&program, |tracer| {
+                tracer.range(0x602..=0x604);
+            })),
+            ["0602", "0603", "0604"]
+        );
+        assert_eq!(
+            addresses(trace(&program, |tracer| {
+                tracer.range(0x601..=0x605).skip(1).limit(2);
+            })),
+            ["0602", "0603"]
+        );
+
+        //Cycles are counted for instructions which are not written
+        let lines = trace(&program, |tracer| {
+            tracer.skip(5);
+        });
+        assert!(lines[0].ends_with("X:05 Y:00 P:20 SP:FF CYC:10"));
+    }
+
+    #[test]
+    fn undecodable_bytes() {
+        let mut mem = MEM::new();
+        mem.load(0xFFFF, &[0x02]);
+        assert_eq!(
+            trace::trace_line(&CpuState::reset(0xFFFF), &mem, 0),
+            "FFFF  02        .byte $02                       A:00 X:00 Y:00 P:20 SP:FF CYC:0"
+        );
+    }
+}